@@ -12,6 +12,10 @@ pub fn derive_sfx_id(input: TokenStream) -> TokenStream {
 pub fn derive_music_id(input: TokenStream) -> TokenStream {
     derive_id(input, "MusicId")
 }
+#[proc_macro_derive(AmbienceId)]
+pub fn derive_ambience_id(input: TokenStream) -> TokenStream {
+    derive_id(input, "AmbienceId")
+}
 #[proc_macro_derive(InputAction)]
 pub fn derive_input_action(input: TokenStream) -> TokenStream {
     derive_id(input, "InputAction")
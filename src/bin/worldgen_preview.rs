@@ -0,0 +1,427 @@
+//! Generates a region of the world without starting the game, and writes it to disk as images.
+//!
+//! This makes it possible to tune the noise graphs and biome selection without launching the
+//! game over and over. No window is opened and no GPU is needed. For example:
+//!
+//! `cargo run --release --bin worldgen_preview -- --seed 42 --chunk 0,0,0 --radius 6`
+//!
+//! This writes elevation.ppm, biome.ppm, humidity.ppm and temperature.ppm to the output directory,
+//! together with stats.txt, which contains the biome distribution and an elevation histogram.
+//! Every pixel is colored after the hex column it falls in, so the images show the actual hexes.
+//! Features such as trees are not placed, so the elevation is that of the bare terrain.
+#![forbid(unsafe_code)]
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use bevy::math::{Vec3, Vec3Swizzles};
+
+use hex::game::hex_grid::axial::{ChunkId, ColumnId, Pos, RADIUS};
+use hex::game::hex_grid::biomes::BiomeType;
+use hex::game::hex_grid::block::BlockType;
+use hex::game::hex_grid::chunk::ChunkDimensions;
+use hex::game::hex_grid::templates::{StructureLibrary, StructureTemplate};
+use hex::game::procedural_generation::noise_graph::{HUMIDITY_GRAPH, TEMPERATURE_GRAPH};
+use hex::game::procedural_generation::world_generator::WorldGenerator;
+use hex::io::asset_loading::{MetaAsset, ModDirectories};
+use hex::io::config::{Config, WorldGenConfig};
+
+const USAGE: &str = "Usage: worldgen_preview [options]
+    --seed <u32>                Overrides the seed from the worldgen config.
+    --chunk <q,r,z>             The chunk at the center of the region. Default: 0,0,0
+    --radius <u32>              The radius of the region, in chunks. Default: 4
+    --layers <u32>              The number of layers of chunks to generate, upwards. Default: 1
+    --pixels-per-meter <f32>    The resolution of the images. Default: 4
+    --mod <dir>                 The mod to read configs and structures from. Default: assets/default
+    --out <dir>                 The directory to write the images to. Default: worldgen_preview";
+
+/// The number of bars in the elevation histogram.
+const HISTOGRAM_BUCKETS: usize = 16;
+/// The width of the longest bar in the elevation histogram, in characters.
+const HISTOGRAM_WIDTH: usize = 50;
+
+struct Options {
+    seed: Option<u32>,
+    center: ChunkId,
+    radius: u32,
+    layers: u32,
+    pixels_per_meter: f32,
+    mod_dir: PathBuf,
+    out_dir: PathBuf,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options {
+            seed: None,
+            center: ChunkId::splat(0),
+            radius: 4,
+            layers: 1,
+            pixels_per_meter: 4.,
+            mod_dir: PathBuf::from("assets/default"),
+            out_dir: PathBuf::from("worldgen_preview"),
+        };
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("Missing value for {}", arg));
+            match arg.as_str() {
+                "--seed" => options.seed = Some(parse(&value()?)?),
+                "--chunk" => {
+                    let value = value()?;
+                    let coordinates = value
+                        .split(',')
+                        .map(parse::<i32>)
+                        .collect::<Result<Vec<i32>, String>>()?;
+                    if let [q, r, z] = coordinates[..] {
+                        options.center = ChunkId::new(q, r, z);
+                    } else {
+                        return Err(format!("Expected a chunk as q,r,z but got {}", value));
+                    }
+                }
+                "--radius" => options.radius = parse(&value()?)?,
+                "--layers" => options.layers = parse::<u32>(&value()?)?.max(1),
+                "--pixels-per-meter" => options.pixels_per_meter = parse(&value()?)?,
+                "--mod" => options.mod_dir = PathBuf::from(value()?),
+                "--out" => options.out_dir = PathBuf::from(value()?),
+                _ => return Err(format!("Unknown argument {}", arg)),
+            }
+        }
+        if options.pixels_per_meter <= 0. {
+            return Err("The number of pixels per meter must be positive.".to_string());
+        }
+        Ok(options)
+    }
+}
+
+fn parse<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("Could not parse '{}'", value))
+}
+
+/// Everything that is known about a single column in the previewed region.
+#[derive(Copy, Clone)]
+struct ColumnSample {
+    /// The absolute height of the highest opaque block, or None if there is none.
+    ground: Option<i32>,
+    /// The absolute height of the water surface, if the column is under water.
+    water: Option<i32>,
+    biome: BiomeType,
+    humidity: f64,
+    temperature: f64,
+}
+
+fn main() {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            std::process::exit(2);
+        }
+    };
+    if let Err(err) = run(&options) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+fn run(options: &Options) -> Result<(), Box<dyn Error>> {
+    let mut worldgen = read_worldgen_config(&options.mod_dir)?;
+    if let Some(seed) = options.seed {
+        worldgen.seed = seed;
+    }
+    let dimensions = read_chunk_dimensions(&options.mod_dir)?;
+    let structures = read_structures(&options.mod_dir)?;
+    let mods = ModDirectories(vec![options.mod_dir.clone()]);
+    let generator = worldgen
+        .preset
+        .create_generator(&worldgen, &structures, &mods);
+
+    let chunk_ids = options.center.range(options.radius);
+    println!(
+        "Generating {} chunks around {:?} with seed {}...",
+        chunk_ids.len() * options.layers as usize,
+        options.center,
+        worldgen.seed
+    );
+    let samples = sample_region(
+        &chunk_ids,
+        options.layers,
+        dimensions,
+        &worldgen,
+        &*generator,
+    );
+
+    fs::create_dir_all(&options.out_dir)?;
+    let raster = Raster::new(&samples, options.pixels_per_meter);
+    let (lowest, highest) = elevation_range(&samples);
+    raster.write(&options.out_dir.join("elevation.ppm"), |sample| {
+        elevation_color(sample, lowest, highest)
+    })?;
+    raster.write(&options.out_dir.join("biome.ppm"), |sample| {
+        biome_color(sample.biome)
+    })?;
+    raster.write(&options.out_dir.join("humidity.ppm"), |sample| {
+        gray(sample.humidity)
+    })?;
+    raster.write(&options.out_dir.join("temperature.ppm"), |sample| {
+        gray(sample.temperature)
+    })?;
+
+    let stats = statistics(&samples, lowest, highest);
+    print!("{}", stats);
+    fs::write(options.out_dir.join("stats.txt"), stats)?;
+    println!("Wrote the preview to {}", options.out_dir.display());
+    Ok(())
+}
+
+fn read_worldgen_config(mod_dir: &Path) -> Result<WorldGenConfig, Box<dyn Error>> {
+    let path = mod_dir.join("config/worldgen.config.ron");
+    match ron::from_str::<Config>(&fs::read_to_string(&path)?)? {
+        Config::WorldGen(config) => Ok(*config),
+        _ => Err(format!("{} is not a worldgen config.", path.display()).into()),
+    }
+}
+
+fn read_chunk_dimensions(mod_dir: &Path) -> Result<ChunkDimensions, Box<dyn Error>> {
+    let path = mod_dir.join("config/world.config.ron");
+    match ron::from_str::<Config>(&fs::read_to_string(&path)?)? {
+        Config::World(config) => Ok(config.chunk_dimensions),
+        _ => Err(format!("{} is not a world config.", path.display()).into()),
+    }
+}
+
+/// Reads all structure templates listed in the file structure, looking for them in the given mod.
+fn read_structures(mod_dir: &Path) -> Result<StructureLibrary, Box<dyn Error>> {
+    let meta = ron::from_str::<MetaAsset>(&fs::read_to_string("assets/file_structure.meta.ron")?)?;
+    let mut templates = HashMap::new();
+    for (id, path) in meta.as_file_structure().structures.iter() {
+        let path = mod_dir.join(path);
+        if let Ok(ron) = fs::read_to_string(&path) {
+            templates.insert(id.clone(), StructureTemplate::from_ron(&ron)?);
+        }
+    }
+    Ok(StructureLibrary::new(templates))
+}
+
+/// Generates the chunks, spread over all available threads, and samples every column in them.
+fn sample_region(
+    chunk_ids: &[ChunkId],
+    layers: u32,
+    dimensions: ChunkDimensions,
+    worldgen: &WorldGenConfig,
+    generator: &dyn WorldGenerator,
+) -> HashMap<ColumnId, ColumnSample> {
+    let humidity = worldgen.graph(HUMIDITY_GRAPH);
+    let temperature = worldgen.graph(TEMPERATURE_GRAPH);
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let batch_size = (chunk_ids.len() + threads - 1) / threads;
+    thread::scope(|scope| {
+        let handles = chunk_ids
+            .chunks(batch_size.max(1))
+            .map(|batch| {
+                let (humidity, temperature) = (&humidity, &temperature);
+                scope.spawn(move || {
+                    let mut samples = HashMap::new();
+                    for chunk_id in batch {
+                        sample_chunk_stack(chunk_id, layers, dimensions, generator, &mut samples);
+                    }
+                    for (column, sample) in samples.iter_mut() {
+                        let xy = column.as_ipos(0).as_xyz().xy().as_dvec2();
+                        sample.humidity = humidity.get(xy);
+                        sample.temperature = temperature.get(xy);
+                    }
+                    samples
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("A preview thread panicked."))
+            .collect()
+    })
+}
+
+/// Generates the chunk and the layers above it, and samples their columns from the top down.
+fn sample_chunk_stack(
+    chunk_id: &ChunkId,
+    layers: u32,
+    dimensions: ChunkDimensions,
+    generator: &dyn WorldGenerator,
+    samples: &mut HashMap<ColumnId, ColumnSample>,
+) {
+    for layer in (0..layers as i32).rev() {
+        let layer_id = chunk_id.delta(0, 0, layer);
+        let chunk = generator.generate_chunk(&layer_id, dimensions);
+        let center = layer_id.center_pos(&dimensions);
+        for column in chunk.columns() {
+            let absolute = (column + center).as_column_id();
+            let to_absolute = |z: Option<i32>| z.map(|z| z + center.z());
+            let ground = to_absolute(chunk.opaque_height(&column).unwrap_or(None));
+            let surface = to_absolute(chunk.surface_height(&column).unwrap_or(None));
+            let sample = samples.entry(absolute).or_insert(ColumnSample {
+                ground: None,
+                water: None,
+                biome: chunk.block(&column.as_ipos(0)).biome_type,
+                humidity: 0.,
+                temperature: 0.,
+            });
+            // Higher layers were sampled first, so anything found there takes precedence:
+            if sample.ground.is_none() {
+                sample.ground = ground;
+            }
+            if sample.water.is_none() {
+                sample.water = surface.filter(|surface| {
+                    let top = chunk.block(&column.as_ipos(surface - center.z()));
+                    top.block_type == BlockType::Water
+                });
+            }
+        }
+    }
+}
+
+/// Maps pixels to the hex columns they fall in.
+struct Raster {
+    width: usize,
+    height: usize,
+    /// For each pixel, row by row, the column it falls in, if that column was sampled.
+    pixels: Vec<Option<ColumnSample>>,
+}
+
+impl Raster {
+    fn new(samples: &HashMap<ColumnId, ColumnSample>, pixels_per_meter: f32) -> Self {
+        let points = samples
+            .keys()
+            .map(|column| column.as_ipos(0).as_xyz().xy())
+            .collect::<Vec<_>>();
+        let min = points.iter().fold(points[0], |min, point| min.min(*point)) - RADIUS;
+        let max = points.iter().fold(points[0], |max, point| max.max(*point)) + RADIUS;
+        let size = (max - min) * pixels_per_meter;
+        let (width, height) = (size.x.ceil() as usize, size.y.ceil() as usize);
+        let mut pixels = Vec::with_capacity(width * height);
+        for row in 0..height {
+            for col in 0..width {
+                // Images go top to bottom, while the world's y-axis points up:
+                let x = min.x + (col as f32 + 0.5) / pixels_per_meter;
+                let y = max.y - (row as f32 + 0.5) / pixels_per_meter;
+                let column = Pos::from_xyz(&Vec3::new(x, y, 0.))
+                    .as_ipos_round()
+                    .as_column_id();
+                pixels.push(samples.get(&column).copied());
+            }
+        }
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Writes the raster as a binary PPM image. Pixels outside of the region are black.
+    fn write(&self, path: &Path, color: impl Fn(&ColumnSample) -> [u8; 3]) -> std::io::Result<()> {
+        let mut bytes = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        for pixel in self.pixels.iter() {
+            bytes.extend(pixel.as_ref().map_or([0, 0, 0], &color));
+        }
+        fs::write(path, bytes)
+    }
+}
+
+fn elevation_range(samples: &HashMap<ColumnId, ColumnSample>) -> (i32, i32) {
+    let heights = samples.values().filter_map(|sample| sample.ground);
+    let lowest = heights.clone().min().unwrap_or(0);
+    let highest = heights.max().unwrap_or(0);
+    (lowest, highest)
+}
+
+fn gray(value: f64) -> [u8; 3] {
+    let value = (value.clamp(0., 1.) * 255.) as u8;
+    [value, value, value]
+}
+
+/// Dry land is shaded from black (lowest) to white (highest). Water is shaded blue,
+/// darker where it is deeper.
+fn elevation_color(sample: &ColumnSample, lowest: i32, highest: i32) -> [u8; 3] {
+    let range = (highest - lowest).max(1) as f64;
+    let ground = sample.ground.unwrap_or(lowest);
+    match sample.water {
+        Some(water) => {
+            let depth = ((water - ground) as f64 / range * 4.).clamp(0., 1.);
+            [0, (80. * (1. - depth)) as u8, (255. - 128. * depth) as u8]
+        }
+        None => gray((ground - lowest) as f64 / range),
+    }
+}
+
+fn biome_color(biome: BiomeType) -> [u8; 3] {
+    match biome {
+        BiomeType::BorealForest => [34, 85, 51],
+        BiomeType::Desert => [237, 201, 175],
+        BiomeType::Forest => [34, 139, 34],
+        BiomeType::Grassland => [124, 200, 80],
+        BiomeType::Ice => [220, 240, 255],
+        BiomeType::Jungle => [0, 100, 0],
+        BiomeType::Savanna => [200, 180, 80],
+        BiomeType::Swamp => [90, 110, 60],
+        BiomeType::Tundra => [160, 170, 150],
+    }
+}
+
+/// Summarises the biome distribution and the elevation of the region as plain text.
+fn statistics(samples: &HashMap<ColumnId, ColumnSample>, lowest: i32, highest: i32) -> String {
+    let total = samples.len().max(1) as f64;
+    let mut text = String::new();
+    let _ = writeln!(text, "Columns: {}", samples.len());
+
+    let mut biomes = HashMap::<BiomeType, usize>::new();
+    for sample in samples.values() {
+        *biomes.entry(sample.biome).or_default() += 1;
+    }
+    let mut biomes = biomes.into_iter().collect::<Vec<_>>();
+    biomes.sort_by_key(|(_, count)| Reverse(*count));
+    let _ = writeln!(text, "\nBiome distribution:");
+    for (biome, count) in biomes {
+        let percentage = count as f64 / total * 100.;
+        let _ = writeln!(
+            text,
+            "  {:<14}{:>8}  {:>5.1}%",
+            format!("{:?}", biome),
+            count,
+            percentage
+        );
+    }
+
+    let water = samples
+        .values()
+        .filter(|sample| sample.water.is_some())
+        .count();
+    let _ = writeln!(
+        text,
+        "\nUnder water: {} ({:.1}%)",
+        water,
+        water as f64 / total * 100.
+    );
+
+    let _ = writeln!(text, "\nElevation ({} to {}):", lowest, highest);
+    let bucket_size = ((highest - lowest + 1) as f64 / HISTOGRAM_BUCKETS as f64).max(1.);
+    let mut buckets = [0usize; HISTOGRAM_BUCKETS];
+    for ground in samples.values().filter_map(|sample| sample.ground) {
+        let bucket = ((ground - lowest) as f64 / bucket_size) as usize;
+        buckets[bucket.min(HISTOGRAM_BUCKETS - 1)] += 1;
+    }
+    let largest = buckets.iter().copied().max().unwrap_or(0).max(1);
+    for (index, count) in buckets.iter().enumerate() {
+        let from = lowest as f64 + index as f64 * bucket_size;
+        if from > highest as f64 {
+            break;
+        }
+        let bar = "#".repeat(count * HISTOGRAM_WIDTH / largest);
+        let _ = writeln!(text, "  {:>6.1} {:>8}  {}", from, count, bar);
+    }
+    text
+}
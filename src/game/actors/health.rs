@@ -0,0 +1,156 @@
+use bevy::prelude::*;
+use bevy::time::TimerMode::Once;
+
+use crate::game::actors::monster::MonsterBehaviour;
+use crate::game::actors::spawn_point::SpawnPoint;
+use crate::game::actors::structs::Player;
+use crate::game::movement::structs::{LandedEvent, MoveState};
+
+/// How long the player can't be damaged after respawning, in seconds.
+pub const RESPAWN_INVULNERABILITY_SECONDS: f32 = 3.;
+
+#[derive(Component)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self::new(100.)
+    }
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.
+    }
+}
+
+/// The damage an actor deals with each of its attacks.
+#[derive(Component, Default)]
+pub struct Damage {
+    pub amount: f32,
+}
+
+/// Apply this component to entities that should take damage when they land too hard.
+#[derive(Component, Default)]
+pub struct FallDamage {
+    /// Landing at or below this speed (in meters per second) doesn't hurt.
+    pub safe_speed: f32,
+    /// Damage taken for each meter per second of impact speed above the safe speed.
+    pub damage_per_speed: f32,
+}
+
+/// While this component is present, the entity doesn't take any damage.
+#[derive(Component)]
+pub struct Invulnerable {
+    pub timer: Timer,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DamageSource {
+    Fall,
+    /// Attacked by the given entity.
+    Attack(Entity),
+}
+
+/// Send this event to damage an entity that has Health.
+pub struct DamageEvent {
+    pub target: Entity,
+    pub amount: f32,
+    pub source: DamageSource,
+}
+
+/// Sent when an entity's health drops to zero.
+pub struct DeathEvent {
+    pub entity: Entity,
+}
+
+/// Converts hard landings into fall damage.
+pub fn fall_damage_system(
+    mut landed_events: EventReader<LandedEvent>,
+    mut damage_events: EventWriter<DamageEvent>,
+    query: Query<&FallDamage>,
+) {
+    for LandedEvent {
+        entity,
+        impact_speed,
+    } in landed_events.iter()
+    {
+        if let Ok(fall_damage) = query.get(*entity) {
+            let excess_speed = impact_speed - fall_damage.safe_speed;
+            if excess_speed > 0. {
+                damage_events.send(DamageEvent {
+                    target: *entity,
+                    amount: excess_speed * fall_damage.damage_per_speed,
+                    source: DamageSource::Fall,
+                });
+            }
+        }
+    }
+}
+
+/// Subtracts damage from the health of its targets, and sends a DeathEvent if they die.
+pub fn apply_damage(
+    mut damage_events: EventReader<DamageEvent>,
+    mut death_events: EventWriter<DeathEvent>,
+    mut query: Query<&mut Health, Without<Invulnerable>>,
+) {
+    for event in damage_events.iter() {
+        if let Ok(mut health) = query.get_mut(event.target) {
+            if health.is_dead() {
+                continue;
+            }
+            health.current = (health.current - event.amount).max(0.);
+            debug!(
+                "{:?} took {} damage from {:?}, {} health left.",
+                event.target, event.amount, event.source, health.current
+            );
+            if health.is_dead() {
+                death_events.send(DeathEvent {
+                    entity: event.target,
+                });
+            }
+        }
+    }
+}
+
+/// Respawns the player at the spawn point and kills off monsters.
+pub fn handle_deaths(
+    mut commands: Commands,
+    mut death_events: EventReader<DeathEvent>,
+    spawn_point: Res<SpawnPoint>,
+    mut player_q: Query<(&mut Health, &mut MoveState, &mut Transform), With<Player>>,
+    mut monster_q: Query<&mut MonsterBehaviour>,
+) {
+    for DeathEvent { entity } in death_events.iter() {
+        if let Ok((mut health, mut move_state, mut tform)) = player_q.get_mut(*entity) {
+            info!("The player died! Respawning...");
+            health.current = health.max;
+            move_state.velocity = Vec3::ZERO;
+            move_state.grounded = false;
+            tform.translation = spawn_point.position;
+            commands.entity(*entity).insert(Invulnerable {
+                timer: Timer::from_seconds(RESPAWN_INVULNERABILITY_SECONDS, Once),
+            });
+        } else if let Ok(mut behaviour) = monster_q.get_mut(*entity) {
+            behaviour.kill();
+        }
+    }
+}
+
+pub fn tick_invulnerability(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Invulnerable)>,
+    time: Res<Time>,
+) {
+    for (entity, mut invulnerable) in query.iter_mut() {
+        if invulnerable.timer.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<Invulnerable>();
+        }
+    }
+}
@@ -1,2 +1,6 @@
+pub mod health;
+pub mod monster;
 pub mod player;
+pub mod spawn_point;
+pub mod spawning;
 pub mod structs;
@@ -0,0 +1,343 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::time::TimerMode::{Once, Repeating};
+use bevy_kira_audio::prelude::{Audio, AudioControl};
+use rand::Rng;
+
+use crate::game::actors::health::{Damage, DamageEvent, DamageSource, FallDamage, Health};
+use crate::game::actors::structs::{Monster, MonsterBundle, Player};
+use crate::game::hex_grid::axial::IPos;
+use crate::game::hex_grid::chunks::Chunks;
+use crate::game::hex_grid::visibility::line_of_sight;
+use crate::game::meshes::hexagon::create_single_block_mesh;
+use crate::game::movement::char_control::{falling_speed, resolve_grounding, walking_movement};
+use crate::game::movement::structs::{LandedEvent, MoveInput, MoveParams, MoveState};
+use crate::game::pathfinding::astar::{ground_below, NavParams};
+use crate::game::pathfinding::path_cache::{PathCache, PathFollower};
+use crate::io::audio::{AudioLibrary, SfxMonster};
+
+/// Height above an actor's feet that monsters look from, and look at.
+const EYE_HEIGHT: f32 = 1.2;
+/// Chasing monsters give up searching for a path after visiting this many positions.
+/// The player is often out of reach, and then the search would visit every position it can.
+const CHASE_MAX_VISITED: usize = 1024;
+/// How far below the player monsters look for the ground to walk to, in blocks.
+const CHASE_MAX_DEPTH: u32 = 32;
+
+/// The states a monster's behaviour can be in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MonsterState {
+    /// Wandering around its home position, unaware of the player.
+    Idle,
+    /// Chasing the player.
+    Aggro,
+    /// Close enough to the player to attack.
+    Attack,
+    /// Dead; the monster despawns once its death timer runs out.
+    Dead,
+}
+
+#[derive(Component)]
+pub struct MonsterBehaviour {
+    pub state: MonsterState,
+    /// The monster starts chasing the player if the player comes within this distance.
+    pub aggro_range: f32,
+    /// The monster gives up the chase if the player gets further away than this distance.
+    pub leash_range: f32,
+    /// The monster attacks if the player is within this distance.
+    pub attack_range: f32,
+    /// Sounds further away than this distance are inaudible.
+    pub hearing_range: f32,
+    /// The maximum distance from home that the monster will wander to while idle.
+    pub wander_radius: f32,
+    /// The position the monster wanders around while idle.
+    pub home: Vec3,
+    /// Where the monster is wandering to while idle, if anywhere.
+    pub wander_target: Option<Vec3>,
+    /// Determines when the monster picks a new wander target.
+    pub wander_timer: Timer,
+    /// Time between attacks.
+    pub attack_timer: Timer,
+    /// Time between dying and despawning.
+    pub death_timer: Timer,
+    /// The state during the previous tick. Used to detect state changes.
+    previous_state: MonsterState,
+}
+
+impl Default for MonsterBehaviour {
+    fn default() -> Self {
+        Self {
+            state: MonsterState::Idle,
+            aggro_range: 12.,
+            leash_range: 20.,
+            attack_range: 1.5,
+            hearing_range: 30.,
+            wander_radius: 6.,
+            home: Vec3::ZERO,
+            wander_target: None,
+            wander_timer: Timer::from_seconds(4., Repeating),
+            attack_timer: Timer::from_seconds(1., Repeating),
+            death_timer: Timer::from_seconds(2., Once),
+            previous_state: MonsterState::Idle,
+        }
+    }
+}
+
+impl MonsterBehaviour {
+    /// Kills the monster. Its behaviour will take care of the rest.
+    pub fn kill(&mut self) {
+        self.state = MonsterState::Dead;
+    }
+    /// Determines the state the monster should be in, given the distance to the player.
+    /// A distance of None means the monster doesn't know where the player is.
+    fn next_state(&self, distance_to_player: Option<f32>) -> MonsterState {
+        match (self.state, distance_to_player) {
+            (MonsterState::Dead, _) => MonsterState::Dead,
+            (_, None) => MonsterState::Idle,
+            (_, Some(distance)) if distance <= self.attack_range => MonsterState::Attack,
+            (MonsterState::Idle, Some(distance)) if distance <= self.aggro_range => {
+                MonsterState::Aggro
+            }
+            (MonsterState::Idle, _) => MonsterState::Idle,
+            (_, Some(distance)) if distance <= self.leash_range => MonsterState::Aggro,
+            _ => MonsterState::Idle,
+        }
+    }
+}
+
+pub fn spawn_monster(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    std_mats: &mut Assets<StandardMaterial>,
+    position: Vec3,
+) -> Entity {
+    commands
+        .spawn(MonsterBundle {
+            move_params: MoveParams {
+                accel: 8.,
+                max_speed: 3.5,
+                boost_mod: 1.,
+                max_fall_speed: 30.,
+                vel_decay_factor: 0.85,
+                turn_speed: 0.,
+                flying: false,
+            },
+            behaviour: MonsterBehaviour {
+                home: position,
+                ..default()
+            },
+            health: Health::new(30.),
+            damage: Damage { amount: 10. },
+            fall_damage: FallDamage {
+                safe_speed: 10.,
+                damage_per_speed: 5.,
+            },
+            pbr: PbrBundle {
+                mesh: meshes.add(create_single_block_mesh()),
+                material: std_mats.add(Color::MAROON.into()),
+                transform: Transform::from_translation(position)
+                    .with_scale(Vec3::new(0.7, 0.7, 1.6)),
+                ..default()
+            },
+            ..default()
+        })
+        .id()
+}
+
+/// Runs the monster's state machine. Plays the matching sound whenever the state changes.
+pub fn monster_behaviour_system(
+    mut commands: Commands,
+    mut q: Query<
+        (Entity, &mut MonsterBehaviour, &Damage, &Transform),
+        (With<Monster>, Without<Player>),
+    >,
+    player_q: Query<(Entity, &Transform), With<Player>>,
+    chunks: Res<Chunks>,
+    mut damage_events: EventWriter<DamageEvent>,
+    audio: Res<Audio>,
+    library: Res<AudioLibrary>,
+    time: Res<Time>,
+) {
+    let player = player_q.get_single().ok();
+    let player_pos = player.map(|(_, tform)| tform.translation);
+    for (entity, mut behaviour, damage, tform) in q.iter_mut() {
+        let distance_to_player = player_pos.map(|pos| pos.distance(tform.translation));
+        // Idle monsters only notice the player if they can see them.
+        let noticed = behaviour.state != MonsterState::Idle
+            || player_pos.map_or(false, |pos| {
+                pos.distance(tform.translation) <= behaviour.aggro_range
+                    && line_of_sight(
+                        &chunks,
+                        &(tform.translation + Vec3::Z * EYE_HEIGHT),
+                        &(pos + Vec3::Z * EYE_HEIGHT),
+                    )
+                    .is_clear()
+            });
+        behaviour.state = behaviour.next_state(distance_to_player.filter(|_| noticed));
+        if behaviour.state != behaviour.previous_state {
+            play_monster_sfx(
+                &audio,
+                &library,
+                &sfx_for_state(behaviour.state),
+                distance_to_player,
+                behaviour.hearing_range,
+            );
+            if behaviour.state == MonsterState::Attack {
+                behaviour.attack_timer.reset();
+            }
+            behaviour.previous_state = behaviour.state;
+        }
+        match behaviour.state {
+            MonsterState::Idle => {
+                if behaviour.wander_timer.tick(time.delta()).just_finished() {
+                    let mut rng = rand::thread_rng();
+                    behaviour.wander_target = if rng.gen_bool(0.5) {
+                        let angle = rng.gen_range(0. ..std::f32::consts::TAU);
+                        let distance = rng.gen_range(0. ..behaviour.wander_radius);
+                        Some(
+                            behaviour.home
+                                + Vec3::new(angle.cos() * distance, angle.sin() * distance, 0.),
+                        )
+                    } else {
+                        None
+                    };
+                }
+            }
+            MonsterState::Attack => {
+                if behaviour.attack_timer.tick(time.delta()).just_finished() {
+                    play_monster_sfx(
+                        &audio,
+                        &library,
+                        &SfxMonster::Hit,
+                        distance_to_player,
+                        behaviour.hearing_range,
+                    );
+                    if let Some((player_entity, _)) = player {
+                        damage_events.send(DamageEvent {
+                            target: player_entity,
+                            amount: damage.amount,
+                            source: DamageSource::Attack(entity),
+                        });
+                    }
+                }
+            }
+            MonsterState::Dead => {
+                if behaviour.death_timer.tick(time.delta()).finished() {
+                    commands.entity(entity).despawn_recursive();
+                }
+            }
+            MonsterState::Aggro => {}
+        }
+    }
+}
+
+/// Moves monsters depending on the state they're in.
+pub fn monster_movement_system(
+    mut q: Query<
+        (
+            Entity,
+            &MonsterBehaviour,
+            &mut PathFollower,
+            &mut MoveState,
+            &MoveParams,
+            &mut Transform,
+        ),
+        (With<Monster>, Without<Player>),
+    >,
+    player_q: Query<&Transform, With<Player>>,
+    chunks: Res<Chunks>,
+    mut path_cache: ResMut<PathCache>,
+    mut landed_events: EventWriter<LandedEvent>,
+    time: Res<Time>,
+) {
+    let player_pos = player_q.get_single().ok().map(|tform| tform.translation);
+    let dt = time.delta_seconds();
+    for (entity, behaviour, mut follower, mut move_state, move_params, mut tform) in q.iter_mut() {
+        let (target, params) = match behaviour.state {
+            MonsterState::Idle => (behaviour.wander_target, NavParams::default()),
+            MonsterState::Aggro => (
+                player_pos,
+                NavParams {
+                    max_visited: CHASE_MAX_VISITED,
+                    ..default()
+                },
+            ),
+            MonsterState::Attack | MonsterState::Dead => (None, NavParams::default()),
+        };
+        let mut input = MoveInput::default();
+        if let Some(target) = target {
+            let target = next_waypoint(
+                &chunks,
+                &mut path_cache,
+                &mut follower,
+                &tform.translation,
+                &target,
+                &params,
+                time.delta(),
+            );
+            let to_target = (target - tform.translation).truncate();
+            if to_target.length_squared() > 0.25 {
+                // Face the target. The body's forward direction is along its local y-axis.
+                let angle = to_target.y.atan2(to_target.x) - std::f32::consts::FRAC_PI_2;
+                tform.rotation = Quat::from_rotation_z(angle);
+                input.xy_plane = Vec2::Y;
+            }
+        }
+        let mut vel = walking_movement(&input, move_params, &move_state, tform.rotation, dt);
+        vel.z = falling_speed(move_params, &move_state, dt);
+        move_state.velocity = vel;
+        tform.translation += move_state.velocity * dt;
+        if let Some(event) = resolve_grounding(entity, &chunks, &mut move_state, &mut tform) {
+            landed_events.send(event);
+        }
+    }
+}
+
+/// Returns the point to walk towards in order to reach the target: the next step along the path
+/// to the ground below it, or the target itself if there is no path or it is only a step away.
+fn next_waypoint(
+    chunks: &Chunks,
+    path_cache: &mut PathCache,
+    follower: &mut PathFollower,
+    from: &Vec3,
+    target: &Vec3,
+    params: &NavParams,
+    delta: Duration,
+) -> Vec3 {
+    let start = IPos::block_containing(from);
+    let target_block = IPos::block_containing(target);
+    // The player may be flying or jumping, but monsters can only walk to the ground below them:
+    let goal = ground_below(chunks, &target_block, CHASE_MAX_DEPTH).unwrap_or(target_block);
+    match follower.next_step(path_cache, chunks, &start, &goal, params, delta) {
+        Some(step) if step != goal => step.as_xyz(),
+        _ => *target,
+    }
+}
+
+fn sfx_for_state(state: MonsterState) -> SfxMonster {
+    match state {
+        MonsterState::Idle => SfxMonster::Idle,
+        MonsterState::Aggro => SfxMonster::Aggro,
+        MonsterState::Attack => SfxMonster::Hit,
+        MonsterState::Dead => SfxMonster::Death,
+    }
+}
+
+/// Plays the sound, quieter the further away the listener is.
+fn play_monster_sfx(
+    audio: &Audio,
+    library: &AudioLibrary,
+    sfx: &SfxMonster,
+    distance: Option<f32>,
+    hearing_range: f32,
+) {
+    let volume = distance
+        .map(|distance| 1. - (distance / hearing_range).min(1.))
+        .unwrap_or(0.);
+    if volume > 0. {
+        if let Some(source) = library.sfx(sfx) {
+            audio.play(source).with_volume(volume as f64);
+        }
+    }
+}
@@ -4,6 +4,8 @@ use bevy::{
     transform::TransformBundle,
 };
 
+use crate::game::actors::health::{FallDamage, Health};
+use crate::game::actors::spawn_point::SpawnPoint;
 use crate::game::hex_grid::chunk_loading::ChunkLoader;
 use crate::game::{
     camera::first_person::{PlayerCamera, PlayerCameraBundle},
@@ -13,23 +15,30 @@ use crate::io::config::WorldConfig;
 
 use super::structs::PlayerBundle;
 
-pub fn setup_player(mut command: Commands, config: Res<WorldConfig>) {
+pub fn setup_player(mut command: Commands, config: Res<WorldConfig>, spawn_point: Res<SpawnPoint>) {
     command
         .spawn(PlayerBundle {
             move_params: MoveParams {
                 accel: 14.4,
                 max_speed: 11.1,
                 boost_mod: 1.8,
-                max_fall_speed: 10.0,
+                max_fall_speed: 30.0,
                 vel_decay_factor: 0.94, // closer to 1 means slower decay
                 turn_speed: 0.15,
                 flying: true,
             },
+            health: Health::new(100.0),
+            fall_damage: FallDamage {
+                safe_speed: 12.0,
+                damage_per_speed: 6.0,
+            },
             chunk_loader: ChunkLoader {
                 radius_min: config.render_distance_min,
                 radius_max: config.render_distance_max,
             },
-            transform: TransformBundle::from_transform(Transform::from_xyz(1.0, 1.0, 12.0)),
+            transform: TransformBundle::from_transform(Transform::from_translation(
+                spawn_point.position,
+            )),
             ..Default::default()
         })
         .with_children(|parent| {
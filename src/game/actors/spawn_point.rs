@@ -0,0 +1,98 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::game::hex_grid::axial::{ChunkId, ColumnId, IPos};
+use crate::game::hex_grid::biomes::BiomeType;
+use crate::game::hex_grid::block::BlockType;
+use crate::game::hex_grid::chunk_loading::ChunkLoadedEvent;
+use crate::game::hex_grid::chunks::Chunks;
+use crate::io::config::WorldConfig;
+
+/// The place where players spawn when they join the world, and where they respawn after dying.
+#[derive(Resource)]
+pub struct SpawnPoint {
+    pub position: Vec3,
+}
+
+impl Default for SpawnPoint {
+    fn default() -> Self {
+        Self {
+            position: Vec3::new(1., 1., 12.),
+        }
+    }
+}
+
+/// Determines where the spawn finder looks for a spawn point.
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SpawnSearchConfig {
+    /// The spawn point is searched for in chunks within this radius of the world origin.
+    pub chunk_radius: u32,
+    /// The number of air blocks needed above the surface.
+    pub headroom: u32,
+    /// If possible, the spawn point will be in one of these biomes.
+    /// If empty, or if none of the biomes are nearby, any biome will do.
+    pub preferred_biomes: Vec<BiomeType>,
+}
+
+/// Generates the chunks around the world origin and picks a spawn point in them.
+/// The spawn point is on a dry, solid surface with enough room above it to stand in.
+/// Columns closer to the origin are preferred, as are columns in one of the preferred biomes.
+pub fn find_spawn_point(
+    mut chunks: ResMut<Chunks>,
+    mut spawn_point: ResMut<SpawnPoint>,
+    mut loaded_events: EventWriter<ChunkLoadedEvent>,
+    config: Res<WorldConfig>,
+) {
+    let search = &config.spawn_search;
+    let chunk_ids = ColumnId::spiral(search.chunk_radius)
+        .into_iter()
+        .map(|qr| qr + ChunkId::default())
+        .collect::<Vec<ChunkId>>();
+    for id in chunk_ids.iter() {
+        if !chunks.contains(id) {
+            chunks.generate_chunk(*id);
+            loaded_events.send(ChunkLoadedEvent { id: *id });
+        }
+    }
+    let candidates = chunk_ids
+        .iter()
+        .flat_map(|id| {
+            let center = id.center_pos(chunks.dimensions());
+            chunks
+                .dimensions()
+                .columns()
+                .into_iter()
+                .map(move |column| (column + center).as_column_id())
+        })
+        .filter_map(|column| spawn_candidate(&chunks, &column, search.headroom))
+        .collect::<Vec<(IPos, BiomeType)>>();
+    let chosen = candidates
+        .iter()
+        .find(|(_, biome)| search.preferred_biomes.contains(biome))
+        .or_else(|| candidates.first());
+    if let Some((pos, biome)) = chosen {
+        debug!("Found a spawn point at {:?}, in biome {:?}.", pos, biome);
+        spawn_point.position = pos.as_xyz();
+    } else {
+        warn!(
+            "Could not find a safe spawn point within {} chunks of the origin. \
+            Falling back to {:?}.",
+            search.chunk_radius, spawn_point.position
+        );
+    }
+}
+
+/// Returns the position on top of the column's surface, if it is safe to spawn there,
+/// along with the biome of the surface block.
+fn spawn_candidate(chunks: &Chunks, column: &ColumnId, headroom: u32) -> Option<(IPos, BiomeType)> {
+    let surface = column.as_ipos(chunks.surface_height(column)?);
+    let block = chunks.block(&surface)?;
+    if block.block_type == BlockType::Water {
+        return None;
+    }
+    let standing_pos = surface.delta(0, 0, 1);
+    let has_headroom =
+        (0..headroom as i32).all(|dz| !chunks.is_solid(&standing_pos.delta(0, 0, dz)));
+    has_headroom.then_some((standing_pos, block.biome_type))
+}
@@ -0,0 +1,122 @@
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::game::actors::monster::spawn_monster;
+use crate::game::hex_grid::axial::{ChunkId, ColumnId, IPos};
+use crate::game::hex_grid::chunk_loading::{ChunkLoadedEvent, ChunkLoader, ChunkUnloadedEvent};
+use crate::game::hex_grid::chunks::Chunks;
+use crate::game::meshes::sun::DayNight;
+use crate::io::config::SpawningConfig;
+
+/// The kinds of actors that can be spawned by the spawner.
+#[derive(Deserialize, Serialize, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ActorType {
+    Monster,
+}
+
+/// Apply this component to actors that were spawned by the spawner.
+/// These actors are despawned when the chunk they are in is unloaded.
+#[derive(Component)]
+pub struct SpawnedActor {
+    pub actor: ActorType,
+}
+
+/// Spawns actors in freshly loaded chunks, according to the spawn tables of the biomes in them.
+pub fn spawn_actors_in_loaded_chunks(
+    mut commands: Commands,
+    mut events: EventReader<ChunkLoadedEvent>,
+    chunks: Res<Chunks>,
+    config: Res<SpawningConfig>,
+    day_night: Res<DayNight>,
+    query_loaders: Query<&Transform, With<ChunkLoader>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut std_mats: ResMut<Assets<StandardMaterial>>,
+) {
+    let mut rng = rand::thread_rng();
+    let daylight = day_night.daylight();
+    let nr_columns = chunks.dimensions().columns().len() as f32;
+    for ChunkLoadedEvent { id } in events.iter() {
+        let chunk = if let Some(chunk) = chunks.get_chunk(id) {
+            chunk
+        } else {
+            // The chunk was already unloaded again.
+            continue;
+        };
+        let mut nr_spawned = 0;
+        let surfaces = chunk
+            .columns()
+            .into_iter()
+            .filter_map(|column| {
+                chunk
+                    .surface_height(&column)
+                    .ok()
+                    .flatten()
+                    .map(|z| (column, z))
+            })
+            .collect::<Vec<(ColumnId, i32)>>();
+        for (column, surface) in surfaces.iter() {
+            if nr_spawned >= config.max_per_chunk {
+                break;
+            }
+            let surface_block = chunk.block(&column.as_ipos(*surface));
+            let spawn_pos = column.as_ipos(surface + 1) + id.center_pos(chunks.dimensions());
+            let xyz = spawn_pos.as_xyz();
+            let too_close = query_loaders
+                .iter()
+                .any(|tform| tform.translation.distance(xyz) < config.min_distance_to_loader);
+            if too_close || !has_headroom(&chunks, &spawn_pos) {
+                continue;
+            }
+            let rules = config.biomes.get(&surface_block.biome_type);
+            for rule in rules.iter().flat_map(|rules| rules.iter()) {
+                let allowed = rule.surface_blocks.contains(&surface_block.block_type)
+                    && daylight >= rule.min_daylight
+                    && daylight <= rule.max_daylight;
+                // Each column gets an equal share of the chunk's spawns:
+                let chance = (rule.density / nr_columns).clamp(0., 1.) as f64;
+                if allowed && rng.gen_bool(chance) {
+                    let entity = match rule.actor {
+                        ActorType::Monster => {
+                            spawn_monster(&mut commands, &mut meshes, &mut std_mats, xyz)
+                        }
+                    };
+                    commands
+                        .entity(entity)
+                        .insert(SpawnedActor { actor: rule.actor });
+                    nr_spawned += 1;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Despawns spawned actors that are in chunks that were just unloaded.
+pub fn despawn_actors_in_unloaded_chunks(
+    mut commands: Commands,
+    mut events: EventReader<ChunkUnloadedEvent>,
+    chunks: Res<Chunks>,
+    query: Query<(Entity, &Transform), With<SpawnedActor>>,
+) {
+    let unloaded = events
+        .iter()
+        .map(|event| event.id)
+        .collect::<HashSet<ChunkId>>();
+    if unloaded.is_empty() {
+        return;
+    }
+    for (entity, transform) in query.iter() {
+        let pos = IPos::block_containing(&transform.translation);
+        let chunk_id = ChunkId::from_block_pos(&pos, chunks.dimensions());
+        if unloaded.contains(&chunk_id) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Checks if there are two blocks of air for an actor to stand in.
+fn has_headroom(chunks: &Chunks, pos: &IPos) -> bool {
+    !chunks.is_solid(pos) && !chunks.is_solid(&pos.delta(0, 0, 1))
+}
@@ -1,5 +1,9 @@
+use crate::game::actors::health::{Damage, FallDamage, Health};
+use crate::game::actors::monster::MonsterBehaviour;
 use crate::game::hex_grid::chunk_loading::ChunkLoader;
+use crate::game::pathfinding::path_cache::PathFollower;
 use bevy::{
+    pbr::PbrBundle,
     prelude::{Bundle, Component},
     transform::TransformBundle,
 };
@@ -14,7 +18,26 @@ pub struct PlayerBundle {
     pub(crate) player_tag: Player,
     pub(crate) move_params: MoveParams,
     pub(crate) move_state: MoveState,
+    pub(crate) health: Health,
+    pub(crate) fall_damage: FallDamage,
     pub(crate) chunk_loader: ChunkLoader,
     #[bundle]
     pub transform: TransformBundle,
 }
+
+#[derive(Component, Default)]
+pub struct Monster;
+
+#[derive(Bundle, Default)]
+pub struct MonsterBundle {
+    pub(crate) monster_tag: Monster,
+    pub(crate) move_params: MoveParams,
+    pub(crate) move_state: MoveState,
+    pub(crate) behaviour: MonsterBehaviour,
+    pub(crate) path_follower: PathFollower,
+    pub(crate) health: Health,
+    pub(crate) damage: Damage,
+    pub(crate) fall_damage: FallDamage,
+    #[bundle]
+    pub pbr: PbrBundle,
+}
@@ -2,16 +2,10 @@ use std::cmp::Ordering;
 use std::ops::{Add, Sub};
 
 use bevy::math::*;
-use once_cell::sync::OnceCell;
 use serde::{Deserialize, Serialize};
 
-use crate::game::hex_grid::axial::{IPos, Pos, RADIUS, SQRT_THREE};
-use crate::game::hex_grid::chunk::{CHUNK_HEIGHT, CHUNK_RADIUS};
-
-/// Matrix for converting a block's qrz coordinates to a chunk id.
-static CHUNK_ID_FROM_BLOCK_POS: OnceCell<Mat4> = OnceCell::new();
-/// Matrix for converting a block's xyz coordinates to a chunk id.
-static CHUNK_ID_FROM_XYZ: OnceCell<Mat4> = OnceCell::new();
+use crate::game::hex_grid::axial::{IPos, Pos};
+use crate::game::hex_grid::chunk::ChunkDimensions;
 
 /// ChunkIds are pointy-topped axial coordinates. They are NOT compatible with block
 /// positions (IPos), which use flat-topped axial coordinates.
@@ -75,59 +69,136 @@ impl ChunkId {
         let delta = a - b;
         delta.q().abs().max(delta.r().abs()).max(delta.z().abs())
     }
+    /// Returns the distance between the columns of two chunks, ignoring the z-coordinate.
     #[must_use]
-    pub fn center_pos(&self) -> IPos {
-        IPos::new(
-            (CHUNK_RADIUS as i32 * 2 + 1) * self.q() + CHUNK_RADIUS as i32 * self.r(),
-            CHUNK_RADIUS as i32 * -self.q() + (CHUNK_RADIUS as i32 + 1) * self.r(),
-            self.z() * CHUNK_HEIGHT as i32,
-        )
+    pub fn column_distance(a: &Self, b: &Self) -> i32 {
+        IPos::column_distance(&a.as_axial(), &b.as_axial())
     }
     #[must_use]
-    pub fn from_xyz(xyz: &Vec3) -> Self {
-        let xyz = CHUNK_ID_FROM_XYZ
-            .get()
-            .expect("ChunkId::setup() was never called!")
-            .transform_vector3(*xyz);
-        let rounded = Pos::new(xyz.x, xyz.y, 0.).round();
-        ChunkId::new(
-            rounded.q() as i32,
-            rounded.r() as i32,
-            (xyz.z / CHUNK_HEIGHT as f32).floor() as i32,
-        )
+    pub fn neighbour(&self, neighbour_index: u32) -> Self {
+        Self::from_axial(&self.as_axial().neighbour(neighbour_index))
+    }
+    /// Returns the diagonal neighbour at the given index.
+    /// Diagonal i lies in between the neighbours at index i and i+1.
+    #[must_use]
+    pub fn diagonal(&self, diagonal_index: u32) -> Self {
+        Self::from_axial(&self.as_axial().diagonal(diagonal_index))
     }
+    /// Returns all chunks on a straight line from a to b, on the layer of a.
     #[must_use]
-    pub fn from_block_pos(block_pos: &IPos) -> Self {
-        let xyz = CHUNK_ID_FROM_BLOCK_POS
-            .get()
-            .expect("ChunkId::setup() was never called!")
-            .transform_vector3(block_pos.as_pos().0);
-        let rounded = Pos::new(xyz.x, xyz.y, 0.).round();
-        ChunkId::new(
-            rounded.q() as i32,
-            rounded.r() as i32,
-            (xyz.z / CHUNK_HEIGHT as f32).floor() as i32,
+    pub fn line(a: &Self, b: &Self) -> Vec<Self> {
+        Self::all_from_axial(IPos::line(&a.as_axial(), &b.as_axial()))
+    }
+    /// Returns all chunks on a straight line from a to b, moving through layers as well.
+    #[must_use]
+    pub fn line_3d(a: &Self, b: &Self) -> Vec<Self> {
+        Self::all_from_axial(IPos::line_3d(&a.as_axial(), &b.as_axial()))
+    }
+    /// Returns all chunks within the given distance of this one, on the same layer.
+    #[must_use]
+    pub fn range(&self, radius: u32) -> Vec<Self> {
+        Self::all_from_axial(self.as_axial().range(radius))
+    }
+    /// Returns all chunks within the given distance of this one, including other layers.
+    #[must_use]
+    pub fn range_3d(&self, radius: u32) -> Vec<Self> {
+        Self::all_from_axial(self.as_axial().range_3d(radius))
+    }
+    /// Returns all chunks that are both within radius_a of a and within radius_b of b,
+    /// on the layer of a.
+    #[must_use]
+    pub fn intersect_ranges(a: &Self, radius_a: u32, b: &Self, radius_b: u32) -> Vec<Self> {
+        Self::all_from_axial(IPos::intersect_ranges(
+            &a.as_axial(),
+            radius_a,
+            &b.as_axial(),
+            radius_b,
+        ))
+    }
+    /// Returns all chunks that are both within radius_a of a and within radius_b of b,
+    /// including other layers.
+    #[must_use]
+    pub fn intersect_ranges_3d(a: &Self, radius_a: u32, b: &Self, radius_b: u32) -> Vec<Self> {
+        Self::all_from_axial(IPos::intersect_ranges_3d(
+            &a.as_axial(),
+            radius_a,
+            &b.as_axial(),
+            radius_b,
+        ))
+    }
+    /// Rotates this chunk id around the given center by a multiple of 60 degrees.
+    /// Positive steps rotate counter-clockwise.
+    #[must_use]
+    pub fn rotate_around(&self, center: &Self, steps: i32) -> Self {
+        Self::from_axial(&self.as_axial().rotate_around(&center.as_axial(), steps))
+    }
+    /// Mirrors this chunk id across the q-axis that runs through the given center.
+    #[must_use]
+    pub fn reflect_q(&self, center: &Self) -> Self {
+        Self::from_axial(&self.as_axial().reflect_q(&center.as_axial()))
+    }
+    /// Mirrors this chunk id across the r-axis that runs through the given center.
+    #[must_use]
+    pub fn reflect_r(&self, center: &Self) -> Self {
+        Self::from_axial(&self.as_axial().reflect_r(&center.as_axial()))
+    }
+    /// Mirrors this chunk id across the s-axis that runs through the given center.
+    #[must_use]
+    pub fn reflect_s(&self, center: &Self) -> Self {
+        Self::from_axial(&self.as_axial().reflect_s(&center.as_axial()))
+    }
+    /// The hex algorithms only care about axial coordinates, not whether the hexes are
+    /// flat-topped or pointy-topped. This lets chunk ids reuse the implementations of IPos.
+    /// Note that the resulting IPos is NOT the position of a block in this chunk.
+    fn as_axial(&self) -> IPos {
+        IPos(self.0)
+    }
+    fn from_axial(pos: &IPos) -> Self {
+        ChunkId(pos.0)
+    }
+    fn all_from_axial(positions: Vec<IPos>) -> Vec<Self> {
+        positions.iter().map(Self::from_axial).collect()
+    }
+    /// Returns the position of the center block at the bottom of this chunk.
+    #[must_use]
+    pub fn center_pos(&self, dimensions: &ChunkDimensions) -> IPos {
+        let radius = dimensions.radius as i32;
+        IPos::new(
+            (radius * 2 + 1) * self.q() + radius * self.r(),
+            radius * -self.q() + (radius + 1) * self.r(),
+            self.z() * dimensions.height as i32,
         )
     }
-
-    /// Call this once at the start of the application.
-    pub(crate) fn setup() {
-        let distance_to_edge = (CHUNK_RADIUS as f32 + 0.5) * RADIUS * SQRT_THREE;
-        let half_edge = RADIUS * 0.5;
-        let chunk_virtual_hex_radius = distance_to_edge.hypot(half_edge);
-        let angle = -1. * (half_edge / chunk_virtual_hex_radius).asin();
-        let rotation_correction = Mat4::from_rotation_z(angle);
-        let xyz_to_qrz = Mat4::from_cols(
-            Vec4::new(SQRT_THREE / 3., 0., 0., 0.),
-            Vec4::new(-1. / 3., 2. / 3., 0., 0.),
-            Vec4::new(0., 0., 1., 0.),
-            Vec4::new(0., 0., 0., 1.),
+    /// Returns the id of the chunk that contains the given point.
+    #[must_use]
+    pub fn from_xyz(xyz: &Vec3, dimensions: &ChunkDimensions) -> Self {
+        Self::from_block_pos(&IPos::block_containing(xyz), dimensions)
+    }
+    /// Returns the id of the chunk that contains the given block.
+    #[must_use]
+    pub fn from_block_pos(block_pos: &IPos, dimensions: &ChunkDimensions) -> Self {
+        // The centers of the chunks form a lattice, spanned by the centers of chunks (1,0) and
+        // (0,1). Inverting that basis gives the (fractional) chunk coordinates of the block.
+        let radius = dimensions.radius as f32;
+        let determinant = 3. * radius * radius + 3. * radius + 1.;
+        let (q, r) = (block_pos.q() as f32, block_pos.r() as f32);
+        let estimate = Pos::new(
+            ((radius + 1.) * q - radius * r) / determinant,
+            (radius * q + (2. * radius + 1.) * r) / determinant,
+            0.,
         )
-        .mul_scalar(1. / chunk_virtual_hex_radius);
-        CHUNK_ID_FROM_XYZ.set(xyz_to_qrz * rotation_correction)
-            .expect("Setup was called twice, but you should only call it once at the start of the application.");
-        CHUNK_ID_FROM_BLOCK_POS.set(xyz_to_qrz * rotation_correction * Pos::setup_qrz_to_xyz())
-            .expect("Setup was called twice, but you should only call it once at the start of the application.");
+        .as_ipos_round();
+        let z = block_pos.z().div_euclid(dimensions.height as i32);
+        // Chunks are hexagons, but they don't line up exactly with the hexagons of the lattice.
+        // Near the borders, the block may belong to one of the neighbours of the estimate.
+        let estimate = ChunkId::new(estimate.q(), estimate.r(), z);
+        std::iter::once(estimate)
+            .chain((0..6).map(|i| estimate.neighbour(i)))
+            .find(|candidate| {
+                IPos::column_distance(&candidate.center_pos(dimensions), block_pos)
+                    <= dimensions.radius as i32
+            })
+            .unwrap_or(estimate)
     }
 }
 
@@ -191,3 +262,31 @@ impl Add for &ChunkId {
         ChunkId(self.0 + other.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_block_pos_agrees_with_the_columns_of_each_chunk() {
+        for (radius, height) in [(1, 1), (2, 5), (3, 16), (8, 32), (13, 7)] {
+            let dimensions = ChunkDimensions { radius, height };
+            for chunk_id in ChunkId::new(-1, 2, -1).range_3d(3) {
+                let center = chunk_id.center_pos(&dimensions);
+                for column in dimensions.columns() {
+                    for z in 0..height as i32 {
+                        let pos = column.as_ipos(z) + center;
+                        assert_eq!(
+                            ChunkId::from_block_pos(&pos, &dimensions),
+                            chunk_id,
+                            "Block {:?} with chunk radius {} and height {}",
+                            pos,
+                            radius,
+                            height
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,10 +1,11 @@
+use std::cmp::Ordering;
 use std::ops::{Add, Mul};
 
 use bevy::math::IVec2;
 
 use crate::game::hex_grid::axial::{ChunkId, IPos};
 
-#[derive(Default, Copy, Clone)]
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct ColumnId(IVec2);
 
 impl ColumnId {
@@ -56,6 +57,66 @@ impl ColumnId {
             _ => panic!("Shouldn't happen, compiler doesn't realise this match is exhaustive."),
         }
     }
+    /// Returns the diagonal neighbour at the given index.
+    /// Diagonal i lies in between the neighbours at index i and i+1.
+    #[must_use]
+    pub fn diagonal(&self, diagonal_index: u32) -> Self {
+        self.as_ipos(0).diagonal(diagonal_index).as_column_id()
+    }
+    /// Returns the distance on the hexagonal grid.
+    #[must_use]
+    pub fn distance(a: &Self, b: &Self) -> i32 {
+        IPos::column_distance(&a.as_ipos(0), &b.as_ipos(0))
+    }
+    /// Returns all columns on a straight line from a to b, including both a and b.
+    #[must_use]
+    pub fn line(a: &Self, b: &Self) -> Vec<Self> {
+        IPos::line(&a.as_ipos(0), &b.as_ipos(0))
+            .iter()
+            .map(IPos::as_column_id)
+            .collect()
+    }
+    /// Returns all columns within the given distance of this one.
+    /// Unlike spiral(), the result is centered on self and not ordered in rings.
+    #[must_use]
+    pub fn range(&self, radius: u32) -> Vec<Self> {
+        self.as_ipos(0)
+            .range(radius)
+            .iter()
+            .map(IPos::as_column_id)
+            .collect()
+    }
+    /// Returns all columns that are both within radius_a of a and within radius_b of b.
+    #[must_use]
+    pub fn intersect_ranges(a: &Self, radius_a: u32, b: &Self, radius_b: u32) -> Vec<Self> {
+        IPos::intersect_ranges(&a.as_ipos(0), radius_a, &b.as_ipos(0), radius_b)
+            .iter()
+            .map(IPos::as_column_id)
+            .collect()
+    }
+    /// Rotates this column around the given center by a multiple of 60 degrees.
+    /// Positive steps rotate counter-clockwise.
+    #[must_use]
+    pub fn rotate_around(&self, center: &Self, steps: i32) -> Self {
+        self.as_ipos(0)
+            .rotate_around(&center.as_ipos(0), steps)
+            .as_column_id()
+    }
+    /// Mirrors this column across the q-axis that runs through the given center.
+    #[must_use]
+    pub fn reflect_q(&self, center: &Self) -> Self {
+        self.as_ipos(0).reflect_q(&center.as_ipos(0)).as_column_id()
+    }
+    /// Mirrors this column across the r-axis that runs through the given center.
+    #[must_use]
+    pub fn reflect_r(&self, center: &Self) -> Self {
+        self.as_ipos(0).reflect_r(&center.as_ipos(0)).as_column_id()
+    }
+    /// Mirrors this column across the s-axis that runs through the given center.
+    #[must_use]
+    pub fn reflect_s(&self, center: &Self) -> Self {
+        self.as_ipos(0).reflect_s(&center.as_ipos(0)).as_column_id()
+    }
     /// Returns a Vec of all ColumnIds in a hexagon of a given radius, centered on (0, 0).
     /// It does this by repeatedly calling ColumnId::ring() in a spiral pattern.
     #[must_use]
@@ -116,6 +177,19 @@ impl Add<IPos> for &ColumnId {
     }
 }
 
+/// Order by q first, then r.
+impl PartialOrd<Self> for ColumnId {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ColumnId {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.q(), self.r()).cmp(&(other.q(), other.r()))
+    }
+}
+
 impl Add<ChunkId> for ColumnId {
     type Output = ChunkId;
 
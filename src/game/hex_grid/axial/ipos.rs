@@ -4,7 +4,7 @@ use std::ops::{Add, Div, Mul, Sub};
 use bevy::math::*;
 use serde::{Deserialize, Serialize};
 
-use crate::game::hex_grid::axial::Pos;
+use crate::game::hex_grid::axial::{ColumnId, Pos};
 
 /// A hexagonal coordinate in the flat-topped axial coordinate system.
 /// This is the integral version. There is also a floating-point version.
@@ -33,6 +33,14 @@ impl IPos {
     pub fn from_xyz(xyz: &Vec3) -> Self {
         Pos::from_xyz(xyz).as_ipos_round()
     }
+    /// Returns the position of the block that contains the given point.
+    /// Unlike from_xyz, this rounds the z-coordinate down, since blocks extend upwards from
+    /// their position.
+    #[must_use]
+    pub fn block_containing(xyz: &Vec3) -> Self {
+        let pos = Self::from_xyz(xyz);
+        Self::new(pos.q(), pos.r(), xyz.z.floor() as i32)
+    }
     #[must_use]
     pub fn q(&self) -> i32 {
         self.0.x
@@ -91,6 +99,166 @@ impl IPos {
             _ => panic!("Shouldn't happen, compiler doesn't realise this match is exhaustive."),
         }
     }
+    /// Returns the diagonal neighbour at the given index. Diagonal neighbours don't share a face
+    /// with this hex, they only touch one of its corners.
+    /// Diagonal i lies in between the neighbours at index i and i+1.
+    #[must_use]
+    pub fn diagonal(&self, diagonal_index: u32) -> Self {
+        self + &Self::diagonal_direction(diagonal_index)
+    }
+    #[must_use]
+    pub fn diagonal_direction(diagonal_index: u32) -> Self {
+        Self::direction(diagonal_index) + Self::direction(diagonal_index + 1)
+    }
+    /// Returns the 8 neighbours of a hex prism: the 6 neighbours on the same layer,
+    /// followed by the one above and the one below.
+    #[must_use]
+    pub fn neighbours_3d(&self) -> Vec<Self> {
+        let mut results = (0..6).map(|i| self.neighbour(i)).collect::<Vec<Self>>();
+        results.push(self.delta(0, 0, 1));
+        results.push(self.delta(0, 0, -1));
+        results
+    }
+    /// Returns the distance on the hexagonal grid, ignoring the z-coordinate.
+    /// This is the number of steps between the columns of a and b.
+    #[must_use]
+    pub fn column_distance(a: &Self, b: &Self) -> i32 {
+        let delta = a - b;
+        delta.q().abs().max(delta.r().abs()).max(delta.s().abs())
+    }
+    /// Returns the distance between two hex prisms, when moving to any of the 8 neighbours
+    /// of a prism counts as a single step.
+    #[must_use]
+    pub fn distance_3d(a: &Self, b: &Self) -> i32 {
+        Self::column_distance(a, b).max((a.z() - b.z()).abs())
+    }
+    /// Returns all hexes on a straight line from a to b, including both a and b.
+    /// The line is drawn on the layer of a; the z-coordinate of b is ignored.
+    #[must_use]
+    pub fn line(a: &Self, b: &Self) -> Vec<Self> {
+        let steps = Self::column_distance(a, b);
+        if steps == 0 {
+            return vec![*a];
+        }
+        // Nudge the start point a little to avoid landing exactly on the edge between two hexes.
+        let start = a.as_pos() + Pos::new(1e-6, 1e-6, 0.);
+        let end = Self::new(b.q(), b.r(), a.z()).as_pos() + Pos::new(1e-6, 1e-6, 0.);
+        (0..=steps)
+            .map(|i| Pos::lerp(&start, &end, i as f32 / steps as f32).as_ipos_round())
+            .collect()
+    }
+    /// Returns all hex prisms on a straight line from a to b, including both a and b.
+    /// Consecutive prisms on the line are always 3D neighbours.
+    #[must_use]
+    pub fn line_3d(a: &Self, b: &Self) -> Vec<Self> {
+        let steps = Self::distance_3d(a, b);
+        if steps == 0 {
+            return vec![*a];
+        }
+        // Lerping in 3D takes more steps than there are columns on the line, which lets the
+        // rounding skip a column now and then. So the line walks the columns of the 2D line
+        // instead, spreading the steps up or down evenly along the way.
+        let columns = Self::line(a, b);
+        let column_steps = columns.len() as i32 - 1;
+        let delta_z = b.z() - a.z();
+        (0..=steps)
+            .map(|i| {
+                let t = i as f32 / steps as f32;
+                let column = columns[(t * column_steps as f32).round() as usize];
+                column.delta(0, 0, (t * delta_z as f32).round() as i32)
+            })
+            .collect()
+    }
+    /// Returns all hexes within the given distance of this one, on the same layer.
+    #[must_use]
+    pub fn range(&self, radius: u32) -> Vec<Self> {
+        Self::intersect_ranges(self, radius, self, radius)
+    }
+    /// Returns all hex prisms within the given 3D distance of this one.
+    /// The result is shaped like a hexagonal prism that is 2 * radius + 1 blocks high.
+    #[must_use]
+    pub fn range_3d(&self, radius: u32) -> Vec<Self> {
+        Self::intersect_ranges_3d(self, radius, self, radius)
+    }
+    /// Returns all hexes that are both within radius_a of a and within radius_b of b.
+    /// The result lies on the layer of a.
+    #[must_use]
+    pub fn intersect_ranges(a: &Self, radius_a: u32, b: &Self, radius_b: u32) -> Vec<Self> {
+        Self::intersect_prisms(a, radius_a, b, radius_b, a.z(), a.z())
+    }
+    /// Returns all hex prisms that are both within 3D distance radius_a of a
+    /// and within 3D distance radius_b of b.
+    #[must_use]
+    pub fn intersect_ranges_3d(a: &Self, radius_a: u32, b: &Self, radius_b: u32) -> Vec<Self> {
+        let z_min = (a.z() - radius_a as i32).max(b.z() - radius_b as i32);
+        let z_max = (a.z() + radius_a as i32).min(b.z() + radius_b as i32);
+        Self::intersect_prisms(a, radius_a, b, radius_b, z_min, z_max)
+    }
+    fn intersect_prisms(
+        a: &Self,
+        radius_a: u32,
+        b: &Self,
+        radius_b: u32,
+        z_min: i32,
+        z_max: i32,
+    ) -> Vec<Self> {
+        let (radius_a, radius_b) = (radius_a as i32, radius_b as i32);
+        let q_min = (a.q() - radius_a).max(b.q() - radius_b);
+        let q_max = (a.q() + radius_a).min(b.q() + radius_b);
+        let r_min = (a.r() - radius_a).max(b.r() - radius_b);
+        let r_max = (a.r() + radius_a).min(b.r() + radius_b);
+        let s_min = (a.s() - radius_a).max(b.s() - radius_b);
+        let s_max = (a.s() + radius_a).min(b.s() + radius_b);
+        let mut results = vec![];
+        for q in q_min..=q_max {
+            for r in r_min.max(-q - s_max)..=r_max.min(-q - s_min) {
+                for z in z_min..=z_max {
+                    results.push(Self::new(q, r, z));
+                }
+            }
+        }
+        results
+    }
+    /// Rotates this hex around the given center by a multiple of 60 degrees.
+    /// Positive steps rotate counter-clockwise, in the same direction as the neighbour indices.
+    /// Rotation happens around the vertical axis, so the z-coordinate is left unchanged.
+    #[must_use]
+    pub fn rotate_around(&self, center: &Self, steps: i32) -> Self {
+        let relative = self - center;
+        let (mut q, mut r, mut s) = (relative.q(), relative.r(), relative.s());
+        for _ in 0..steps.rem_euclid(6) {
+            (q, r, s) = (-r, -s, -q);
+        }
+        center + &Self::new(q, r, relative.z())
+    }
+    /// Mirrors this hex across the q-axis that runs through the given center.
+    #[must_use]
+    pub fn reflect_q(&self, center: &Self) -> Self {
+        let relative = self - center;
+        center + &Self::new(relative.q(), relative.s(), relative.z())
+    }
+    /// Mirrors this hex across the r-axis that runs through the given center.
+    #[must_use]
+    pub fn reflect_r(&self, center: &Self) -> Self {
+        let relative = self - center;
+        center + &Self::new(relative.s(), relative.r(), relative.z())
+    }
+    /// Mirrors this hex across the s-axis that runs through the given center.
+    #[must_use]
+    pub fn reflect_s(&self, center: &Self) -> Self {
+        let relative = self - center;
+        center + &Self::new(relative.r(), relative.q(), relative.z())
+    }
+    /// Mirrors this hex vertically across the horizontal plane at the given height.
+    #[must_use]
+    pub fn reflect_z(&self, z: i32) -> Self {
+        Self::new(self.q(), self.r(), 2 * z - self.z())
+    }
+    /// Returns the column this hex is in.
+    #[must_use]
+    pub fn as_column_id(&self) -> ColumnId {
+        ColumnId::new(self.q(), self.r())
+    }
 }
 
 /// Order by q first, then r, then z.
@@ -177,3 +345,258 @@ impl Div for IPos {
         IPos(self.0 / other.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::game::hex_grid::axial::{ChunkId, ColumnId};
+
+    /// Every position within a few steps of a handful of centers, on several layers.
+    fn sample_positions() -> Vec<IPos> {
+        [IPos::default(), IPos::new(7, -3, 2), IPos::new(-12, 5, -4)]
+            .iter()
+            .flat_map(|center| center.range_3d(4))
+            .collect()
+    }
+
+    #[test]
+    fn six_rotations_return_to_the_start() {
+        for center in [IPos::default(), IPos::new(3, -5, 1)] {
+            for pos in sample_positions() {
+                let rotated = (0..6).fold(pos, |pos, _| pos.rotate_around(&center, 1));
+                assert_eq!(rotated, pos);
+                assert_eq!(pos.rotate_around(&center, 6), pos);
+                assert_eq!(
+                    pos.rotate_around(&center, -1),
+                    pos.rotate_around(&center, 5)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn reflecting_twice_is_the_identity() {
+        let center = IPos::new(-2, 4, 1);
+        for pos in sample_positions() {
+            assert_eq!(pos.reflect_q(&center).reflect_q(&center), pos);
+            assert_eq!(pos.reflect_r(&center).reflect_r(&center), pos);
+            assert_eq!(pos.reflect_s(&center).reflect_s(&center), pos);
+            assert_eq!(pos.reflect_z(3).reflect_z(3), pos);
+        }
+    }
+
+    #[test]
+    fn line_has_one_hex_per_step() {
+        for a in [IPos::default(), IPos::new(5, -9, 3)] {
+            for b in a.range(8) {
+                let line = IPos::line(&a, &b);
+                assert_eq!(line.len(), IPos::column_distance(&a, &b) as usize + 1);
+                assert_eq!(line.first(), Some(&a));
+                assert_eq!(line.last(), Some(&b));
+                for pair in line.windows(2) {
+                    assert_eq!(IPos::column_distance(&pair[0], &pair[1]), 1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn range_is_a_centered_hexagonal_number() {
+        for radius in 0..=12 {
+            let expected = 3 * radius * radius + 3 * radius + 1;
+            assert_eq!(IPos::new(4, -1, 2).range(radius).len(), expected as usize);
+        }
+    }
+    /// All positions in the box around the center, so results can be checked by brute force.
+    fn box_around(center: &IPos, radius: i32, z_radius: i32) -> Vec<IPos> {
+        let mut positions = vec![];
+        for q in -radius..=radius {
+            for r in -radius..=radius {
+                for z in -z_radius..=z_radius {
+                    positions.push(center.delta(q, r, z));
+                }
+            }
+        }
+        positions
+    }
+
+    /// Sorts the positions, and checks that none of them appear twice.
+    fn sorted<T: Ord + Copy + std::fmt::Debug>(positions: &[T]) -> Vec<T> {
+        let set = positions.iter().copied().collect::<BTreeSet<T>>();
+        assert_eq!(set.len(), positions.len(), "Duplicates in {:?}", positions);
+        set.into_iter().collect()
+    }
+
+    #[test]
+    fn diagonals_lie_between_two_neighbours() {
+        for pos in sample_positions() {
+            for i in 0..6 {
+                let diagonal = pos.diagonal(i);
+                assert_eq!(IPos::column_distance(&pos, &diagonal), 2);
+                assert_eq!(diagonal.z(), pos.z());
+                assert_eq!(pos.neighbour(i).neighbour(i + 1), diagonal);
+                assert_eq!(pos.neighbour(i + 1).neighbour(i), diagonal);
+            }
+        }
+    }
+
+    #[test]
+    fn intersect_ranges_matches_a_brute_force_filter() {
+        let a = IPos::new(2, -1, 3);
+        for b in [
+            a,
+            IPos::new(5, -3, 3),
+            IPos::new(-1, 4, 6),
+            IPos::new(9, 0, 3),
+        ] {
+            for (radius_a, radius_b) in [(0, 0), (3, 2), (4, 4), (2, 7)] {
+                let expected = box_around(&a, radius_a as i32, 0)
+                    .into_iter()
+                    .filter(|pos| {
+                        IPos::column_distance(pos, &a) <= radius_a as i32
+                            && IPos::column_distance(pos, &b) <= radius_b as i32
+                    })
+                    .collect::<Vec<IPos>>();
+                let intersection = IPos::intersect_ranges(&a, radius_a, &b, radius_b);
+                assert_eq!(sorted(&intersection), sorted(&expected));
+            }
+        }
+    }
+
+    #[test]
+    fn intersect_ranges_3d_matches_a_brute_force_filter() {
+        let a = IPos::new(2, -1, 3);
+        for b in [
+            a,
+            IPos::new(5, -3, 4),
+            IPos::new(-1, 4, 6),
+            IPos::new(3, 0, -3),
+        ] {
+            for (radius_a, radius_b) in [(0, 0), (3, 2), (4, 4), (2, 7)] {
+                let expected = box_around(&a, radius_a as i32, radius_a as i32)
+                    .into_iter()
+                    .filter(|pos| {
+                        IPos::distance_3d(pos, &a) <= radius_a as i32
+                            && IPos::distance_3d(pos, &b) <= radius_b as i32
+                    })
+                    .collect::<Vec<IPos>>();
+                let intersection = IPos::intersect_ranges_3d(&a, radius_a, &b, radius_b);
+                assert_eq!(sorted(&intersection), sorted(&expected));
+            }
+        }
+    }
+
+    #[test]
+    fn range_3d_is_a_stack_of_hexagons() {
+        let center = IPos::new(-3, 2, 5);
+        for radius in 0..=6 {
+            let range = center.range_3d(radius);
+            let hexagon = 3 * radius * radius + 3 * radius + 1;
+            assert_eq!(range.len(), (hexagon * (2 * radius + 1)) as usize);
+            let expected = box_around(&center, radius as i32, radius as i32)
+                .into_iter()
+                .filter(|pos| IPos::distance_3d(pos, &center) <= radius as i32)
+                .collect::<Vec<IPos>>();
+            assert_eq!(sorted(&range), sorted(&expected));
+        }
+    }
+
+    #[test]
+    fn line_3d_has_one_prism_per_step() {
+        for a in [IPos::default(), IPos::new(5, -9, 3)] {
+            for b in a.range_3d(5) {
+                let line = IPos::line_3d(&a, &b);
+                assert_eq!(line.len(), IPos::distance_3d(&a, &b) as usize + 1);
+                assert_eq!(line.first(), Some(&a));
+                assert_eq!(line.last(), Some(&b));
+                for pair in line.windows(2) {
+                    assert_eq!(IPos::distance_3d(&pair[0], &pair[1]), 1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn column_ids_agree_with_positions() {
+        let a = ColumnId::new(3, -2);
+        let b = ColumnId::new(-1, 4);
+        let (pos_a, pos_b) = (a.as_ipos(0), b.as_ipos(0));
+        let columns = |positions: Vec<IPos>| {
+            positions
+                .iter()
+                .map(IPos::as_column_id)
+                .collect::<Vec<ColumnId>>()
+        };
+        assert_eq!(
+            ColumnId::distance(&a, &b),
+            IPos::column_distance(&pos_a, &pos_b)
+        );
+        assert_eq!(ColumnId::line(&a, &b), columns(IPos::line(&pos_a, &pos_b)));
+        assert_eq!(a.range(3), columns(pos_a.range(3)));
+        assert_eq!(
+            ColumnId::intersect_ranges(&a, 4, &b, 5),
+            columns(IPos::intersect_ranges(&pos_a, 4, &pos_b, 5))
+        );
+        for i in 0..6 {
+            assert_eq!(a.neighbour(i).as_ipos(0), pos_a.neighbour(i));
+            assert_eq!(a.diagonal(i).as_ipos(0), pos_a.diagonal(i));
+            assert_eq!(
+                a.rotate_around(&b, i as i32).as_ipos(0),
+                pos_a.rotate_around(&pos_b, i as i32)
+            );
+        }
+        assert_eq!(a.reflect_q(&b).as_ipos(0), pos_a.reflect_q(&pos_b));
+        assert_eq!(a.reflect_r(&b).as_ipos(0), pos_a.reflect_r(&pos_b));
+        assert_eq!(a.reflect_s(&b).as_ipos(0), pos_a.reflect_s(&pos_b));
+        assert_eq!(
+            sorted(&ColumnId::spiral(4)),
+            sorted(&ColumnId::default().range(4))
+        );
+    }
+
+    #[test]
+    fn chunk_ids_agree_with_positions() {
+        let a = ChunkId::new(3, -2, 1);
+        let b = ChunkId::new(-1, 4, -2);
+        let (pos_a, pos_b) = (IPos(a.0), IPos(b.0));
+        let chunks = |positions: Vec<IPos>| {
+            positions
+                .iter()
+                .map(|pos| ChunkId(pos.0))
+                .collect::<Vec<ChunkId>>()
+        };
+        assert_eq!(ChunkId::distance(&a, &b), IPos::distance(&pos_a, &pos_b));
+        assert_eq!(
+            ChunkId::column_distance(&a, &b),
+            IPos::column_distance(&pos_a, &pos_b)
+        );
+        assert_eq!(ChunkId::line(&a, &b), chunks(IPos::line(&pos_a, &pos_b)));
+        assert_eq!(
+            ChunkId::line_3d(&a, &b),
+            chunks(IPos::line_3d(&pos_a, &pos_b))
+        );
+        assert_eq!(a.range(3), chunks(pos_a.range(3)));
+        assert_eq!(a.range_3d(2), chunks(pos_a.range_3d(2)));
+        assert_eq!(
+            ChunkId::intersect_ranges(&a, 4, &b, 5),
+            chunks(IPos::intersect_ranges(&pos_a, 4, &pos_b, 5))
+        );
+        assert_eq!(
+            ChunkId::intersect_ranges_3d(&a, 4, &b, 5),
+            chunks(IPos::intersect_ranges_3d(&pos_a, 4, &pos_b, 5))
+        );
+        for i in 0..6 {
+            assert_eq!(a.neighbour(i).0, pos_a.neighbour(i).0);
+            assert_eq!(a.diagonal(i).0, pos_a.diagonal(i).0);
+            assert_eq!(
+                a.rotate_around(&b, i as i32).0,
+                pos_a.rotate_around(&pos_b, i as i32).0
+            );
+        }
+        assert_eq!(a.reflect_q(&b).0, pos_a.reflect_q(&pos_b).0);
+        assert_eq!(a.reflect_r(&b).0, pos_a.reflect_r(&pos_b).0);
+        assert_eq!(a.reflect_s(&b).0, pos_a.reflect_s(&pos_b).0);
+    }
+}
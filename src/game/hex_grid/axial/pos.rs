@@ -2,7 +2,6 @@ use std::cmp::Ordering;
 use std::ops::{Add, Sub};
 
 use bevy::prelude::*;
-use once_cell::sync::OnceCell;
 use serde::{Deserialize, Serialize};
 
 use crate::game::hex_grid::axial::IPos;
@@ -15,10 +14,6 @@ pub const RADIUS: f32 = 0.6;
 pub const SQRT_THREE: f32 = 1.7320508;
 /// One sixth Tau or one third Pi. Convenience constant because Tau is just nicer.
 pub const FRAC_TAU_6: f32 = std::f32::consts::FRAC_PI_3;
-/// Matrix for converting coordinates from xyz to qrz.
-static XYZ_TO_QRZ: OnceCell<Mat4> = OnceCell::new();
-/// Matrix for converting coordinates from qrz to xyz.
-static QRZ_TO_XYZ: OnceCell<Mat4> = OnceCell::new();
 
 /// A hexagonal coordinate in the flat-topped axial coordinate system.
 /// This is the floating-point version. There is also an integral version.
@@ -26,7 +21,6 @@ static QRZ_TO_XYZ: OnceCell<Mat4> = OnceCell::new();
 #[derive(Deserialize, Serialize, Default, Copy, Clone, Debug, PartialEq)]
 pub struct Pos(pub(crate) Vec3);
 
-/// TODO: Use matrix calculation instead?
 impl Pos {
     #[must_use]
     pub fn new(q: f32, r: f32, z: f32) -> Self {
@@ -34,10 +28,11 @@ impl Pos {
     }
     #[must_use]
     pub fn from_xyz(xyz: &Vec3) -> Self {
-        Pos(XYZ_TO_QRZ
-            .get()
-            .expect("Pos::setup() was never called!")
-            .transform_vector3(*xyz))
+        Self::new(
+            (2. / 3. * xyz.x) / RADIUS,
+            (-1. / 3. * xyz.x + SQRT_THREE / 3. * xyz.y) / RADIUS,
+            xyz.z / HEIGHT,
+        )
     }
     #[must_use]
     pub fn q(&self) -> f32 {
@@ -82,10 +77,11 @@ impl Pos {
     /// Convert axial coordinates to regular, square coordinates.
     #[must_use]
     pub fn as_xyz(&self) -> Vec3 {
-        QRZ_TO_XYZ
-            .get()
-            .expect("Pos::setup() was never called!")
-            .transform_vector3(self.0)
+        Vec3::new(
+            3. / 2. * self.q() * RADIUS,
+            (SQRT_THREE / 2. * self.q() + SQRT_THREE * self.r()) * RADIUS,
+            self.z() * HEIGHT,
+        )
     }
     /// Round to the nearest integral hex.
     #[must_use]
@@ -105,40 +101,17 @@ impl Pos {
             Self::new(rounded_q, rounded_r, self.z().round())
         }
     }
+    /// Linearly interpolates between a and b. A t of 0 returns a, a t of 1 returns b.
+    #[must_use]
+    pub fn lerp(a: &Self, b: &Self, t: f32) -> Self {
+        Pos(a.0.lerp(b.0, t))
+    }
     /// Returns the distance on the hexagonal grid.
     #[must_use]
     pub fn distance(a: &Self, b: &Self) -> f32 {
         let delta = a - b;
         delta.q().max(delta.r()).max(delta.z())
     }
-
-    /// Call this once at the start of the application.
-    pub(crate) fn setup() {
-        XYZ_TO_QRZ.set(Self::setup_xyz_to_qrz())
-            .expect("Setup was called twice, but you should only call it once at the start of the application.");
-        QRZ_TO_XYZ.set(Self::setup_qrz_to_xyz())
-            .expect("Setup was called twice, but you should only call it once at the start of the application.");
-    }
-    /// Do not call this outside of the setup stage.
-    pub(crate) fn setup_xyz_to_qrz() -> Mat4 {
-        Mat4::from_cols(
-            Vec4::new(2. / 3., -1. / 3., 0., 0.),
-            Vec4::new(0., SQRT_THREE / 3., 0., 0.),
-            Vec4::new(0., 0., 1., 0.),
-            Vec4::new(0., 0., 0., 1.),
-        )
-        .mul_scalar(1. / RADIUS)
-    }
-    /// Do not call this outside of the setup stage.
-    pub(crate) fn setup_qrz_to_xyz() -> Mat4 {
-        Mat4::from_cols(
-            Vec4::new(3. / 2., SQRT_THREE / 2., 0., 0.),
-            Vec4::new(0., SQRT_THREE, 0., 0.),
-            Vec4::new(0., 0., 1., 0.),
-            Vec4::new(0., 0., 0., 1.),
-        )
-        .mul_scalar(RADIUS)
-    }
 }
 
 /// Order by q first, then r, then z.
@@ -1,18 +1,160 @@
+use bevy::math::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::game::hex_grid::axial::{FRAC_TAU_6, HEIGHT, RADIUS, SQRT_THREE};
 use crate::game::hex_grid::biomes::BiomeType;
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+/// The radius of a pillar, in meters.
+const PILLAR_RADIUS: f32 = RADIUS * 0.3;
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Eq, PartialEq, Hash)]
 pub enum BlockType {
     Air,
     Stone,
     Dirt,
     Grass,
     Water,
+    Coal,
+    Copper,
+    Iron,
+    Gold,
+    Wood,
+    Leaves,
+    Cobblestone,
+}
+
+impl BlockType {
+    /// Every block type, in the order they are declared in.
+    #[must_use]
+    pub fn all() -> Vec<BlockType> {
+        vec![
+            BlockType::Air,
+            BlockType::Stone,
+            BlockType::Dirt,
+            BlockType::Grass,
+            BlockType::Water,
+            BlockType::Coal,
+            BlockType::Copper,
+            BlockType::Iron,
+            BlockType::Gold,
+            BlockType::Wood,
+            BlockType::Leaves,
+            BlockType::Cobblestone,
+        ]
+    }
+}
+
+/// The shape of the solid part of a block. Blocks that aren't full leave the rest of their hex
+/// prism empty.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub enum BlockShape {
+    /// The whole hex prism.
+    #[default]
+    Full,
+    /// The bottom half of the hex prism.
+    HalfSlab,
+    /// The bottom quarter of the hex prism, like a layer of snow.
+    Layer,
+    /// A ramp that is full height along one edge and slopes down to nothing at the opposite edge.
+    /// The edge uses the same index as the neighbour on the other side of it.
+    Wedge(u32),
+    /// A thin column in the middle of the hex prism, the full height of the block.
+    Pillar,
+}
+
+impl BlockShape {
+    /// The distance (in meters) from the center of the hex to the corners of the shape.
+    #[must_use]
+    pub fn radius(&self) -> f32 {
+        match self {
+            BlockShape::Pillar => PILLAR_RADIUS,
+            _ => RADIUS,
+        }
+    }
+    /// The height of the top surface at each of the 6 corners of the shape, relative to the bottom
+    /// of the block. Corner i lies between edges i-1 and i.
+    #[must_use]
+    pub fn corner_heights(&self) -> [f32; 6] {
+        match self {
+            BlockShape::Full | BlockShape::Pillar => [HEIGHT; 6],
+            BlockShape::HalfSlab => [HEIGHT / 2.; 6],
+            BlockShape::Layer => [HEIGHT / 4.; 6],
+            BlockShape::Wedge(_) => {
+                let mut heights = [0.; 6];
+                for (i, height) in heights.iter_mut().enumerate() {
+                    let angle = FRAC_TAU_6 * i as f32;
+                    *height = self
+                        .surface_height(Vec2::new(angle.cos(), angle.sin()) * RADIUS)
+                        .unwrap_or(0.);
+                }
+                heights
+            }
+        }
+    }
+    /// Returns the height of the top surface at the given offset (in meters) from the center of
+    /// the hex, relative to the bottom of the block. Returns None if the shape doesn't cover that
+    /// point.
+    #[must_use]
+    pub fn surface_height(&self, offset: Vec2) -> Option<f32> {
+        match self {
+            BlockShape::Full => Some(HEIGHT),
+            BlockShape::HalfSlab => Some(HEIGHT / 2.),
+            BlockShape::Layer => Some(HEIGHT / 4.),
+            BlockShape::Pillar => (offset.length() <= PILLAR_RADIUS).then_some(HEIGHT),
+            BlockShape::Wedge(edge) => {
+                // The distance from the center to the middle of an edge:
+                let inner_radius = RADIUS * SQRT_THREE / 2.;
+                let angle = FRAC_TAU_6 * (*edge as f32 + 0.5);
+                let along = offset.dot(Vec2::new(angle.cos(), angle.sin()));
+                Some((HEIGHT * (0.5 + along / (2. * inner_radius))).clamp(0., HEIGHT))
+            }
+        }
+    }
+    /// The height (from the bottom) up to which the side at the given edge is completely filled.
+    /// A neighbour's face against this side is hidden if it is no higher than this.
+    #[must_use]
+    pub fn side_coverage(&self, edge: u32) -> f32 {
+        match self {
+            BlockShape::Full => HEIGHT,
+            BlockShape::HalfSlab => HEIGHT / 2.,
+            BlockShape::Layer => HEIGHT / 4.,
+            BlockShape::Wedge(wedge_edge) if wedge_edge % 6 == edge % 6 => HEIGHT,
+            BlockShape::Wedge(_) | BlockShape::Pillar => 0.,
+        }
+    }
+    /// The height of this shape's face at the side of the given edge. The face is hidden if the
+    /// neighbour on that side covers it at least this high. Pillars don't reach the sides of the
+    /// hex, so their faces are never hidden by a neighbour.
+    #[must_use]
+    pub fn side_extent(&self, edge: u32) -> f32 {
+        match self {
+            BlockShape::Pillar => f32::INFINITY,
+            BlockShape::Wedge(_) => {
+                let heights = self.corner_heights();
+                let edge = edge.rem_euclid(6) as usize;
+                heights[edge].max(heights[(edge + 1) % 6])
+            }
+            _ => self.side_coverage(edge),
+        }
+    }
+    /// True if the top of the shape is flat, at the full height of the block, and covers the
+    /// whole hex.
+    #[must_use]
+    pub fn covers_top(&self) -> bool {
+        *self == BlockShape::Full
+    }
+    /// True if the bottom of the shape covers the whole hex.
+    #[must_use]
+    pub fn covers_bottom(&self) -> bool {
+        *self != BlockShape::Pillar
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
 pub struct Block {
     pub block_type: BlockType,
     pub biome_type: BiomeType,
+    pub shape: BlockShape,
 }
 
 impl Default for Block {
@@ -20,6 +162,7 @@ impl Default for Block {
         Block {
             block_type: BlockType::Air,
             biome_type: BiomeType::Grassland,
+            shape: BlockShape::Full,
         }
     }
 }
@@ -28,4 +171,8 @@ impl Block {
     pub fn is_solid(&self) -> bool {
         self.block_type != BlockType::Air
     }
+    /// Transparent blocks can be seen through.
+    pub fn is_transparent(&self) -> bool {
+        matches!(self.block_type, BlockType::Air | BlockType::Water)
+    }
 }
@@ -1,165 +1,411 @@
-use bevy::math::{IVec2, Vec3Swizzles};
+use bevy::math::{DVec2, Vec3Swizzles};
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::game::hex_grid::axial::{ChunkId, ColumnId, IPos};
 use crate::game::hex_grid::biomes::generate_biomes;
-use crate::game::hex_grid::block::{Block, BlockType};
-use crate::game::hex_grid::chunks::map_value;
-use crate::game::procedural_generation::noise_generation::{
-    generate_noise, get_noise_profile, NoiseLayer,
-};
+use crate::game::hex_grid::block::{Block, BlockShape, BlockType};
+use crate::game::hex_grid::world_error::WorldError;
+use crate::game::procedural_generation::terrain::TerrainGenerator;
 
-pub const CHUNK_RADIUS: usize = 8;
-pub const CHUNK_HEIGHT: usize = 32;
-pub const CHUNK_BOUNDS: usize = CHUNK_RADIUS * 2 + 1;
+/// The size of the chunks in a world. All chunks in the same world have the same dimensions.
+/// Neither the radius nor the height may be zero; such dimensions are rejected when they are read.
+#[derive(Deserialize, Serialize, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[serde(try_from = "RawChunkDimensions")]
+pub struct ChunkDimensions {
+    /// The number of columns between the center column and the edge of the chunk.
+    pub radius: u32,
+    /// The number of blocks in each column.
+    pub height: u32,
+}
+
+impl Default for ChunkDimensions {
+    fn default() -> Self {
+        Self {
+            radius: 8,
+            height: 32,
+        }
+    }
+}
+
+/// ChunkDimensions as they are written in config files, before they are checked.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawChunkDimensions {
+    radius: u32,
+    height: u32,
+}
+
+impl TryFrom<RawChunkDimensions> for ChunkDimensions {
+    type Error = String;
+
+    fn try_from(raw: RawChunkDimensions) -> Result<Self, Self::Error> {
+        if raw.radius == 0 || raw.height == 0 {
+            return Err(format!(
+                "Chunk dimensions must be at least 1, but got radius {} and height {}.",
+                raw.radius, raw.height
+            ));
+        }
+        Ok(Self {
+            radius: raw.radius,
+            height: raw.height,
+        })
+    }
+}
+
+impl ChunkDimensions {
+    /// The width of the square array that a chunk's columns are stored in.
+    #[must_use]
+    pub fn bounds(&self) -> usize {
+        self.radius as usize * 2 + 1
+    }
+    /// A Vec of relative positions of all columns in a chunk.
+    /// These are positions relative to the chunk's center.
+    #[must_use]
+    pub fn columns(&self) -> Vec<ColumnId> {
+        ColumnId::spiral(self.radius)
+    }
+    /// Checks if the position (relative to the chunk's center) lies inside the chunk.
+    #[must_use]
+    pub fn contains(&self, relative_pos: &IPos) -> bool {
+        IPos::column_distance(relative_pos, &IPos::default()) <= self.radius as i32
+            && relative_pos.z() >= 0
+            && relative_pos.z() < self.height as i32
+    }
+}
+
+/// A single column of blocks, along with its cached heightmap values.
+#[derive(Clone)]
+struct Column {
+    /// The blocks in this column, from bottom to top.
+    blocks: Vec<Block>,
+    /// The z-coordinate of the highest solid block, if there is one.
+    highest_solid: Option<i32>,
+    /// The z-coordinate of the highest block that isn't transparent, if there is one.
+    highest_opaque: Option<i32>,
+}
+
+impl Column {
+    fn new(height: u32) -> Self {
+        Self {
+            blocks: vec![Block::default(); height as usize],
+            highest_solid: None,
+            highest_opaque: None,
+        }
+    }
+    /// Keeps the heightmap up to date after the block at height z was replaced.
+    fn update_heights(&mut self, z: i32) {
+        self.highest_solid = Self::updated_height(&self.blocks, self.highest_solid, z, |block| {
+            block.is_solid()
+        });
+        self.highest_opaque = Self::updated_height(&self.blocks, self.highest_opaque, z, |block| {
+            !block.is_transparent()
+        });
+    }
+    fn updated_height(
+        blocks: &[Block],
+        highest: Option<i32>,
+        z: i32,
+        counts: impl Fn(&Block) -> bool,
+    ) -> Option<i32> {
+        if counts(&blocks[z as usize]) {
+            Some(highest.map_or(z, |highest| highest.max(z)))
+        } else if highest == Some(z) {
+            // The highest block was removed, look for the next one down.
+            (0..z).rev().find(|z| counts(&blocks[*z as usize]))
+        } else {
+            highest
+        }
+    }
+}
 
 #[derive(Resource)]
 pub struct Chunk {
-    /// Outer array is Q-coordinate. Second array is R-coordinate. Inner array is Z-coordinate.
+    dimensions: ChunkDimensions,
+    /// Outer Vec is Q-coordinate. Second Vec is R-coordinate.
     /// Since chunks are hexagonal, not all columns in a square array exist.
-    /// That's why the inner array is wrapped in an Option.
-    blocks: [[Option<[Block; CHUNK_HEIGHT]>; CHUNK_BOUNDS]; CHUNK_BOUNDS],
+    /// That's why the columns are wrapped in an Option.
+    columns: Vec<Vec<Option<Column>>>,
 }
 
-impl Default for Chunk {
-    fn default() -> Self {
+impl Chunk {
+    /// Creates a chunk of the given dimensions that is filled with air.
+    pub fn new(dimensions: ChunkDimensions) -> Self {
+        let bounds = dimensions.bounds();
         let mut chunk = Self {
-            blocks: [[None; CHUNK_BOUNDS]; CHUNK_BOUNDS],
+            dimensions,
+            columns: vec![vec![None; bounds]; bounds],
         };
-        for pos in Chunk::chunk_columns().iter() {
-            let storage_pos = Chunk::column_to_storage_pos(pos);
-            *chunk
-                .blocks
-                .get_mut(storage_pos.q() as usize)
-                .unwrap_or_else(|| panic!("{}", Self::index_out_of_bounds(&pos.as_ipos(0))))
-                .get_mut(storage_pos.r() as usize)
-                .unwrap_or_else(|| panic!("{}", Self::index_out_of_bounds(&pos.as_ipos(0)))) =
-                Some([Block::default(); CHUNK_HEIGHT]);
+        for pos in dimensions.columns().iter() {
+            let storage_pos = chunk.column_to_storage_pos(pos);
+            chunk.columns[storage_pos.q() as usize][storage_pos.r() as usize] =
+                Some(Column::new(dimensions.height));
         }
         chunk
     }
-}
-
-impl Chunk {
-    /// Converts relative position (relative to the chunk center)
-    /// to storage position (internal array storage).
-    /// This is needed because the relative position can be negative; it spirals out from (0,0).
-    fn storage_pos(relative_pos: &IPos) -> IPos {
-        relative_pos + &IPos::new(CHUNK_RADIUS as i32, CHUNK_RADIUS as i32, 0)
+    #[must_use]
+    pub fn dimensions(&self) -> &ChunkDimensions {
+        &self.dimensions
     }
     /// Converts relative position (relative to the chunk center)
     /// to storage position (internal array storage).
     /// This is needed because the relative position can be negative; it spirals out from (0,0).
-    fn column_to_storage_pos(relative_pos: &ColumnId) -> ColumnId {
-        relative_pos + &ColumnId::new(CHUNK_RADIUS as i32, CHUNK_RADIUS as i32)
+    fn column_to_storage_pos(&self, relative_pos: &ColumnId) -> ColumnId {
+        let radius = self.dimensions.radius as i32;
+        relative_pos + &ColumnId::new(radius, radius)
     }
-    #[must_use]
-    pub fn block(&self, pos: &IPos) -> &Block {
-        let storage_pos = Chunk::storage_pos(pos);
-        self.blocks
+    fn try_get_column(&self, column: &ColumnId) -> Result<&Column, WorldError> {
+        let storage_pos = self.column_to_storage_pos(column);
+        self.columns
             .get(storage_pos.q() as usize)
-            .unwrap_or_else(|| panic!("{}", Self::index_out_of_bounds(pos)))
-            .get(storage_pos.r() as usize)
-            .unwrap_or_else(|| panic!("{}", Self::index_out_of_bounds(pos)))
+            .and_then(|row| row.get(storage_pos.r() as usize))
+            .ok_or(WorldError::OutOfBounds(column.as_ipos(0)))?
             .as_ref()
-            .unwrap_or_else(|| panic!("{}", Self::index_out_of_bounds(pos)))
-            .get(storage_pos.z() as usize)
-            .unwrap_or_else(|| panic!("{}", Self::index_out_of_bounds(pos)))
+            .ok_or(WorldError::InvalidColumn(*column))
     }
-    pub fn set(&mut self, pos: &IPos, block: Block) {
-        let storage_pos = Chunk::storage_pos(pos);
-        *self
-            .blocks
+    fn try_get_column_mut(&mut self, column: &ColumnId) -> Result<&mut Column, WorldError> {
+        let storage_pos = self.column_to_storage_pos(column);
+        self.columns
             .get_mut(storage_pos.q() as usize)
-            .unwrap_or_else(|| panic!("{}", Self::index_out_of_bounds(pos)))
-            .get_mut(storage_pos.r() as usize)
-            .unwrap_or_else(|| panic!("{}", Self::index_out_of_bounds(pos)))
+            .and_then(|row| row.get_mut(storage_pos.r() as usize))
+            .ok_or(WorldError::OutOfBounds(column.as_ipos(0)))?
             .as_mut()
-            .unwrap_or_else(|| panic!("{}", Self::index_out_of_bounds(pos)))
-            .get_mut(storage_pos.z() as usize)
-            .unwrap_or_else(|| panic!("{}", Self::index_out_of_bounds(pos))) = block;
-    }
-    fn index_out_of_bounds(pos: &IPos) -> String {
-        format!(
-            "Chunk lookup index out of bounds. Tried to access \
-        (q={},r={},z={}). Expected axial coordinates relative to chunk; they should not exceed \
-        chunk length/width/height.",
-            pos.q(),
-            pos.r(),
-            pos.z(),
-        )
-    }
-
-    pub fn from_noise(chunk_id: &ChunkId) -> Self {
-        let noise_pos = Chunk::storage_pos(&chunk_id.center_pos())
-            .as_xyz() // TODO: Do we need storage pos here?
-            .xy()
-            .as_dvec2();
-        let noise_bounds = IVec2::splat(CHUNK_BOUNDS as i32);
-        let elevation_noise = generate_noise(
-            noise_pos,
-            noise_bounds,
-            get_noise_profile(NoiseLayer::Elevation),
-        );
-        let humidity_noise = generate_noise(
-            noise_pos,
-            noise_bounds,
-            get_noise_profile(NoiseLayer::Humidity),
-        );
-        let temperature_noise = generate_noise(
-            noise_pos,
-            noise_bounds,
-            get_noise_profile(NoiseLayer::Temperature),
-        );
+            .ok_or(WorldError::InvalidColumn(*column))
+    }
+    /// Returns all blocks in the column, from bottom to top.
+    /// The column is relative to the chunk center.
+    pub fn try_column(&self, column: &ColumnId) -> Result<&[Block], WorldError> {
+        Ok(&self.try_get_column(column)?.blocks)
+    }
+    /// Returns the block at the given position, relative to the chunk center.
+    pub fn try_block(&self, pos: &IPos) -> Result<&Block, WorldError> {
+        self.try_column(&pos.as_column_id())?
+            .get(pos.z() as usize)
+            .ok_or(WorldError::OutOfBounds(*pos))
+    }
+    /// Replaces the block at the given position, relative to the chunk center.
+    /// This also keeps the heightmaps up to date.
+    pub fn try_set(&mut self, pos: &IPos, block: Block) -> Result<(), WorldError> {
+        let column = self.try_get_column_mut(&pos.as_column_id())?;
+        *column
+            .blocks
+            .get_mut(pos.z() as usize)
+            .ok_or(WorldError::OutOfBounds(*pos))? = block;
+        column.update_heights(pos.z());
+        Ok(())
+    }
+    /// Returns the z-coordinate (relative to the chunk) of the highest solid block in the column,
+    /// or None if the column is empty.
+    pub fn surface_height(&self, column: &ColumnId) -> Result<Option<i32>, WorldError> {
+        Ok(self.try_get_column(column)?.highest_solid)
+    }
+    /// Returns the z-coordinate (relative to the chunk) of the highest block in the column that
+    /// isn't transparent, or None if there is none.
+    pub fn opaque_height(&self, column: &ColumnId) -> Result<Option<i32>, WorldError> {
+        Ok(self.try_get_column(column)?.highest_opaque)
+    }
+    /// Like try_block, but panics if the position lies outside of the chunk.
+    #[must_use]
+    pub fn block(&self, pos: &IPos) -> &Block {
+        self.try_block(pos).unwrap_or_else(|err| panic!("{}", err))
+    }
+    /// Like try_set, but panics if the position lies outside of the chunk.
+    pub fn set(&mut self, pos: &IPos, block: Block) {
+        self.try_set(pos, block)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    pub fn from_noise(
+        chunk_id: &ChunkId,
+        dimensions: ChunkDimensions,
+        terrain: &TerrainGenerator,
+    ) -> Self {
+        let mut chunk = Chunk::new(dimensions);
+        let center = chunk_id.center_pos(&dimensions);
+        let columns = dimensions.columns();
+        // Noise is sampled at each column's absolute position in the world,
+        // so that neighbouring chunks line up seamlessly.
+        let absolute_columns = columns
+            .iter()
+            .map(|column| (column + center).as_column_id())
+            .collect::<Vec<ColumnId>>();
+        let noise_positions = absolute_columns
+            .iter()
+            .map(|column| column.as_ipos(0).as_xyz().xy().as_dvec2())
+            .collect::<Vec<DVec2>>();
+        let terrain_height = |column: &ColumnId| terrain.terrain_height(column, &dimensions);
+        let water = terrain.hydrology.water(&absolute_columns, &terrain_height);
+        let humidity_noise = terrain.humidity.get_all(&noise_positions);
+        let temperature_noise = terrain.temperature.get_all(&noise_positions);
 
         let biomes = generate_biomes(humidity_noise, temperature_noise);
-        let mut chunk = Chunk::default();
 
-        let mut min_elevation = 0.0;
-        let mut max_elevation = 0.0;
-        for ele in elevation_noise.iter() {
-            if ele < &min_elevation {
-                min_elevation = *ele;
+        for (index, qr) in columns.iter().enumerate() {
+            let mut z_elevation = terrain_height(&absolute_columns[index]);
+            let column_water = water[index];
+            if let Some(water) = column_water {
+                // Rivers carve their channels into the terrain:
+                z_elevation = z_elevation.min(water.bed);
             }
-            if ele > &max_elevation {
-                max_elevation = *ele;
-            }
-        }
-        // debug!(
-        //     "min elevation: {}, max elevation: {}",
-        //     min_elevation, max_elevation
-        // );
-
-        for qr in Chunk::chunk_columns().iter() {
-            let noise_pos = Chunk::column_to_storage_pos(qr);
-            let index = (noise_pos.r() as usize * CHUNK_BOUNDS) + noise_pos.q() as usize;
-            let elevation = elevation_noise[index];
-            let z_elevation = map_value(elevation, -1.0, 1.0, 0.0, CHUNK_HEIGHT as f64);
-            // debug!("Elevation: {} \t {}", elevation, z_elevation);
             let biome_type = biomes[index];
 
-            for z in 0..CHUNK_HEIGHT {
-                let block_type = if z < z_elevation as usize {
+            for z in 0..dimensions.height as i32 {
+                let pos = (qr + center).delta(0, 0, z);
+                let block_type = if terrain.caves.is_solid(&pos, z_elevation) {
                     BlockType::Stone
+                } else if column_water.map_or(false, |water| {
+                    pos.z() as f64 + 1. > water.bed && (pos.z() as f64) < water.level
+                }) {
+                    BlockType::Water
                 } else {
                     BlockType::Air
                 };
-                let pos = qr.as_ipos(z as i32);
                 chunk.set(
-                    &pos,
+                    &qr.as_ipos(z),
                     Block {
                         block_type,
                         biome_type,
+                        shape: BlockShape::Full,
                     },
                 );
             }
         }
+        terrain.ores.populate(&mut chunk, chunk_id);
         chunk
     }
 
-    /// A Vec of relative positions of all blocks in a chunk.
+    /// A Vec of relative positions of all columns in this chunk.
     /// These are positions relative to the chunk's center.
-    pub fn chunk_columns() -> Vec<ColumnId> {
-        ColumnId::spiral(CHUNK_RADIUS as u32)
+    pub fn columns(&self) -> Vec<ColumnId> {
+        self.dimensions.columns()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::hex_grid::templates::StructureLibrary;
+    use crate::game::procedural_generation::noise_graph::ELEVATION_GRAPH;
+    use crate::io::config::WorldGenConfig;
+    use bevy::utils::HashMap;
+
+    const SIZES: [(u32, u32); 5] = [(1, 1), (2, 5), (3, 16), (8, 32), (13, 7)];
+
+    #[test]
+    fn columns_form_a_hexagon_of_the_given_radius() {
+        for (radius, height) in SIZES {
+            let dimensions = ChunkDimensions { radius, height };
+            let columns = dimensions.columns();
+            let r = radius as usize;
+            assert_eq!(columns.len(), 3 * r * r + 3 * r + 1);
+            let unique = columns
+                .iter()
+                .collect::<std::collections::HashSet<&ColumnId>>();
+            assert_eq!(unique.len(), columns.len());
+            for column in columns.iter() {
+                assert!(dimensions.contains(&column.as_ipos(0)));
+                assert!(dimensions.contains(&column.as_ipos(height as i32 - 1)));
+                assert!(!dimensions.contains(&column.as_ipos(-1)));
+                assert!(!dimensions.contains(&column.as_ipos(height as i32)));
+            }
+            for column in ColumnId::ring(radius + 1) {
+                assert!(!dimensions.contains(&column.as_ipos(0)));
+            }
+        }
+    }
+
+    #[test]
+    fn chunk_stores_every_block_within_its_dimensions() {
+        let stone = Block {
+            block_type: BlockType::Stone,
+            ..Default::default()
+        };
+        for (radius, height) in SIZES {
+            let dimensions = ChunkDimensions { radius, height };
+            let mut chunk = Chunk::new(dimensions);
+            for column in dimensions.columns() {
+                for z in 0..height as i32 {
+                    chunk.set(&column.as_ipos(z), stone);
+                }
+                assert_eq!(chunk.surface_height(&column), Ok(Some(height as i32 - 1)));
+                assert!(chunk.try_block(&column.as_ipos(height as i32)).is_err());
+            }
+            for column in ColumnId::ring(radius + 1) {
+                assert!(chunk.try_set(&column.as_ipos(0), stone).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn zero_dimensions_are_rejected() {
+        let parse = ron::de::from_str::<ChunkDimensions>;
+        assert_eq!(
+            parse("(radius: 3, height: 16)"),
+            Ok(ChunkDimensions {
+                radius: 3,
+                height: 16
+            })
+        );
+        assert!(parse("(radius: 0, height: 16)").is_err());
+        assert!(parse("(radius: 3, height: 0)").is_err());
+    }
+
+    #[test]
+    fn terrain_is_continuous_across_chunk_borders() {
+        // Steep hills without caves, rivers or anything else that could cause a jump on purpose:
+        let elevation = "Simplex((scale: 40., octaves: 3, persistence: 0.5, lacunarity: 2.))";
+        let config = WorldGenConfig {
+            seed: 42,
+            graphs: [(
+                ELEVATION_GRAPH.to_string(),
+                ron::from_str(elevation).unwrap(),
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let terrain = TerrainGenerator::from_config(&config, &StructureLibrary::default());
+        let dimensions = ChunkDimensions::default();
+        let center_id = ChunkId::new(2, -1, 0);
+        for neighbour_id in center_id.range(1) {
+            if neighbour_id == center_id {
+                continue;
+            }
+            // The surface height of every column in both chunks, by absolute position:
+            let mut surface = HashMap::new();
+            for id in [center_id, neighbour_id] {
+                let chunk = Chunk::from_noise(&id, dimensions, &terrain);
+                for column in dimensions.columns() {
+                    let height = chunk.surface_height(&column).unwrap().unwrap_or(-1);
+                    surface.insert(column + id.center_pos(&dimensions), height);
+                }
+            }
+            let mut steepest_within = 0;
+            let mut steepest_across = 0;
+            for (pos, height) in surface.iter() {
+                for neighbour in (0..6).map(|index| pos.neighbour(index)) {
+                    if let Some(other) = surface.get(&neighbour) {
+                        let step = (height - other).abs();
+                        if ChunkId::from_block_pos(pos, &dimensions)
+                            == ChunkId::from_block_pos(&neighbour, &dimensions)
+                        {
+                            steepest_within = steepest_within.max(step);
+                        } else {
+                            steepest_across = steepest_across.max(step);
+                        }
+                    }
+                }
+            }
+            assert!(steepest_within > 0, "The terrain is flat");
+            assert!(
+                steepest_across <= steepest_within,
+                "The terrain jumps {} blocks at the border between {:?} and {:?}, \
+                but never more than {} within them.",
+                steepest_across,
+                center_id,
+                neighbour_id,
+                steepest_within
+            );
+        }
     }
 }
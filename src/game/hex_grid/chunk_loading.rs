@@ -3,7 +3,11 @@ use bevy::utils::HashSet;
 
 use crate::game::hex_grid::axial::{ChunkId, ColumnId};
 use crate::game::hex_grid::chunks::Chunks;
+use crate::game::hex_grid::templates::StructureLibrary;
 use crate::game::meshes::hexagon::create_chunk_mesh;
+use crate::io::asset_loading::ModDirectories;
+use crate::io::config::{WorldConfig, WorldGenConfig};
+use crate::io::world_meta::WorldMeta;
 
 /// Apply this component to an entity with a Transform.
 /// The game will make sure chunks are loaded around the entity.
@@ -46,10 +50,56 @@ pub struct LoadUnloadEvent {
     pub are_rendered: HashSet<ChunkId>,
 }
 
+/// Sent when a chunk was generated and added to Chunks.
+pub struct ChunkLoadedEvent {
+    pub id: ChunkId,
+}
+
+/// Sent when a chunk was culled from Chunks.
+pub struct ChunkUnloadedEvent {
+    pub id: ChunkId,
+}
+
+/// Sent once per frame in which blocks were changed, listing all chunks that were affected.
+pub struct ChunksChangedEvent {
+    pub ids: HashSet<ChunkId>,
+}
+
+/// Starts out with an empty world. The seed, preset and chunk dimensions are those the world was
+/// created with; a new world takes them from the configs. The other generator settings always
+/// come from the worldgen config.
+pub fn setup_chunks(
+    mut commands: Commands,
+    mut chunks: ResMut<Chunks>,
+    config: Res<WorldConfig>,
+    worldgen: Res<WorldGenConfig>,
+    structures: Res<StructureLibrary>,
+    mods: Res<ModDirectories>,
+) {
+    let new_world = || WorldMeta::from_configs(&config, &worldgen);
+    let meta = match WorldMeta::load_or_create(&WorldMeta::world_dir(), new_world) {
+        Ok(meta) => meta,
+        Err(err) => {
+            error!("{} Using the settings from the configs instead.", err);
+            new_world()
+        }
+    };
+    let worldgen = WorldGenConfig {
+        seed: meta.seed,
+        ..worldgen.clone()
+    };
+    *chunks = Chunks::new(
+        meta.chunk_dimensions,
+        meta.preset.create_generator(&worldgen, &structures, &mods),
+    );
+    commands.insert_resource(meta);
+}
+
 /// A system meant to run periodically (not every tick).
 /// It checks if a chunk load / unload cycle should be triggered. If so, it triggers an event.
 pub fn check_chunk_loader(
     mut events: EventWriter<LoadUnloadEvent>,
+    chunks: Res<Chunks>,
     query_loaders: Query<(&Transform, &ChunkLoader)>,
     query_mesh: Query<(Entity, &ChunkMesh)>,
 ) {
@@ -58,7 +108,7 @@ pub fn check_chunk_loader(
     let mut may_be_rendered = HashSet::default();
     let mut must_be_rendered = HashSet::default();
     for (transform, loader) in query_loaders.iter() {
-        let center_chunk = ChunkId::from_xyz(&transform.translation);
+        let center_chunk = ChunkId::from_xyz(&transform.translation, chunks.dimensions());
         let center_chunk = ChunkId::new(center_chunk.q(), center_chunk.r(), 0);
         for qr in ColumnId::spiral(loader.radius_min).drain(0..) {
             must_be_rendered.insert(qr + center_chunk);
@@ -89,6 +139,8 @@ pub fn check_chunk_loader(
 pub fn load_unload_chunks(
     mut commands: Commands,
     mut events: EventReader<LoadUnloadEvent>,
+    mut loaded_events: EventWriter<ChunkLoadedEvent>,
+    mut unloaded_events: EventWriter<ChunkUnloadedEvent>,
     mut chunks: ResMut<Chunks>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut std_mats: ResMut<Assets<StandardMaterial>>,
@@ -104,7 +156,9 @@ pub fn load_unload_chunks(
         .last()
         .expect("This system is triggered by the event, so the event should be present.");
     // Unload chunks that don't need to be loaded:
-    chunks.cull_chunks(to_be_loaded);
+    for id in chunks.cull_chunks(to_be_loaded) {
+        unloaded_events.send(ChunkUnloadedEvent { id });
+    }
     // Despawn meshes that don't need to be rendered:
     for (entity, mesh) in query_mesh.iter() {
         if !to_be_rendered.contains(&mesh.id) {
@@ -118,19 +172,53 @@ pub fn load_unload_chunks(
         .collect::<Vec<&ChunkId>>();
     not_yet_loaded.iter().for_each(|id| {
         chunks.generate_chunk(**id);
+        loaded_events.send(ChunkLoadedEvent { id: **id });
     });
     // Create meshes for chunks that should be rendered:
     to_be_rendered
         .iter()
         .filter(|id| !are_rendered.contains(id))
-        .for_each(|id| {
-            commands
-                .spawn(MaterialMeshBundle {
-                    mesh: meshes.add(create_chunk_mesh(&chunks, id)),
-                    transform: Transform::from_translation(id.center_pos().as_xyz()),
-                    material: std_mats.add(Color::WHITE.into()),
-                    ..default()
-                })
-                .insert(ChunkMesh { id: *id });
+        .for_each(|id| match create_chunk_mesh(&chunks, id) {
+            Ok(mesh) => {
+                commands
+                    .spawn(MaterialMeshBundle {
+                        mesh: meshes.add(mesh),
+                        transform: Transform::from_translation(
+                            id.center_pos(chunks.dimensions()).as_xyz(),
+                        ),
+                        material: std_mats.add(Color::WHITE.into()),
+                        ..default()
+                    })
+                    .insert(ChunkMesh { id: *id });
+            }
+            Err(err) => warn!("Could not create chunk mesh: {}", err),
         });
 }
+
+/// Rebuilds the meshes of chunks whose blocks were changed,
+/// and notifies other systems of the change with a single event.
+pub fn remesh_dirty_chunks(
+    mut chunks: ResMut<Chunks>,
+    mut changed_events: EventWriter<ChunksChangedEvent>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    query_mesh: Query<(&ChunkMesh, &Handle<Mesh>)>,
+) {
+    let dirty = chunks.take_dirty();
+    if dirty.is_empty() {
+        return;
+    }
+    for (chunk_mesh, handle) in query_mesh.iter() {
+        if !dirty.contains(&chunk_mesh.id) {
+            continue;
+        }
+        match create_chunk_mesh(&chunks, &chunk_mesh.id) {
+            Ok(new_mesh) => {
+                if let Some(mesh) = meshes.get_mut(handle) {
+                    *mesh = new_mesh;
+                }
+            }
+            Err(err) => warn!("Could not rebuild chunk mesh: {}", err),
+        }
+    }
+    changed_events.send(ChunksChangedEvent { ids: dirty });
+}
@@ -1,23 +1,77 @@
+use std::collections::BTreeSet;
+
 use bevy::math::IVec2;
 use bevy::utils::{HashMap, HashSet};
 
-use crate::game::hex_grid::axial::{ChunkId, IPos};
-use crate::game::hex_grid::block::Block;
-use crate::game::hex_grid::chunk::{Chunk, CHUNK_BOUNDS};
+use crate::game::hex_grid::axial::{ChunkId, ColumnId, IPos};
+use crate::game::hex_grid::block::{Block, BlockType};
+use crate::game::hex_grid::chunk::{Chunk, ChunkDimensions};
+use crate::game::hex_grid::region::Region;
+use crate::game::hex_grid::world_error::WorldError;
+use crate::game::procedural_generation::presets::VoidGenerator;
+use crate::game::procedural_generation::world_generator::WorldGenerator;
 use bevy::ecs::system::Resource;
-#[derive(Default, Resource)]
+#[derive(Resource)]
 pub struct Chunks {
+    /// The dimensions of every chunk in this world.
+    dimensions: ChunkDimensions,
     chunks: HashMap<ChunkId, Chunk>,
+    /// The z-coordinates of the loaded chunks, keyed by the column of chunks they are stacked in.
+    stacks: HashMap<IVec2, BTreeSet<i32>>,
+    /// Chunks whose blocks changed since the last time the dirty chunks were taken.
+    dirty: HashSet<ChunkId>,
+    /// Generates new chunks.
+    generator: Box<dyn WorldGenerator>,
+    /// Blocks of features that grew out of one chunk into another. Keyed by the chunk the blocks
+    /// are in, and then by the chunk the feature grew out of. They are kept for as long as the
+    /// chunk they grew out of is loaded, so they can be applied again if the other chunk is
+    /// regenerated.
+    pending_writes: HashMap<ChunkId, HashMap<ChunkId, Vec<(IPos, Block)>>>,
+    /// The feature blocks in each chunk, mapped to the chunk that their feature grew out of.
+    feature_owners: HashMap<ChunkId, HashMap<IPos, ChunkId>>,
+}
+
+impl Default for Chunks {
+    /// An empty world, in which nothing is ever generated.
+    fn default() -> Self {
+        Self::new(
+            ChunkDimensions::default(),
+            Box::new(VoidGenerator::new(0, 0, BlockType::Air)),
+        )
+    }
 }
 
 impl Chunks {
+    pub fn new(dimensions: ChunkDimensions, generator: Box<dyn WorldGenerator>) -> Self {
+        Self {
+            dimensions,
+            chunks: HashMap::default(),
+            stacks: HashMap::default(),
+            dirty: HashSet::default(),
+            generator,
+            pending_writes: HashMap::default(),
+            feature_owners: HashMap::default(),
+        }
+    }
+    #[must_use]
+    pub fn dimensions(&self) -> &ChunkDimensions {
+        &self.dimensions
+    }
+    /// Returns the id of the chunk that contains the given block.
+    #[must_use]
+    pub fn chunk_id(&self, pos: &IPos) -> ChunkId {
+        ChunkId::from_block_pos(pos, &self.dimensions)
+    }
+    /// Returns the block at the given absolute position.
+    pub fn try_block(&self, pos: &IPos) -> Result<&Block, WorldError> {
+        let chunk_id = self.chunk_id(pos);
+        let pos_relative = pos - &chunk_id.center_pos(&self.dimensions);
+        self.try_get_chunk(&chunk_id)?.try_block(&pos_relative)
+    }
+    /// Returns the block at the given absolute position, or None if its chunk isn't loaded.
     #[must_use]
     pub fn block(&self, pos: &IPos) -> Option<&Block> {
-        let chunk_id = ChunkId::from_block_pos(pos);
-        let pos_relative = pos - &chunk_id.center_pos();
-        self.chunks
-            .get(&chunk_id)
-            .map(|chunk| chunk.block(&pos_relative))
+        self.try_block(pos).ok()
     }
     #[must_use]
     pub fn is_solid(&self, pos_absolute: &IPos) -> bool {
@@ -25,22 +79,185 @@ impl Chunks {
             .map(|block| block.is_solid())
             .unwrap_or(false)
     }
+    /// Replaces the block at the given absolute position.
+    /// The chunk it is in, and any neighbouring chunks it borders on, are marked as dirty.
+    pub fn set_block(&mut self, pos: &IPos, block: Block) -> Result<(), WorldError> {
+        let chunk_id = self.chunk_id(pos);
+        let pos_relative = pos - &chunk_id.center_pos(&self.dimensions);
+        self.try_get_chunk_mut(&chunk_id)?
+            .try_set(&pos_relative, block)?;
+        // The block no longer belongs to a feature, so other features can't replace it:
+        if let Some(owners) = self.feature_owners.get_mut(&chunk_id) {
+            owners.remove(pos);
+        }
+        self.mark_dirty(pos);
+        Ok(())
+    }
+    /// Replaces a batch of blocks at once.
+    /// Either all blocks are set, or none are: if any of the positions is invalid,
+    /// the first error is returned and the world is left unchanged.
+    pub fn set_blocks(
+        &mut self,
+        blocks: impl IntoIterator<Item = (IPos, Block)>,
+    ) -> Result<(), WorldError> {
+        let blocks = blocks.into_iter().collect::<Vec<(IPos, Block)>>();
+        if let Some(err) = blocks.iter().find_map(|(pos, _)| self.try_block(pos).err()) {
+            return Err(err);
+        }
+        for (pos, block) in blocks {
+            self.set_block(&pos, block)?;
+        }
+        Ok(())
+    }
+    /// Returns the z-coordinate of the highest solid block in the column,
+    /// looking through all loaded chunks that are stacked on top of each other.
+    /// Returns None if none of the loaded blocks in the column are solid.
+    #[must_use]
+    pub fn surface_height(&self, column: &ColumnId) -> Option<i32> {
+        self.column_height(column, Chunk::surface_height)
+    }
+    /// Returns the z-coordinate of the highest block in the column that isn't transparent,
+    /// looking through all loaded chunks that are stacked on top of each other.
+    #[must_use]
+    pub fn opaque_height(&self, column: &ColumnId) -> Option<i32> {
+        self.column_height(column, Chunk::opaque_height)
+    }
+    fn column_height(
+        &self,
+        column: &ColumnId,
+        height: impl Fn(&Chunk, &ColumnId) -> Result<Option<i32>, WorldError>,
+    ) -> Option<i32> {
+        let bottom_chunk = self.chunk_id(&column.as_ipos(0));
+        let column_relative =
+            (column.as_ipos(0) - bottom_chunk.center_pos(&self.dimensions)).as_column_id();
+        self.stacks
+            .get(&bottom_chunk.qr())?
+            .iter()
+            .rev()
+            .find_map(|z| {
+                let id = ChunkId::new(bottom_chunk.q(), bottom_chunk.r(), *z);
+                height(self.chunks.get(&id)?, &column_relative)
+                    .ok()
+                    .flatten()
+                    .map(|height| height + id.center_pos(&self.dimensions).z())
+            })
+    }
+    /// Iterates over all blocks in the region, along with their absolute positions.
+    /// Blocks in chunks that aren't loaded are skipped.
+    pub fn blocks_in_region<'a>(
+        &'a self,
+        region: &Region,
+    ) -> impl Iterator<Item = (IPos, &'a Block)> + 'a {
+        region
+            .positions()
+            .filter_map(|pos| self.block(&pos).map(|block| (pos, block)))
+    }
+    /// Returns the ids of all chunks that changed since the last call, and clears them.
+    /// Each chunk is only included once, no matter how many of its blocks changed.
+    pub fn take_dirty(&mut self) -> HashSet<ChunkId> {
+        std::mem::take(&mut self.dirty)
+    }
+    /// Marks the chunk containing the position as dirty, as well as the chunks containing
+    /// its neighbours. Their meshes may have faces that are hidden or revealed by the change.
+    fn mark_dirty(&mut self, pos: &IPos) {
+        let mut neighbours = pos.neighbours_3d();
+        neighbours.push(*pos);
+        for neighbour in neighbours.iter() {
+            let chunk_id = self.chunk_id(neighbour);
+            if self.contains(&chunk_id) {
+                self.dirty.insert(chunk_id);
+            }
+        }
+    }
     /// Removes chunks from memory that are not in the given whitelist.
-    pub fn cull_chunks(&mut self, allowed: &HashSet<ChunkId>) {
-        let _ = self.chunks.drain_filter(|key, _| !allowed.contains(key));
+    /// Returns the ids of the chunks that were removed.
+    pub fn cull_chunks(&mut self, allowed: &HashSet<ChunkId>) -> Vec<ChunkId> {
+        self.dirty.retain(|id| allowed.contains(id));
+        for writes in self.pending_writes.values_mut() {
+            writes.retain(|source, _| allowed.contains(source));
+        }
+        self.pending_writes.retain(|_, writes| !writes.is_empty());
+        self.feature_owners.retain(|id, _| allowed.contains(id));
+        let removed = self
+            .chunks
+            .drain_filter(|key, _| !allowed.contains(key))
+            .map(|(key, _)| key)
+            .collect::<Vec<ChunkId>>();
+        for id in removed.iter() {
+            if let Some(stack) = self.stacks.get_mut(&id.qr()) {
+                stack.remove(&id.z());
+            }
+        }
+        self.stacks.retain(|_, stack| !stack.is_empty());
+        removed
     }
     #[must_use]
     pub fn contains(&self, id: &ChunkId) -> bool {
         self.chunks.contains_key(id)
     }
 
+    /// Generates the chunk with this world's generator, then places features on the generated terrain.
+    /// Features from neighbouring chunks that reach into this chunk are added as well,
+    /// and parts of this chunk's features that reach into other chunks are either written
+    /// into them directly, or queued until those chunks are generated.
+    /// Where features overlap, the one that grew out of the lowest ChunkId wins, so the
+    /// result doesn't depend on the order in which chunks are generated.
     pub fn generate_chunk(&mut self, chunk_id: ChunkId) {
-        let chunk = Chunk::from_noise(&chunk_id);
+        let mut chunk = self.generator.generate_chunk(&chunk_id, self.dimensions);
+        // Features are placed on the bare terrain, so they don't depend on which neighbours'
+        // features happened to be written into the chunk already:
+        let features = self.generator.place_features(&chunk, &chunk_id);
+        let center = chunk_id.center_pos(&self.dimensions);
+        let mut owners = HashMap::default();
+        if let Some(writes) = self.pending_writes.get(&chunk_id) {
+            for (source, writes) in writes.iter() {
+                for (pos, block) in writes.iter() {
+                    place_feature_block(&mut chunk, &mut owners, pos, &center, *block, *source);
+                }
+            }
+        }
         self.chunks.insert(chunk_id, chunk);
+        self.feature_owners.insert(chunk_id, owners);
+        self.stacks
+            .entry(chunk_id.qr())
+            .or_default()
+            .insert(chunk_id.z());
+        for (pos, block) in features {
+            let target_id = self.chunk_id(&pos);
+            if target_id != chunk_id {
+                self.pending_writes
+                    .entry(target_id)
+                    .or_default()
+                    .entry(chunk_id)
+                    .or_default()
+                    .push((pos, block));
+            }
+            if let Some(target) = self.chunks.get_mut(&target_id) {
+                let owners = self.feature_owners.entry(target_id).or_default();
+                let target_center = target_id.center_pos(&self.dimensions);
+                let placed =
+                    place_feature_block(target, owners, &pos, &target_center, block, chunk_id);
+                if placed && target_id != chunk_id {
+                    // The target's mesh may already exist, so it needs to be rebuilt:
+                    self.mark_dirty(&pos);
+                }
+            }
+        }
     }
 
-    pub fn get_chunk(&self, chunk_id: &ChunkId) -> &Chunk {
-        self.chunks.get(chunk_id).unwrap() // TODO: fix this unsafe unwrap?
+    #[must_use]
+    pub fn get_chunk(&self, chunk_id: &ChunkId) -> Option<&Chunk> {
+        self.chunks.get(chunk_id)
+    }
+    pub fn try_get_chunk(&self, chunk_id: &ChunkId) -> Result<&Chunk, WorldError> {
+        self.chunks
+            .get(chunk_id)
+            .ok_or(WorldError::UnloadedChunk(*chunk_id))
+    }
+    pub fn try_get_chunk_mut(&mut self, chunk_id: &ChunkId) -> Result<&mut Chunk, WorldError> {
+        self.chunks
+            .get_mut(chunk_id)
+            .ok_or(WorldError::UnloadedChunk(*chunk_id))
     }
 
     #[allow(dead_code)] // Will be implemented later.
@@ -50,27 +267,51 @@ impl Chunks {
     pub fn save_chunk_to_disk(position: IVec2) {}
 }
 
-pub fn qr_to_index(position: &IPos) -> usize {
-    (position.r() as usize * CHUNK_BOUNDS) + position.q() as usize
+/// Features only grow into blocks that aren't solid, so they never cut into the terrain.
+/// A block of another feature is only replaced if that feature grew out of a higher ChunkId
+/// than the source of this one. Returns true if the block was placed.
+fn place_feature_block(
+    chunk: &mut Chunk,
+    owners: &mut HashMap<IPos, ChunkId>,
+    pos: &IPos,
+    chunk_center: &IPos,
+    block: Block,
+    source: ChunkId,
+) -> bool {
+    let pos_relative = pos - chunk_center;
+    let may_place = match (chunk.try_block(&pos_relative), owners.get(pos)) {
+        (Err(_), _) => false,
+        (Ok(_), Some(owner)) => source < *owner,
+        (Ok(existing), None) => !existing.is_solid(),
+    };
+    if may_place && chunk.try_set(&pos_relative, block).is_ok() {
+        owners.insert(*pos, source);
+        true
+    } else {
+        false
+    }
+}
+
+pub fn qr_to_index(position: &IPos, bounds: usize) -> usize {
+    (position.r() as usize * bounds) + position.q() as usize
 }
 
-pub fn index_to_qr(index: usize) -> IPos {
-    let q = index as i32 % CHUNK_BOUNDS as i32;
-    let r = index as i32 / CHUNK_BOUNDS as i32;
+pub fn index_to_qr(index: usize, bounds: usize) -> IPos {
+    let q = index as i32 % bounds as i32;
+    let r = index as i32 / bounds as i32;
     let z = 0;
     IPos::new(q, r, z)
 }
 
-pub fn qrz_to_index(position: IPos) -> usize {
-    ((position.z() * CHUNK_BOUNDS as i32 * CHUNK_BOUNDS as i32)
-        + (position.r() * CHUNK_BOUNDS as i32)
-        + position.q()) as usize
+pub fn qrz_to_index(position: IPos, bounds: usize) -> usize {
+    ((position.z() * bounds as i32 * bounds as i32) + (position.r() * bounds as i32) + position.q())
+        as usize
 }
 
-pub fn index_to_qrz(index: usize) -> IPos {
-    let z = index / (CHUNK_BOUNDS * CHUNK_BOUNDS);
-    let r = (index - (z * CHUNK_BOUNDS * CHUNK_BOUNDS)) / CHUNK_BOUNDS;
-    let q = index - (z * CHUNK_BOUNDS * CHUNK_BOUNDS) - (r * CHUNK_BOUNDS);
+pub fn index_to_qrz(index: usize, bounds: usize) -> IPos {
+    let z = index / (bounds * bounds);
+    let r = (index - (z * bounds * bounds)) / bounds;
+    let q = index - (z * bounds * bounds) - (r * bounds);
     IPos::new(q as i32, r as i32, z as i32)
 }
 
@@ -84,3 +325,141 @@ pub fn map_value(value: f64, from_min: f64, from_max: f64, to_min: f64, to_max:
 // References
 // 1. 3D -> 1D Array, 1D -> 3D Array
 // https://stackoverflow.com/questions/7367770/how-to-flatten-or-index-3d-array-in-1d-array
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::hex_grid::biomes::BiomeType;
+    use crate::game::procedural_generation::presets::{FlatGenerator, FlatLayer};
+
+    const DIMENSIONS: ChunkDimensions = ChunkDimensions {
+        radius: 2,
+        height: 4,
+    };
+
+    /// A stone floor, out of which every chunk grows one wide slab of a block type of its own.
+    /// The slabs reach well into the neighbouring chunks, so they overlap.
+    struct SlabGenerator;
+
+    impl WorldGenerator for SlabGenerator {
+        fn generate_chunk(&self, chunk_id: &ChunkId, dimensions: ChunkDimensions) -> Chunk {
+            let floor = [FlatLayer {
+                block_type: BlockType::Stone,
+                thickness: 1,
+            }];
+            FlatGenerator::new(&floor, BiomeType::Grassland).generate_chunk(chunk_id, dimensions)
+        }
+        fn place_features(&self, _chunk: &Chunk, chunk_id: &ChunkId) -> Vec<(IPos, Block)> {
+            let block_types = BlockType::all()
+                .into_iter()
+                .filter(|block_type| *block_type != BlockType::Air)
+                .collect::<Vec<BlockType>>();
+            let index = (chunk_id.q() * 3 + chunk_id.r() * 5).rem_euclid(block_types.len() as i32);
+            let block = Block {
+                block_type: block_types[index as usize],
+                ..Default::default()
+            };
+            chunk_id
+                .center_pos(&DIMENSIONS)
+                .delta(0, 0, 1)
+                .range(DIMENSIONS.radius * 2)
+                .into_iter()
+                .map(|pos| (pos, block))
+                .collect()
+        }
+    }
+
+    /// The same slabs as the SlabGenerator, along with a marker block two blocks above the
+    /// surface of every column. The markers are placed wherever the generated chunk's surface is,
+    /// so they move if the neighbours' slabs are already in the chunk when features are placed.
+    struct SurfaceMarkerGenerator;
+
+    impl WorldGenerator for SurfaceMarkerGenerator {
+        fn generate_chunk(&self, chunk_id: &ChunkId, dimensions: ChunkDimensions) -> Chunk {
+            SlabGenerator.generate_chunk(chunk_id, dimensions)
+        }
+        fn place_features(&self, chunk: &Chunk, chunk_id: &ChunkId) -> Vec<(IPos, Block)> {
+            let center = chunk_id.center_pos(&DIMENSIONS);
+            let marker = Block {
+                block_type: BlockType::Leaves,
+                ..Default::default()
+            };
+            let markers = DIMENSIONS.columns().into_iter().filter_map(|column| {
+                let surface = chunk.surface_height(&column).ok()??;
+                Some((column.as_ipos(surface + 2) + center, marker))
+            });
+            SlabGenerator
+                .place_features(chunk, chunk_id)
+                .into_iter()
+                .chain(markers)
+                .collect()
+        }
+    }
+
+    fn assert_generation_order_does_not_matter(generator: fn() -> Box<dyn WorldGenerator>) {
+        let ids = ChunkId::splat(0).range(1);
+        let mut forwards = Chunks::new(DIMENSIONS, generator());
+        let mut backwards = Chunks::new(DIMENSIONS, generator());
+        for id in ids.iter() {
+            forwards.generate_chunk(*id);
+        }
+        for id in ids.iter().rev() {
+            backwards.generate_chunk(*id);
+        }
+        for id in ids.iter() {
+            let center = id.center_pos(&DIMENSIONS);
+            for column in DIMENSIONS.columns() {
+                for z in 0..DIMENSIONS.height as i32 {
+                    let pos = column.as_ipos(z) + center;
+                    assert_eq!(
+                        forwards.block(&pos).map(|block| block.block_type),
+                        backwards.block(&pos).map(|block| block.block_type),
+                        "Block {:?}",
+                        pos
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn overlapping_features_do_not_depend_on_the_generation_order() {
+        assert_generation_order_does_not_matter(|| Box::new(SlabGenerator));
+    }
+
+    #[test]
+    fn features_are_placed_on_the_surface_of_the_generated_terrain() {
+        assert_generation_order_does_not_matter(|| Box::new(SurfaceMarkerGenerator));
+    }
+
+    #[test]
+    fn column_height_only_looks_at_the_chunks_stacked_in_that_column() {
+        let dimensions = DIMENSIONS;
+        let layers = [FlatLayer {
+            block_type: BlockType::Stone,
+            thickness: 6,
+        }];
+        let mut chunks = Chunks::new(
+            dimensions,
+            Box::new(FlatGenerator::new(&layers, BiomeType::Grassland)),
+        );
+        let bottom = ChunkId::new(0, 0, 0);
+        let top = ChunkId::new(0, 0, 1);
+        let neighbour = ChunkId::new(1, 0, 1);
+        for id in [bottom, top, neighbour] {
+            chunks.generate_chunk(id);
+        }
+        let column = ColumnId::new(1, 1);
+        assert_eq!(chunks.surface_height(&column), Some(5));
+
+        let allowed = [bottom, neighbour]
+            .into_iter()
+            .collect::<HashSet<ChunkId>>();
+        assert_eq!(chunks.cull_chunks(&allowed), vec![top]);
+        assert_eq!(chunks.surface_height(&column), Some(3));
+
+        let allowed = [neighbour].into_iter().collect::<HashSet<ChunkId>>();
+        chunks.cull_chunks(&allowed);
+        assert_eq!(chunks.surface_height(&column), None);
+    }
+}
@@ -4,3 +4,7 @@ pub mod block;
 pub mod chunk;
 pub mod chunk_loading;
 pub mod chunks;
+pub mod region;
+pub mod templates;
+pub mod visibility;
+pub mod world_error;
@@ -0,0 +1,49 @@
+use crate::game::hex_grid::axial::IPos;
+
+/// A box-shaped selection of blocks, bounded by minimum and maximum q, r and z coordinates.
+/// Because the q and r axes are at an angle to each other, it's shaped like a rhombus when seen
+/// from above.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Region {
+    min: IPos,
+    max: IPos,
+}
+
+impl Region {
+    /// Creates the smallest region that contains both corners.
+    #[must_use]
+    pub fn from_corners(a: &IPos, b: &IPos) -> Self {
+        Self {
+            min: IPos::new(a.q().min(b.q()), a.r().min(b.r()), a.z().min(b.z())),
+            max: IPos::new(a.q().max(b.q()), a.r().max(b.r()), a.z().max(b.z())),
+        }
+    }
+    #[must_use]
+    pub fn min(&self) -> IPos {
+        self.min
+    }
+    #[must_use]
+    pub fn max(&self) -> IPos {
+        self.max
+    }
+    #[must_use]
+    pub fn contains(&self, pos: &IPos) -> bool {
+        (self.min.q()..=self.max.q()).contains(&pos.q())
+            && (self.min.r()..=self.max.r()).contains(&pos.r())
+            && (self.min.z()..=self.max.z()).contains(&pos.z())
+    }
+    /// The number of blocks in this region.
+    #[must_use]
+    pub fn volume(&self) -> usize {
+        let size = self.max - self.min + IPos::splat(1);
+        size.q() as usize * size.r() as usize * size.z() as usize
+    }
+    /// Iterates over all positions in this region, column by column, from bottom to top.
+    pub fn positions(&self) -> impl Iterator<Item = IPos> {
+        let (min, max) = (self.min, self.max);
+        (min.q()..=max.q()).flat_map(move |q| {
+            (min.r()..=max.r())
+                .flat_map(move |r| (min.z()..=max.z()).map(move |z| IPos::new(q, r, z)))
+        })
+    }
+}
@@ -0,0 +1,376 @@
+use std::collections::HashMap;
+
+use bevy::asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use serde::{Deserialize, Serialize};
+
+use crate::game::hex_grid::axial::IPos;
+use crate::game::hex_grid::block::{Block, BlockShape, BlockType};
+use crate::game::hex_grid::chunks::Chunks;
+use crate::game::hex_grid::region::Region;
+use crate::game::hex_grid::world_error::WorldError;
+
+/// A reusable structure: a set of blocks at positions relative to the template's origin.
+/// Templates can be captured from the world, saved as RON and placed again elsewhere,
+/// both in-game and during world generation.
+#[derive(Debug, Deserialize, Serialize, TypeUuid, Clone)]
+#[serde(deny_unknown_fields)]
+#[uuid = "e49ee5f5-6a97-44b2-9247-a0edb2f922e5"]
+pub struct StructureTemplate {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub author: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// The blocks, relative to the origin. Positions that aren't listed are left alone on placement.
+    pub blocks: Vec<TemplateBlock>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Copy, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct TemplateBlock {
+    pub pos: IPos,
+    pub block_type: BlockType,
+    #[serde(default)]
+    pub shape: BlockShape,
+}
+
+impl TemplateBlock {
+    /// A full block of the given type.
+    #[must_use]
+    pub fn full(pos: IPos, block_type: BlockType) -> Self {
+        Self {
+            pos,
+            block_type,
+            shape: BlockShape::Full,
+        }
+    }
+}
+
+/// How to orient a template when it's placed.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct TemplatePlacement {
+    /// The number of 60 degree steps to rotate counter-clockwise, around the origin.
+    pub rotation: i32,
+    /// If true, the template is mirrored across the q-axis before it is rotated.
+    pub mirrored: bool,
+}
+
+impl TemplatePlacement {
+    /// All 12 distinct ways to place a template: 6 rotations, with and without mirroring.
+    #[must_use]
+    pub fn all() -> Vec<Self> {
+        [false, true]
+            .iter()
+            .flat_map(|mirrored| {
+                (0..6).map(move |rotation| Self {
+                    rotation,
+                    mirrored: *mirrored,
+                })
+            })
+            .collect()
+    }
+    /// Applies this placement to a position relative to the template's origin.
+    #[must_use]
+    pub fn apply(&self, relative_pos: &IPos) -> IPos {
+        let origin = IPos::default();
+        let pos = if self.mirrored {
+            relative_pos.reflect_q(&origin)
+        } else {
+            *relative_pos
+        };
+        pos.rotate_around(&origin, self.rotation)
+    }
+    /// Applies this placement to a block's shape, turning wedges along with the template.
+    #[must_use]
+    pub fn apply_to_shape(&self, shape: &BlockShape) -> BlockShape {
+        if let BlockShape::Wedge(edge) = shape {
+            let direction = self.apply(&IPos::direction(*edge));
+            let edge = (0..6)
+                .find(|i| IPos::direction(*i) == direction)
+                .expect("A placement always maps a direction onto another direction.");
+            BlockShape::Wedge(edge)
+        } else {
+            *shape
+        }
+    }
+}
+
+impl StructureTemplate {
+    /// Copies all blocks in the region into a new template, relative to the given origin.
+    /// Air is only included if `include_air` is true; with air included, placing the template
+    /// also clears out whatever was in the way.
+    /// Fails if part of the region isn't loaded.
+    pub fn capture(
+        chunks: &Chunks,
+        region: &Region,
+        origin: &IPos,
+        name: &str,
+        include_air: bool,
+    ) -> Result<Self, WorldError> {
+        let mut blocks = vec![];
+        for pos in region.positions() {
+            let block = chunks.try_block(&pos)?;
+            if include_air || block.block_type != BlockType::Air {
+                blocks.push(TemplateBlock {
+                    pos: pos - *origin,
+                    block_type: block.block_type,
+                    shape: block.shape,
+                });
+            }
+        }
+        Ok(Self {
+            name: name.to_string(),
+            description: String::new(),
+            author: String::new(),
+            tags: vec![],
+            blocks,
+        })
+    }
+
+    /// Returns the blocks of this template at absolute positions,
+    /// as they would end up when placed at the origin with the given placement.
+    #[must_use]
+    pub fn transformed(&self, origin: &IPos, placement: &TemplatePlacement) -> Vec<TemplateBlock> {
+        self.blocks
+            .iter()
+            .map(|block| TemplateBlock {
+                pos: placement.apply(&block.pos) + *origin,
+                block_type: block.block_type,
+                shape: placement.apply_to_shape(&block.shape),
+            })
+            .collect()
+    }
+
+    /// Places the template in the world. Blocks keep the biome of the block they replace.
+    /// Either the whole template is placed, or nothing is: if any part of it falls in a chunk
+    /// that isn't loaded, the error is returned and the world is left unchanged.
+    pub fn place(
+        &self,
+        chunks: &mut Chunks,
+        origin: &IPos,
+        placement: &TemplatePlacement,
+    ) -> Result<(), WorldError> {
+        let mut blocks = vec![];
+        for block in self.transformed(origin, placement) {
+            let biome_type = chunks.try_block(&block.pos)?.biome_type;
+            blocks.push((
+                block.pos,
+                Block {
+                    block_type: block.block_type,
+                    biome_type,
+                    shape: block.shape,
+                },
+            ));
+        }
+        chunks.set_blocks(blocks)
+    }
+
+    pub fn to_ron(&self) -> Result<String, ron::Error> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+    }
+    pub fn from_ron(ron: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::de::from_str(ron)
+    }
+}
+
+/// All structure templates that were loaded from mods, by the id they have in
+/// file_structure.meta.ron.
+#[derive(Resource, Default, Clone)]
+pub struct StructureLibrary {
+    templates: HashMap<String, StructureTemplate>,
+}
+
+impl StructureLibrary {
+    pub fn new(templates: HashMap<String, StructureTemplate>) -> Self {
+        Self { templates }
+    }
+    #[must_use]
+    pub fn get(&self, id: &str) -> Option<&StructureTemplate> {
+        self.templates.get(id)
+    }
+    pub fn insert(&mut self, id: &str, template: StructureTemplate) {
+        self.templates.insert(id.to_string(), template);
+    }
+}
+
+#[derive(Default)]
+pub struct StructureLoader;
+
+impl AssetLoader for StructureLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let custom_asset = ron::de::from_bytes::<StructureTemplate>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(custom_asset));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["structure.ron"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::hex_grid::axial::ChunkId;
+    use crate::game::hex_grid::biomes::BiomeType;
+    use crate::game::hex_grid::chunk::ChunkDimensions;
+    use crate::game::procedural_generation::presets::{FlatGenerator, FlatLayer};
+
+    const FLOOR: i32 = 2;
+
+    /// A lopsided little structure, so that every placement of it looks different.
+    fn template() -> StructureTemplate {
+        let mut blocks = vec![
+            TemplateBlock::full(IPos::new(0, 0, 0), BlockType::Stone),
+            TemplateBlock::full(IPos::new(1, 0, 0), BlockType::Stone),
+            TemplateBlock::full(IPos::new(2, -1, 0), BlockType::Wood),
+            TemplateBlock::full(IPos::new(0, 1, 1), BlockType::Leaves),
+        ];
+        blocks.extend((0..6).map(|edge| TemplateBlock {
+            pos: IPos::new(-1, edge as i32, 2),
+            block_type: BlockType::Cobblestone,
+            shape: BlockShape::Wedge(edge),
+        }));
+        StructureTemplate {
+            name: "Test".to_string(),
+            description: String::new(),
+            author: String::new(),
+            tags: vec![],
+            blocks,
+        }
+    }
+
+    fn with_blocks(template: &StructureTemplate, blocks: Vec<TemplateBlock>) -> StructureTemplate {
+        StructureTemplate {
+            blocks,
+            ..template.clone()
+        }
+    }
+
+    fn test_world() -> Chunks {
+        let dimensions = ChunkDimensions {
+            radius: 4,
+            height: 8,
+        };
+        let layers = [FlatLayer {
+            block_type: BlockType::Stone,
+            thickness: FLOOR as u32,
+        }];
+        let generator = FlatGenerator::new(&layers, BiomeType::Grassland);
+        let mut chunks = Chunks::new(dimensions, Box::new(generator));
+        for chunk_id in ChunkId::splat(0).range(2) {
+            chunks.generate_chunk(chunk_id);
+        }
+        chunks
+    }
+
+    #[test]
+    fn six_rotations_are_the_identity() {
+        let original = template();
+        let origin = IPos::default();
+        let step = TemplatePlacement {
+            rotation: 1,
+            mirrored: false,
+        };
+        // Wedges turn along with the template:
+        for (before, after) in original
+            .blocks
+            .iter()
+            .zip(original.transformed(&origin, &step))
+        {
+            if let BlockShape::Wedge(edge) = before.shape {
+                assert_eq!(after.shape, BlockShape::Wedge((edge + 1) % 6));
+            }
+        }
+        let rotated = (0..6).fold(original.clone(), |template, _| {
+            with_blocks(&template, template.transformed(&origin, &step))
+        });
+        assert_eq!(rotated.blocks, original.blocks);
+        let full_turn = TemplatePlacement {
+            rotation: 6,
+            mirrored: false,
+        };
+        assert_eq!(original.transformed(&origin, &full_turn), original.blocks);
+    }
+
+    #[test]
+    fn mirroring_twice_is_the_identity() {
+        let original = template();
+        let origin = IPos::default();
+        let mirror = TemplatePlacement {
+            rotation: 0,
+            mirrored: true,
+        };
+        let mirrored = with_blocks(&original, original.transformed(&origin, &mirror));
+        assert_ne!(mirrored.blocks, original.blocks);
+        assert_eq!(mirrored.transformed(&origin, &mirror), original.blocks);
+    }
+
+    #[test]
+    fn every_placement_is_different() {
+        let original = template();
+        let placements = TemplatePlacement::all()
+            .iter()
+            .map(|placement| original.transformed(&IPos::default(), placement))
+            .collect::<Vec<Vec<TemplateBlock>>>();
+        for (i, a) in placements.iter().enumerate() {
+            for b in placements.iter().skip(i + 1) {
+                assert_ne!(a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn a_captured_template_can_be_saved_and_placed_again() {
+        let mut chunks = test_world();
+        let origin = IPos::new(-3, 1, FLOOR);
+        for block in template().transformed(&origin, &TemplatePlacement::default()) {
+            let new_block = Block {
+                block_type: block.block_type,
+                biome_type: BiomeType::Grassland,
+                shape: block.shape,
+            };
+            chunks.set_block(&block.pos, new_block).unwrap();
+        }
+        let region = Region::from_corners(&origin.delta(-2, -1, 0), &origin.delta(3, 6, 3));
+        let captured = StructureTemplate::capture(&chunks, &region, &origin, "Copy", true).unwrap();
+        let ron = captured.to_ron().unwrap();
+        let loaded = StructureTemplate::from_ron(&ron).unwrap();
+        assert_eq!(loaded.name, "Copy");
+        assert_eq!(loaded.blocks, captured.blocks);
+
+        // Placed unturned into another world, the template reproduces the captured blocks:
+        let mut copy = test_world();
+        loaded
+            .place(&mut copy, &origin, &TemplatePlacement::default())
+            .unwrap();
+        for pos in region.positions() {
+            let original = chunks.block(&pos).unwrap();
+            let placed = copy.block(&pos).unwrap();
+            assert_eq!(placed.block_type, original.block_type, "Block {:?}", pos);
+            assert_eq!(placed.shape, original.shape, "Block {:?}", pos);
+        }
+        let mut turned = test_world();
+        let target = IPos::new(4, -2, FLOOR);
+        let placement = TemplatePlacement {
+            rotation: 2,
+            mirrored: true,
+        };
+        loaded.place(&mut turned, &target, &placement).unwrap();
+        for block in captured.transformed(&target, &placement) {
+            let placed = turned.block(&block.pos).unwrap();
+            assert_eq!(placed.block_type, block.block_type, "Block {:?}", block.pos);
+            assert_eq!(placed.shape, block.shape, "Block {:?}", block.pos);
+        }
+    }
+}
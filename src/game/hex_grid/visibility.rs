@@ -0,0 +1,265 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+use crate::game::hex_grid::axial::{ColumnId, IPos, HEIGHT, RADIUS, SQRT_THREE};
+use crate::game::hex_grid::chunks::Chunks;
+
+/// Shadows are widened by this angle (in radians), so that light can't leak through the seam
+/// between two neighbouring opaque columns.
+const SHADOW_EPSILON: f32 = 1e-4;
+
+/// The result of a line of sight check.
+#[derive(Debug, Clone, Default)]
+pub struct LineOfSight {
+    /// All cells that the line passes through, in order, up to and including the blocking cell.
+    pub visible: Vec<IPos>,
+    /// The first cell that blocks the line of sight, if any.
+    pub blocked_by: Option<IPos>,
+}
+
+impl LineOfSight {
+    pub fn is_clear(&self) -> bool {
+        self.blocked_by.is_none()
+    }
+}
+
+/// Returns all columns that can be seen from the center, up to the given radius.
+/// Opaque columns cast a shadow over every column behind them.
+/// Opaque columns are visible themselves, as long as they're not in another column's shadow.
+///
+/// This works ring by ring, outward from the center. A column is visible if the ray from the
+/// center to the middle of the column doesn't pass through any of the shadows cast so far.
+pub fn field_of_view(
+    center: &ColumnId,
+    radius: u32,
+    is_opaque: impl Fn(&ColumnId) -> bool,
+) -> Vec<ColumnId> {
+    let origin = center.as_ipos(0).as_xyz().truncate();
+    let mut visible = vec![*center];
+    let mut shadows: Vec<(f32, f32)> = vec![];
+    for ring_index in 1..=radius {
+        let mut new_shadows = vec![];
+        for column in ColumnId::ring(ring_index) {
+            let column = &column + center;
+            let to_column = column.as_ipos(0).as_xyz().truncate() - origin;
+            let angle = to_column.y.atan2(to_column.x).rem_euclid(TAU);
+            if shadows.iter().any(|shadow| in_shadow(angle, shadow)) {
+                continue;
+            }
+            visible.push(column);
+            if is_opaque(&column) {
+                // The shadow spans the inner circle of the hexagon, as seen from the center.
+                let inner_radius = RADIUS * SQRT_THREE / 2.;
+                let half_width = (inner_radius / to_column.length()).min(1.).asin();
+                new_shadows.push((
+                    angle - half_width - SHADOW_EPSILON,
+                    angle + half_width + SHADOW_EPSILON,
+                ));
+            }
+        }
+        // Columns on the same ring can't shadow each other.
+        shadows.append(&mut new_shadows);
+    }
+    visible
+}
+
+/// Returns all columns that can be seen from the given position, looking horizontally.
+/// A column is opaque if the block at the same height as the viewer isn't transparent.
+/// Columns in chunks that aren't loaded are treated as transparent.
+pub fn field_of_view_in_chunks(chunks: &Chunks, viewer: &IPos, radius: u32) -> Vec<ColumnId> {
+    field_of_view(&viewer.as_column_id(), radius, |column| {
+        chunks
+            .block(&column.as_ipos(viewer.z()))
+            .map(|block| !block.is_transparent())
+            .unwrap_or(false)
+    })
+}
+
+/// Traces a line between two points in world space, through all hex prism cells in between.
+/// The line is blocked by the first cell that isn't transparent.
+/// The cell containing the starting point never blocks the line.
+///
+/// The line walks from cell to cell, each time crossing whichever face of the current cell it
+/// leaves through first: one of the six sides, or the top or bottom. So it visits every cell it
+/// touches, even where it only clips the corner of one.
+pub fn line_of_sight(chunks: &Chunks, from: &Vec3, to: &Vec3) -> LineOfSight {
+    let mut result = LineOfSight::default();
+    let direction = *to - *from;
+    let start = IPos::block_containing(from);
+    let end = IPos::block_containing(to);
+    // Every step crosses into a neighbouring cell and gets closer to the end, so this is plenty.
+    // It only guards against rounding errors near the end point.
+    let max_steps = 2 * IPos::column_distance(&start, &end) + (end.z() - start.z()).abs() + 2;
+    let mut cell = start;
+    for _ in 0..=max_steps {
+        result.visible.push(cell);
+        let opaque = chunks
+            .block(&cell)
+            .map(|block| !block.is_transparent())
+            .unwrap_or(false);
+        if opaque && cell != start {
+            result.blocked_by = Some(cell);
+            break;
+        }
+        if cell == end {
+            break;
+        }
+        let (exit, next) = next_cell(&cell, from, &direction);
+        if exit > 1. {
+            break;
+        }
+        cell = next;
+    }
+    result
+}
+
+/// Returns where the line from the origin along the direction leaves the cell, as a fraction of
+/// the direction, along with the cell it enters.
+fn next_cell(cell: &IPos, origin: &Vec3, direction: &Vec3) -> (f32, IPos) {
+    let center = cell.as_xyz();
+    let mut exit = (f32::INFINITY, *cell);
+    for i in 0..6 {
+        // The side facing a neighbour lies halfway between the two centers.
+        let neighbour = cell.neighbour(i);
+        let to_neighbour = (neighbour.as_xyz() - center).truncate();
+        let speed = direction.truncate().dot(to_neighbour);
+        if speed > 0. {
+            let side = center.truncate() + to_neighbour / 2.;
+            let t = (side - origin.truncate()).dot(to_neighbour) / speed;
+            if t < exit.0 {
+                exit = (t, neighbour);
+            }
+        }
+    }
+    if direction.z != 0. {
+        let (face, dz) = if direction.z > 0. {
+            (center.z + HEIGHT, 1)
+        } else {
+            (center.z, -1)
+        };
+        let t = (face - origin.z) / direction.z;
+        if t < exit.0 {
+            exit = (t, cell.delta(0, 0, dz));
+        }
+    }
+    exit
+}
+
+/// Checks if the angle (in the range [0, TAU)) lies within the shadow.
+/// Shadows may extend past 0 or TAU, so the angle is checked a full turn in either direction too.
+fn in_shadow(angle: f32, (start, end): &(f32, f32)) -> bool {
+    [angle - TAU, angle, angle + TAU]
+        .iter()
+        .any(|angle| angle >= start && angle <= end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::hex_grid::axial::ChunkId;
+    use crate::game::hex_grid::biomes::BiomeType;
+    use crate::game::hex_grid::block::{Block, BlockType};
+    use crate::game::hex_grid::chunk::ChunkDimensions;
+    use crate::game::procedural_generation::presets::{FlatGenerator, FlatLayer};
+
+    const FLOOR: i32 = 3;
+
+    fn test_world() -> Chunks {
+        let dimensions = ChunkDimensions {
+            radius: 3,
+            height: 8,
+        };
+        let layers = [FlatLayer {
+            block_type: BlockType::Stone,
+            thickness: FLOOR as u32,
+        }];
+        let generator = FlatGenerator::new(&layers, BiomeType::Grassland);
+        let mut chunks = Chunks::new(dimensions, Box::new(generator));
+        for chunk_id in ChunkId::splat(0).range(1) {
+            chunks.generate_chunk(chunk_id);
+        }
+        chunks
+    }
+
+    fn set_stone(chunks: &mut Chunks, pos: IPos) {
+        let block = Block {
+            block_type: BlockType::Stone,
+            ..Default::default()
+        };
+        chunks.set_block(&pos, block).unwrap();
+    }
+
+    /// The point in the middle of the block.
+    fn middle(pos: &IPos) -> Vec3 {
+        pos.as_xyz() + Vec3::Z * HEIGHT / 2.
+    }
+
+    #[test]
+    fn opaque_columns_cast_a_shadow_behind_them() {
+        let center = ColumnId::new(0, 0);
+        let pillar = ColumnId::new(1, 0);
+        let visible = field_of_view(&center, 4, |column| *column == pillar);
+        assert!(visible.contains(&pillar));
+        for behind in [
+            ColumnId::new(2, 0),
+            ColumnId::new(3, 0),
+            ColumnId::new(4, 0),
+        ] {
+            assert!(
+                !visible.contains(&behind),
+                "{:?} should be in the shadow",
+                behind
+            );
+        }
+        for column in ColumnId::ring(1) {
+            assert!(visible.contains(&column));
+        }
+        assert!(visible.contains(&ColumnId::new(-4, 0)));
+    }
+
+    #[test]
+    fn a_wall_blocks_the_line_of_sight_at_its_first_block() {
+        let mut chunks = test_world();
+        let from = IPos::new(0, 0, FLOOR);
+        let to = IPos::new(5, 0, FLOOR + 1);
+        let clear = line_of_sight(&chunks, &middle(&from), &middle(&to));
+        assert!(clear.is_clear());
+        assert_eq!(clear.visible.first(), Some(&from));
+        assert_eq!(clear.visible.last(), Some(&to));
+        for cells in clear.visible.windows(2) {
+            assert_eq!(IPos::distance_3d(&cells[0], &cells[1]), 1);
+        }
+        for q in 2..=3 {
+            for z in FLOOR..FLOOR + 3 {
+                set_stone(&mut chunks, IPos::new(q, 0, z));
+            }
+        }
+        let blocked = line_of_sight(&chunks, &middle(&from), &middle(&to));
+        let first_wall = blocked.visible.iter().find(|cell| cell.q() == 2).copied();
+        assert!(first_wall.is_some());
+        assert_eq!(blocked.blocked_by, first_wall);
+        assert_eq!(blocked.visible.last(), first_wall.as_ref());
+    }
+
+    #[test]
+    fn a_line_that_clips_the_corner_of_a_block_is_blocked_by_it() {
+        let mut chunks = test_world();
+        let corner = IPos::new(0, 0, FLOOR);
+        set_stone(&mut chunks, corner);
+        // Passes just inside the corner of the block that lies on the positive x-axis,
+        // so only a tiny bit of the line lies within the block.
+        let x = RADIUS - 0.01;
+        let z = FLOOR as f32 + 0.5;
+        let from = Vec3::new(x, -2.1, z);
+        let to = Vec3::new(x, 1.9, z);
+        assert_ne!(IPos::block_containing(&from), corner);
+        assert_ne!(IPos::block_containing(&to), corner);
+        let blocked = line_of_sight(&chunks, &from, &to);
+        assert_eq!(blocked.blocked_by, Some(corner));
+        // Just outside the corner, the line passes:
+        let x = RADIUS + 0.01;
+        let clear = line_of_sight(&chunks, &Vec3::new(x, -2.1, z), &Vec3::new(x, 1.9, z));
+        assert!(clear.is_clear());
+    }
+}
@@ -0,0 +1,47 @@
+use std::fmt::{Display, Formatter};
+
+use crate::game::hex_grid::axial::{ChunkId, ColumnId, IPos};
+
+/// The ways in which accessing blocks in the world can fail.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WorldError {
+    /// The chunk with this id is not loaded.
+    UnloadedChunk(ChunkId),
+    /// The position (relative to the chunk's center) lies outside of the chunk.
+    OutOfBounds(IPos),
+    /// The column (relative to the chunk's center) lies within the square that the chunk is stored
+    /// in, but outside of the hexagonal chunk itself.
+    InvalidColumn(ColumnId),
+}
+
+impl Display for WorldError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WorldError::UnloadedChunk(id) => write!(
+                f,
+                "Chunk (q={},r={},z={}) is not loaded.",
+                id.q(),
+                id.r(),
+                id.z()
+            ),
+            WorldError::OutOfBounds(pos) => write!(
+                f,
+                "Chunk lookup index out of bounds. Tried to access (q={},r={},z={}). Expected \
+                axial coordinates relative to chunk; they should not exceed chunk \
+                length/width/height.",
+                pos.q(),
+                pos.r(),
+                pos.z()
+            ),
+            WorldError::InvalidColumn(column) => write!(
+                f,
+                "Column (q={},r={}) is not part of the chunk. Expected axial coordinates \
+                relative to chunk; they should not exceed the chunk radius.",
+                column.q(),
+                column.r()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for WorldError {}
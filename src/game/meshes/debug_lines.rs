@@ -8,7 +8,6 @@ use bevy::render::render_resource::{
 use serde::{Deserialize, Serialize};
 
 use crate::io::config::DebugConfig;
-use crate::{default, Color, MaterialMeshBundle, Mesh, Transform, Vec3};
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub enum OriginLinesDisplay {
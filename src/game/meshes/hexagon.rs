@@ -3,8 +3,9 @@ use bevy::render::mesh::{Indices, PrimitiveTopology};
 
 use crate::game::hex_grid::axial;
 use crate::game::hex_grid::axial::{ChunkId, IPos, FRAC_TAU_6};
-use crate::game::hex_grid::chunk::{Chunk, CHUNK_HEIGHT};
+use crate::game::hex_grid::chunk::ChunkDimensions;
 use crate::game::hex_grid::chunks::Chunks;
+use crate::game::hex_grid::world_error::WorldError;
 
 /// Only for testing. Maybe delete this at some point.
 pub fn spawn_test_grid(
@@ -12,6 +13,7 @@ pub fn spawn_test_grid(
     mut meshes: ResMut<Assets<Mesh>>,
     mut std_mats: ResMut<Assets<StandardMaterial>>,
 ) {
+    let dimensions = ChunkDimensions::default();
     for chunk_r in 0..8 {
         for chunk_q in 0..8 {
             let chunk_pos = ChunkId::new(chunk_q, chunk_r, 0);
@@ -19,21 +21,23 @@ pub fn spawn_test_grid(
             commands.spawn(MaterialMeshBundle {
                 mesh: meshes.add(create_single_block_mesh()),
                 transform: Transform::from_translation(
-                    chunk_pos.center_pos().as_xyz()
+                    chunk_pos.center_pos(&dimensions).as_xyz()
                         + Vec3::new(0., 0., 1. + (chunk_q + chunk_r) as f32 * 0.5),
                 ),
                 material: std_mats.add(Color::WHITE.into()),
                 ..default()
             });
 
-            for block_pos in Chunk::chunk_columns()
+            for block_pos in dimensions
+                .columns()
                 .iter()
-                .map(|relative_pos| relative_pos + chunk_pos.center_pos())
+                .map(|relative_pos| relative_pos + chunk_pos.center_pos(&dimensions))
             {
                 let mut xyz = block_pos.as_xyz();
                 xyz.z += if chunk_q % 2 == 0 { 0. } else { 0.1 };
                 xyz.z += (chunk_q + chunk_r) as f32 * 0.5;
-                let what_chunk_it_thinks_it_belongs_to = ChunkId::from_block_pos(&block_pos);
+                let what_chunk_it_thinks_it_belongs_to =
+                    ChunkId::from_block_pos(&block_pos, &dimensions);
                 commands.spawn(MaterialMeshBundle {
                     mesh: meshes.add(create_single_block_mesh()),
                     transform: Transform::from_translation(xyz),
@@ -52,40 +56,48 @@ pub fn spawn_test_grid(
     }
 }
 
-pub fn create_chunk_mesh(chunks: &Chunks, chunk_id: &ChunkId) -> Mesh {
-    let chunk = chunks.get_chunk(chunk_id);
-    let vertical_neighbours = [
-        IPos::new(0, 0, -1), // <== Bottom neighbour
-        IPos::new(0, 0, 1),  // <== Top neighbour
-    ];
-    let vertical_normals = [
-        Vec3::new(0., 0., -1.), // <== Bottom normal
-        Vec3::new(0., 0., 1.),  // <== Top normal
-    ];
+pub fn create_chunk_mesh(chunks: &Chunks, chunk_id: &ChunkId) -> Result<Mesh, WorldError> {
+    let chunk = chunks.try_get_chunk(chunk_id)?;
     let mut vertices = vec![];
     let mut indices = vec![];
-    for pos in Chunk::chunk_columns().iter() {
-        // First add all the vertical faces:
-        (0..6).for_each(|i: i8| {
-            let angle_a = FRAC_TAU_6 * i as f32;
-            let angle_b = FRAC_TAU_6 * (i + 1).rem_euclid(6) as f32;
-            let normal_a = Vec3::new(angle_a.cos(), angle_a.sin(), 0.);
-            let normal_b = Vec3::new(angle_b.cos(), angle_b.sin(), 0.);
-            let normal_face = ((normal_a + normal_b) / 2.).normalize();
-            for z in 0..CHUNK_HEIGHT {
-                let pos_relative = pos.as_ipos(z as i32);
-                if !chunk.block(&pos_relative).is_solid() {
-                    // This block isn't solid, so we obviously shouldn't include it in the mesh.
-                    continue;
+    let center_pos = chunk_id.center_pos(chunks.dimensions());
+    for pos in chunk.columns().iter() {
+        for z in 0..chunk.dimensions().height {
+            let pos_relative = pos.as_ipos(z as i32);
+            let block = chunk.block(&pos_relative);
+            if !block.is_solid() {
+                // This block isn't solid, so we obviously shouldn't include it in the mesh.
+                continue;
+            }
+            let pos_absolute = pos_relative + center_pos;
+            let shape = block.shape;
+            let radius = shape.radius();
+            let heights = shape.corner_heights();
+            // First add all the vertical faces:
+            (0..6).for_each(|i: u32| {
+                let j = (i + 1).rem_euclid(6);
+                if heights[i as usize] <= 0. && heights[j as usize] <= 0. {
+                    // This side of a wedge has no height at all.
+                    return;
                 }
-                let pos_absolute = pos_relative + chunk_id.center_pos();
-                let neighbour = pos_absolute.neighbour(i as u32);
-                if chunks.is_solid(&neighbour) {
-                    // The neighbour is solid, so there is no point in rendering this face.
-                    continue;
+                let neighbour = chunks.block(&pos_absolute.neighbour(i));
+                if neighbour.map_or(false, |neighbour| {
+                    neighbour.is_solid()
+                        && neighbour.shape.side_coverage((i + 3).rem_euclid(6))
+                            >= shape.side_extent(i)
+                }) {
+                    // The neighbour covers this face, so there is no point in rendering it.
+                    return;
                 }
-                let (pos_a_bottom, pos_a_top) = calc_pos(angle_a, &pos_relative);
-                let (pos_b_bottom, pos_b_top) = calc_pos(angle_b, &pos_relative);
+                let angle_a = FRAC_TAU_6 * i as f32;
+                let angle_b = FRAC_TAU_6 * j as f32;
+                let normal_a = Vec3::new(angle_a.cos(), angle_a.sin(), 0.);
+                let normal_b = Vec3::new(angle_b.cos(), angle_b.sin(), 0.);
+                let normal_face = ((normal_a + normal_b) / 2.).normalize();
+                let (pos_a_bottom, pos_a_top) =
+                    calc_pos(angle_a, radius, heights[i as usize], &pos_relative);
+                let (pos_b_bottom, pos_b_top) =
+                    calc_pos(angle_b, radius, heights[j as usize], &pos_relative);
                 vertices.push((pos_a_bottom, normal_face, [1., 1.]));
                 vertices.push((pos_b_bottom, normal_face, [1., 1.]));
                 vertices.push((pos_a_top, normal_face, [1., 1.]));
@@ -93,46 +105,48 @@ pub fn create_chunk_mesh(chunks: &Chunks, chunk_id: &ChunkId) -> Mesh {
                 let len = vertices.len() as u32;
                 indices.append(&mut vec![len - 4, len - 3, len - 2]);
                 indices.append(&mut vec![len - 1, len - 2, len - 3]);
+            });
+            // Now add the top and bottom faces.
+            // The top can only be hidden if it is flat and at the full height of the block:
+            let above = chunks.block(&pos_absolute.delta(0, 0, 1));
+            let top_hidden = heights.iter().all(|height| *height >= axial::HEIGHT)
+                && above.map_or(false, |above| {
+                    above.is_solid() && above.shape.covers_bottom()
+                });
+            let below = chunks.block(&pos_absolute.delta(0, 0, -1));
+            let bottom_hidden =
+                below.map_or(false, |below| below.is_solid() && below.shape.covers_top());
+            let xyz = pos_relative.as_xyz();
+            if !top_hidden {
+                let corners = (0..6)
+                    .map(|i| calc_pos(FRAC_TAU_6 * i as f32, radius, heights[i], &pos_relative).1)
+                    .collect::<Vec<Vec3>>();
+                // Tops are always flat, but not necessarily level:
+                let normal = (corners[2] - corners[0])
+                    .cross(corners[4] - corners[0])
+                    .normalize();
+                let center_height = shape.surface_height(Vec2::ZERO).unwrap_or(0.);
+                add_hexagon(
+                    &mut vertices,
+                    &mut indices,
+                    xyz + Vec3::new(0., 0., center_height),
+                    &corners,
+                    normal,
+                );
             }
-        });
-        // Now add the top and bottom faces:
-        for z in 0..CHUNK_HEIGHT {
-            let pos_relative = pos.as_ipos(z as i32);
-            if !chunk.block(&pos_relative).is_solid() {
-                // This block isn't solid, so we obviously shouldn't include it in the mesh.
-                continue;
+            // Do not draw the bottom of the world, it's never seen:
+            if !bottom_hidden && pos_absolute.z() != 0 {
+                let corners = (0..6)
+                    .map(|i| calc_pos(FRAC_TAU_6 * i as f32, radius, 0., &pos_relative).0)
+                    .collect::<Vec<Vec3>>();
+                add_hexagon(
+                    &mut vertices,
+                    &mut indices,
+                    xyz,
+                    &corners,
+                    Vec3::new(0., 0., -1.),
+                );
             }
-            let pos_absolute = pos_relative + chunk_id.center_pos();
-            (0..2).for_each(|j: i8| {
-                if j == 0 && pos_absolute.z() == 0 {
-                    // Do not draw the bottom of the world, it's never seen.
-                    return;
-                }
-                // j==0 for bottom face, j==1 for top face.
-                // Check if the neighbour is solid. If so, we don't have to render this face:
-                let neighbour = pos_absolute + vertical_neighbours[j as usize];
-                if !chunks.is_solid(&neighbour) {
-                    let xyz = pos_relative.delta(0, 0, j as i32).as_xyz();
-                    let len = vertices.len() as u32;
-                    // Center vertex:
-                    vertices.push((xyz, vertical_normals[j as usize], [1., 1.]));
-                    // Corner vertices:
-                    (0..6).for_each(|i: i8| {
-                        let angle = FRAC_TAU_6 * i as f32;
-                        let pos_corner = Vec3::new(
-                            angle.cos() * axial::RADIUS + xyz.x,
-                            angle.sin() * axial::RADIUS + xyz.y,
-                            xyz.z,
-                        );
-                        vertices.push((pos_corner, vertical_normals[j as usize], [1., 1.]));
-                        indices.append(&mut vec![
-                            len,
-                            len + 1 + (i as u32 + 1 - j as u32).rem_euclid(6),
-                            len + 1 + (i as u32 + j as u32).rem_euclid(6),
-                        ]);
-                    });
-                }
-            });
         }
     }
     debug!(
@@ -152,7 +166,7 @@ pub fn create_chunk_mesh(chunks: &Chunks, chunk_id: &ChunkId) -> Mesh {
     mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
     mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
     mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
-    mesh
+    Ok(mesh)
 }
 
 pub fn create_single_block_mesh() -> Mesh {
@@ -168,8 +182,10 @@ pub fn create_single_block_mesh() -> Mesh {
         let normal_a = Vec3::new(angle_a.cos(), angle_a.sin(), 0.);
         let normal_b = Vec3::new(angle_b.cos(), angle_b.sin(), 0.);
         let normal_face = ((normal_a + normal_b) / 2.).normalize();
-        let (pos_a_bottom, pos_a_top) = calc_pos(angle_a, &IPos::default());
-        let (pos_b_bottom, pos_b_top) = calc_pos(angle_b, &IPos::default());
+        let (pos_a_bottom, pos_a_top) =
+            calc_pos(angle_a, axial::RADIUS, axial::HEIGHT, &IPos::default());
+        let (pos_b_bottom, pos_b_top) =
+            calc_pos(angle_b, axial::RADIUS, axial::HEIGHT, &IPos::default());
         // Add vertices. In order to get sharp edges, add each vertex thrice: once per face.
         vertices.push((pos_a_bottom, normal_face, [1., 1.]));
         vertices.push((pos_b_bottom, normal_face, [1., 1.]));
@@ -204,17 +220,42 @@ fn index(i: i8, local_index: i8) -> u32 {
     2 + i as u32 * 8 + local_index as u32
 }
 
-fn calc_pos(angle: f32, pos: &IPos) -> (Vec3, Vec3) {
+/// Returns the bottom and top of the corner at the given angle, radius and height.
+fn calc_pos(angle: f32, radius: f32, height: f32, pos: &IPos) -> (Vec3, Vec3) {
     let xyz = pos.as_xyz();
     let pos_bottom = Vec3::new(
-        angle.cos() * axial::RADIUS + xyz.x,
-        angle.sin() * axial::RADIUS + xyz.y,
+        angle.cos() * radius + xyz.x,
+        angle.sin() * radius + xyz.y,
         xyz.z,
     );
     let pos_top = Vec3::new(
-        angle.cos() * axial::RADIUS + xyz.x,
-        angle.sin() * axial::RADIUS + xyz.y,
-        axial::HEIGHT + xyz.z,
+        angle.cos() * radius + xyz.x,
+        angle.sin() * radius + xyz.y,
+        height + xyz.z,
     );
     (pos_bottom, pos_top)
 }
+
+/// Adds a hexagon as a fan of triangles around its center. The triangles are wound so that they
+/// face the direction of the normal, which must point either up or down.
+fn add_hexagon(
+    vertices: &mut Vec<(Vec3, Vec3, [f32; 2])>,
+    indices: &mut Vec<u32>,
+    center: Vec3,
+    corners: &[Vec3],
+    normal: Vec3,
+) {
+    let len = vertices.len() as u32;
+    let facing_up = normal.z > 0.;
+    vertices.push((center, normal, [1., 1.]));
+    for (i, corner) in corners.iter().enumerate() {
+        vertices.push((*corner, normal, [1., 1.]));
+        let a = len + 1 + i as u32;
+        let b = len + 1 + (i as u32 + 1).rem_euclid(6);
+        if facing_up {
+            indices.append(&mut vec![len, a, b]);
+        } else {
+            indices.append(&mut vec![len, b, a]);
+        }
+    }
+}
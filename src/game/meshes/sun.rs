@@ -62,6 +62,13 @@ pub struct DayNight {
     pub blueness: Spline<f32, f32>,
 }
 
+impl DayNight {
+    /// Returns how far along the day is, as a value that is 0 at midnight and 1 at noon.
+    pub fn daylight(&self) -> f32 {
+        (1. - (self.timer.percent() * std::f32::consts::TAU).cos()) * 0.5
+    }
+}
+
 pub fn process_day_night_input(input: InputHandler, mut day_night: ResMut<DayNight>) {
     if input.is_active(DayNightInput::PauseTime) {
         day_night.paused ^= true;
@@ -3,4 +3,6 @@ pub mod camera;
 pub mod hex_grid;
 pub mod meshes;
 pub mod movement;
+pub mod pathfinding;
 pub mod procedural_generation;
+pub mod world_edit;
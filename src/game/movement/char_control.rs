@@ -1,20 +1,37 @@
 use bevy::input::mouse::MouseMotion;
+use bevy::math::Vec3Swizzles;
 use bevy::prelude::*;
 
 use crate::game::actors::structs::Player;
 use crate::game::camera::first_person::PlayerCamera;
-use crate::game::movement::structs::{MoveInput, MoveParams, MoveState};
+use crate::game::hex_grid::axial::IPos;
+use crate::game::hex_grid::chunks::Chunks;
+use crate::game::movement::structs::{LandedEvent, MoveInput, MoveParams, MoveState};
 use crate::io::input::{InputHandler, MovementInput};
 
+/// Acceleration due to gravity, in meters per second squared.
+pub const GRAVITY: f32 = 20.;
+/// While grounded, entities stick to floors up to this far (in meters) below their feet.
+const SNAP_DOWN_DISTANCE: f32 = 0.25;
+
 pub fn player_movement_system(
-    mut q: Query<(&mut MoveState, &mut MoveParams, &mut Transform), With<Player>>,
+    mut q: Query<(Entity, &mut MoveState, &mut MoveParams, &mut Transform), With<Player>>,
     cam_q: Query<&Transform, (With<Camera>, With<PlayerCamera>, Without<Player>)>,
     input: InputHandler,
+    chunks: Res<Chunks>,
+    mut landed_events: EventWriter<LandedEvent>,
     mut mouse: EventReader<MouseMotion>,
     time: Res<Time>,
 ) {
-    if let Ok((mut move_state, move_params, mut tform)) = q.get_single_mut() {
+    if let Ok((entity, mut move_state, mut move_params, mut tform)) = q.get_single_mut() {
         if let Ok(cam_tform) = cam_q.get_single() {
+            if input.is_active(MovementInput::ToggleFlying) {
+                move_params.flying ^= true;
+                info!(
+                    "Toggled flying {}!",
+                    if move_params.flying { "on" } else { "off" }
+                );
+            }
             let move_input = read_movement_input(&input);
             let dt = time.delta_seconds();
             let mut mouse_mov = Vec2::ZERO;
@@ -34,18 +51,93 @@ pub fn player_movement_system(
                     dt,
                 )
             } else {
-                walking_movement(&move_input, &move_params, &move_state, tform.rotation, dt)
+                let mut vel =
+                    walking_movement(&move_input, &move_params, &move_state, tform.rotation, dt);
+                vel.z = falling_speed(&move_params, &move_state, dt);
+                vel
             };
 
             let mut mov = tform.translation;
             // TODO: Insert collision detection and velocity deflection here.
             mov += move_state.velocity * time.delta_seconds();
             tform.translation = mov;
+            if !move_params.flying {
+                if let Some(event) = resolve_grounding(entity, &chunks, &mut move_state, &mut tform)
+                {
+                    landed_events.send(event);
+                }
+            }
         }
     }
 }
 
-fn walking_movement(
+/// Returns the new vertical velocity after applying gravity, capped at the maximum fall speed.
+pub fn falling_speed(move_params: &MoveParams, move_state: &MoveState, dt: f32) -> f32 {
+    if move_state.grounded {
+        0.
+    } else {
+        (move_state.velocity.z - GRAVITY * dt).max(-move_params.max_fall_speed)
+    }
+}
+
+/// Checks whether the entity is standing on a solid block and snaps it onto the block if so.
+/// Returns a LandedEvent if the entity just touched down after falling.
+///
+/// Blocks that aren't full are stood on at the height of their surface right under the entity's
+/// feet, so walking up a wedge lifts the entity up with the slope.
+pub fn resolve_grounding(
+    entity: Entity,
+    chunks: &Chunks,
+    move_state: &mut MoveState,
+    tform: &mut Transform,
+) -> Option<LandedEvent> {
+    let feet = tform.translation;
+    let was_grounded = move_state.grounded;
+    // While grounded, follow surfaces that slope down instead of falling onto them:
+    let tolerance = if was_grounded {
+        SNAP_DOWN_DISTANCE
+    } else {
+        0.01
+    };
+    let floor = [
+        IPos::block_containing(&feet),
+        IPos::block_containing(&(feet - Vec3::new(0., 0., tolerance))),
+    ]
+    .iter()
+    .filter_map(|pos| floor_height(chunks, pos, feet.xy()))
+    .find(|height| *height >= feet.z - tolerance);
+    move_state.grounded = move_state.velocity.z <= 0. && floor.is_some();
+    let floor = match floor {
+        Some(floor) if move_state.grounded => floor,
+        _ => return None,
+    };
+    // Stand on top of the block:
+    tform.translation.z = floor;
+    let impact_speed = -move_state.velocity.z;
+    move_state.velocity.z = 0.;
+    if was_grounded {
+        None
+    } else {
+        Some(LandedEvent {
+            entity,
+            impact_speed,
+        })
+    }
+}
+
+/// Returns the height of the surface of the block at the given position, directly under the given
+/// point in the xy-plane. Returns None if the block isn't solid, or its shape doesn't cover
+/// the point.
+fn floor_height(chunks: &Chunks, pos: &IPos, xy: Vec2) -> Option<f32> {
+    let block = chunks.block(pos).filter(|block| block.is_solid())?;
+    let xyz = pos.as_xyz();
+    block
+        .shape
+        .surface_height(xy - xyz.xy())
+        .map(|height| xyz.z + height)
+}
+
+pub fn walking_movement(
     input: &MoveInput,
     move_params: &MoveParams,
     move_state: &MoveState,
@@ -1,11 +1,13 @@
 use bevy::math::{Vec2, Vec3};
-use bevy::prelude::Component;
+use bevy::prelude::{Component, Entity};
 
 use crate::game::movement::direction::Direction1D;
 
 #[derive(Component, Default)]
 pub struct MoveState {
     pub velocity: Vec3,
+    /// Whether the entity is standing on solid ground. Always false while flying.
+    pub grounded: bool,
 }
 
 #[derive(Component, Default)]
@@ -25,3 +27,10 @@ pub struct MoveInput {
     pub up_down: Direction1D,
     pub sprint: bool,
 }
+
+/// Sent when a walking entity touches down on solid ground after falling.
+pub struct LandedEvent {
+    pub entity: Entity,
+    /// The downward speed at the moment of impact, in meters per second.
+    pub impact_speed: f32,
+}
@@ -0,0 +1,278 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use bevy::utils::{HashMap, HashSet};
+
+use crate::game::hex_grid::axial::{ChunkId, IPos};
+use crate::game::hex_grid::chunks::Chunks;
+
+/// Cost of moving one column sideways.
+const STEP_COST: u32 = 10;
+/// Additional cost per block of height difference when stepping up or dropping down.
+const CLIMB_COST: u32 = 4;
+
+/// Describes how an actor can move through the world.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct NavParams {
+    /// The number of air blocks the actor needs to stand in.
+    pub headroom: u32,
+    /// The number of blocks the actor can climb up in a single step.
+    pub max_step_up: u32,
+    /// The number of blocks the actor can drop down in a single step.
+    pub max_drop_down: u32,
+    /// The search gives up after visiting this many positions.
+    pub max_visited: usize,
+}
+
+impl Default for NavParams {
+    fn default() -> Self {
+        Self {
+            headroom: 2,
+            max_step_up: 1,
+            max_drop_down: 3,
+            max_visited: 4096,
+        }
+    }
+}
+
+/// The result of a path search.
+#[derive(Debug, Clone, Default)]
+pub struct Path {
+    /// The positions to walk through, starting with the start position and ending with the goal.
+    /// Empty if there is no path.
+    pub steps: Vec<IPos>,
+    /// All chunks that the search looked at.
+    /// If any block in these chunks changes, the path may no longer be valid or optimal.
+    pub chunks: HashSet<ChunkId>,
+}
+
+impl Path {
+    pub fn is_found(&self) -> bool {
+        !self.steps.is_empty()
+    }
+}
+
+/// An actor can stand at this position if the block below is solid,
+/// and there's enough air above it.
+pub fn is_walkable(chunks: &Chunks, pos: &IPos, params: &NavParams) -> bool {
+    chunks.is_solid(&pos.delta(0, 0, -1)) && is_clear(chunks, pos, params.headroom)
+}
+
+/// Returns the position at or below pos that an actor at pos would land on when falling down,
+/// or None if there's no ground within max_depth blocks.
+pub fn ground_below(chunks: &Chunks, pos: &IPos, max_depth: u32) -> Option<IPos> {
+    (0..=max_depth as i32)
+        .map(|dz| pos.delta(0, 0, -dz))
+        .find(|pos| chunks.is_solid(&pos.delta(0, 0, -1)))
+}
+
+/// Checks if the given number of blocks, starting at pos and going up, are all free of obstacles.
+fn is_clear(chunks: &Chunks, pos: &IPos, height: u32) -> bool {
+    (0..height as i32).all(|dz| !chunks.is_solid(&pos.delta(0, 0, dz)))
+}
+
+/// Returns all positions that can be reached in a single step from pos, with their cost.
+/// These are always in one of the 6 neighbouring columns.
+pub fn walkable_neighbours(chunks: &Chunks, pos: &IPos, params: &NavParams) -> Vec<(IPos, u32)> {
+    let mut results = vec![];
+    for i in 0..6 {
+        let column = pos.neighbour(i);
+        for dz in -(params.max_drop_down as i32)..=params.max_step_up as i32 {
+            let target = column.delta(0, 0, dz);
+            if !is_walkable(chunks, &target, params) {
+                continue;
+            }
+            // When stepping up, the actor needs extra room above its head before moving sideways.
+            // When dropping down, it needs room to fall past the edge of the column.
+            let passable = if dz > 0 {
+                is_clear(chunks, &pos.delta(0, 0, params.headroom as i32), dz as u32)
+            } else {
+                is_clear(
+                    chunks,
+                    &target.delta(0, 0, params.headroom as i32),
+                    (-dz) as u32,
+                )
+            };
+            if passable {
+                results.push((target, STEP_COST + CLIMB_COST * dz.unsigned_abs()));
+            }
+        }
+    }
+    results
+}
+
+/// Finds the cheapest walkable path between two positions using A*.
+/// Both positions should be walkable; they are the positions the actor stands in, not the floor.
+/// The search works across chunk borders, but never enters chunks that aren't loaded.
+pub fn find_path(chunks: &Chunks, start: &IPos, goal: &IPos, params: &NavParams) -> Path {
+    let mut visited_chunks = HashSet::default();
+    let mut open = BinaryHeap::new();
+    let mut came_from: HashMap<IPos, IPos> = HashMap::default();
+    let mut cost_so_far: HashMap<IPos, u32> = HashMap::default();
+    let mut closed: HashSet<IPos> = HashSet::default();
+    open.push(Reverse((heuristic(start, goal), *start)));
+    cost_so_far.insert(*start, 0);
+
+    while let Some(Reverse((_, current))) = open.pop() {
+        if !closed.insert(current) {
+            continue;
+        }
+        visited_chunks.insert(chunks.chunk_id(&current));
+        if current == *goal {
+            return Path {
+                steps: reconstruct_path(&came_from, current),
+                chunks: visited_chunks,
+            };
+        }
+        if closed.len() >= params.max_visited {
+            break;
+        }
+        let current_cost = cost_so_far[&current];
+        for (next, step_cost) in walkable_neighbours(chunks, &current, params) {
+            let new_cost = current_cost + step_cost;
+            if cost_so_far.get(&next).map_or(true, |&cost| new_cost < cost) {
+                visited_chunks.insert(chunks.chunk_id(&next));
+                cost_so_far.insert(next, new_cost);
+                came_from.insert(next, current);
+                open.push(Reverse((new_cost + heuristic(&next, goal), next)));
+            }
+        }
+    }
+    Path {
+        steps: vec![],
+        chunks: visited_chunks,
+    }
+}
+
+/// Estimates the remaining cost. Never overestimates, since every step moves exactly one column.
+fn heuristic(a: &IPos, b: &IPos) -> u32 {
+    IPos::column_distance(a, b) as u32 * STEP_COST
+}
+
+fn reconstruct_path(came_from: &HashMap<IPos, IPos>, goal: IPos) -> Vec<IPos> {
+    let mut path = vec![goal];
+    let mut current = goal;
+    while let Some(previous) = came_from.get(&current) {
+        path.push(*previous);
+        current = *previous;
+    }
+    path.reverse();
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::hex_grid::biomes::BiomeType;
+    use crate::game::hex_grid::block::{Block, BlockType};
+    use crate::game::hex_grid::chunk::ChunkDimensions;
+    use crate::game::procedural_generation::presets::{FlatGenerator, FlatLayer};
+
+    /// The height at which actors stand on the floor of the test world.
+    const FLOOR: i32 = 3;
+
+    /// A world of small chunks around the origin, with a floor that is three blocks thick.
+    fn test_world() -> Chunks {
+        let dimensions = ChunkDimensions {
+            radius: 2,
+            height: 8,
+        };
+        let layers = [FlatLayer {
+            block_type: BlockType::Stone,
+            thickness: FLOOR as u32,
+        }];
+        let generator = FlatGenerator::new(&layers, BiomeType::Grassland);
+        let mut chunks = Chunks::new(dimensions, Box::new(generator));
+        for chunk_id in ChunkId::splat(0).range(2) {
+            chunks.generate_chunk(chunk_id);
+        }
+        chunks
+    }
+
+    fn set(chunks: &mut Chunks, pos: IPos, block_type: BlockType) {
+        let block = Block {
+            block_type,
+            ..Default::default()
+        };
+        chunks.set_block(&pos, block).unwrap();
+    }
+
+    #[test]
+    fn steps_up_onto_a_block() {
+        let mut chunks = test_world();
+        set(&mut chunks, IPos::new(1, 0, FLOOR), BlockType::Stone);
+        let start = IPos::new(0, 0, FLOOR);
+        let goal = IPos::new(1, 0, FLOOR + 1);
+        let path = find_path(&chunks, &start, &goal, &NavParams::default());
+        assert_eq!(path.steps, vec![start, goal]);
+        let params = NavParams {
+            max_step_up: 0,
+            ..Default::default()
+        };
+        assert!(!find_path(&chunks, &start, &goal, &params).is_found());
+    }
+
+    #[test]
+    fn drops_down_into_a_pit() {
+        let mut chunks = test_world();
+        set(&mut chunks, IPos::new(1, 0, FLOOR - 1), BlockType::Air);
+        set(&mut chunks, IPos::new(1, 0, FLOOR - 2), BlockType::Air);
+        let start = IPos::new(0, 0, FLOOR);
+        let goal = IPos::new(1, 0, FLOOR - 2);
+        let path = find_path(&chunks, &start, &goal, &NavParams::default());
+        assert_eq!(path.steps, vec![start, goal]);
+        let params = NavParams {
+            max_drop_down: 1,
+            ..Default::default()
+        };
+        assert!(!find_path(&chunks, &start, &goal, &params).is_found());
+    }
+
+    #[test]
+    fn walks_around_a_low_ceiling() {
+        let mut chunks = test_world();
+        let low = IPos::new(1, 0, FLOOR);
+        set(&mut chunks, low.delta(0, 0, 1), BlockType::Stone);
+        let start = IPos::new(0, 0, FLOOR);
+        let goal = IPos::new(2, 0, FLOOR);
+        // The only two-step path leads under the ceiling:
+        let path = find_path(&chunks, &start, &goal, &NavParams::default());
+        assert!(!is_walkable(&chunks, &low, &NavParams::default()));
+        assert_eq!(path.steps.len(), 4);
+        assert!(!path.steps.contains(&low));
+        let params = NavParams {
+            headroom: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            find_path(&chunks, &start, &goal, &params).steps,
+            vec![start, low, goal]
+        );
+    }
+
+    #[test]
+    fn crosses_chunk_borders() {
+        let chunks = test_world();
+        let start = IPos::new(-4, 1, FLOOR);
+        let goal = IPos::new(5, -2, FLOOR);
+        let path = find_path(&chunks, &start, &goal, &NavParams::default());
+        assert_eq!(
+            path.steps.len(),
+            IPos::column_distance(&start, &goal) as usize + 1
+        );
+        assert_eq!(path.steps.first(), Some(&start));
+        assert_eq!(path.steps.last(), Some(&goal));
+        assert!(path.chunks.len() > 2);
+    }
+
+    #[test]
+    fn gives_up_on_an_unreachable_goal() {
+        let chunks = test_world();
+        let start = IPos::new(0, 0, FLOOR);
+        // Floating in mid-air:
+        let goal = IPos::new(3, 0, FLOOR + 3);
+        let path = find_path(&chunks, &start, &goal, &NavParams::default());
+        assert!(!path.is_found());
+        assert!(path.steps.is_empty());
+    }
+}
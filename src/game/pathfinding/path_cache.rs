@@ -0,0 +1,232 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::time::TimerMode::Repeating;
+use bevy::utils::{HashMap, HashSet};
+
+use crate::game::hex_grid::axial::{ChunkId, IPos};
+use crate::game::hex_grid::chunk_loading::{
+    ChunkLoadedEvent, ChunkUnloadedEvent, ChunksChangedEvent,
+};
+use crate::game::hex_grid::chunks::Chunks;
+use crate::game::pathfinding::astar::{find_path, NavParams, Path};
+
+/// The cache is cleared entirely if it grows beyond this many paths.
+const MAX_CACHED_PATHS: usize = 1024;
+/// The number of seconds after which an actor searches for the path it's following again.
+const REPATH_INTERVAL: f32 = 0.5;
+/// An actor searches for a new path right away if its goal moves further than this many blocks.
+const REPATH_DISTANCE: i32 = 2;
+
+/// Remembers paths that were found before, so actors following the same route don't have
+/// to search for it again. Paths are forgotten when any block in a chunk they touch changes.
+#[derive(Resource, Default)]
+pub struct PathCache {
+    paths: HashMap<(IPos, IPos, NavParams), Path>,
+}
+
+impl PathCache {
+    /// Returns the cached path between start and goal, or searches for it if it isn't cached.
+    pub fn find_path(
+        &mut self,
+        chunks: &Chunks,
+        start: &IPos,
+        goal: &IPos,
+        params: &NavParams,
+    ) -> &Path {
+        if self.paths.len() >= MAX_CACHED_PATHS {
+            self.paths.clear();
+        }
+        self.paths
+            .entry((*start, *goal, *params))
+            .or_insert_with(|| find_path(chunks, start, goal, params))
+    }
+    /// Forgets all paths that touch any of the given chunks. Failed searches are forgotten
+    /// whenever any chunk is loaded, since the new chunk may connect the start to the goal.
+    pub fn invalidate(&mut self, changed: &HashSet<ChunkId>, any_loaded: bool) {
+        self.paths
+            .retain(|_, path| !is_outdated(path, changed, any_loaded));
+    }
+    pub fn clear(&mut self) {
+        self.paths.clear();
+    }
+}
+
+/// The path an actor is following. Rather than searching for it every frame, the actor searches
+/// again every so often, when the goal moves, or when the chunks along the path change.
+#[derive(Component)]
+pub struct PathFollower {
+    followed: Option<FollowedPath>,
+    repath_timer: Timer,
+}
+
+struct FollowedPath {
+    /// The goal the path was searched for, which may lie a bit away from the current goal.
+    goal: IPos,
+    path: Path,
+    /// The index of the step the actor is walking towards.
+    next: usize,
+}
+
+impl Default for PathFollower {
+    fn default() -> Self {
+        Self {
+            followed: None,
+            repath_timer: Timer::from_seconds(REPATH_INTERVAL, Repeating),
+        }
+    }
+}
+
+impl PathFollower {
+    /// Returns the next position to walk to in order to get from start to goal,
+    /// or None if there is no path.
+    pub fn next_step(
+        &mut self,
+        cache: &mut PathCache,
+        chunks: &Chunks,
+        start: &IPos,
+        goal: &IPos,
+        params: &NavParams,
+        delta: Duration,
+    ) -> Option<IPos> {
+        let timed_out = self.repath_timer.tick(delta).just_finished();
+        let goal_moved = self.followed.as_ref().map_or(true, |followed| {
+            IPos::distance_3d(&followed.goal, goal) > REPATH_DISTANCE
+        });
+        if timed_out || goal_moved {
+            self.followed = Some(FollowedPath {
+                goal: *goal,
+                path: cache.find_path(chunks, start, goal, params).clone(),
+                next: 1,
+            });
+            self.repath_timer.reset();
+        }
+        let followed = self.followed.as_mut()?;
+        // While walking from one step to the next, the actor may briefly be in neither of them,
+        // so it keeps heading for the same step until it reaches a step further along the path.
+        if let Some(index) = followed.path.steps.iter().position(|step| step == start) {
+            followed.next = index + 1;
+        }
+        followed.path.steps.get(followed.next).copied()
+    }
+    /// Forgets the path if it touches any of the given chunks, like PathCache::invalidate.
+    pub fn invalidate(&mut self, changed: &HashSet<ChunkId>, any_loaded: bool) {
+        if self.followed.as_ref().map_or(false, |followed| {
+            is_outdated(&followed.path, changed, any_loaded)
+        }) {
+            self.followed = None;
+        }
+    }
+}
+
+fn is_outdated(path: &Path, changed: &HashSet<ChunkId>, any_loaded: bool) -> bool {
+    (any_loaded && !path.is_found()) || !path.chunks.is_disjoint(changed)
+}
+
+/// Invalidates cached and followed paths whenever chunks are loaded, unloaded or changed.
+/// A chunk being unloaded may cut off a path, and a chunk being loaded may open up a shorter one.
+pub fn invalidate_path_cache(
+    mut cache: ResMut<PathCache>,
+    mut followers: Query<&mut PathFollower>,
+    mut loaded_events: EventReader<ChunkLoadedEvent>,
+    mut unloaded_events: EventReader<ChunkUnloadedEvent>,
+    mut changed_events: EventReader<ChunksChangedEvent>,
+) {
+    let loaded = loaded_events
+        .iter()
+        .map(|event| event.id)
+        .collect::<Vec<ChunkId>>();
+    let changed = loaded
+        .iter()
+        .copied()
+        .chain(unloaded_events.iter().map(|event| event.id))
+        .chain(
+            changed_events
+                .iter()
+                .flat_map(|event| event.ids.iter().copied()),
+        )
+        .collect::<HashSet<ChunkId>>();
+    if !changed.is_empty() {
+        cache.invalidate(&changed, !loaded.is_empty());
+        for mut follower in followers.iter_mut() {
+            follower.invalidate(&changed, !loaded.is_empty());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::hex_grid::biomes::BiomeType;
+    use crate::game::hex_grid::block::BlockType;
+    use crate::game::hex_grid::chunk::ChunkDimensions;
+    use crate::game::procedural_generation::presets::{FlatGenerator, FlatLayer};
+
+    const FLOOR: i32 = 3;
+
+    fn test_world() -> Chunks {
+        let dimensions = ChunkDimensions {
+            radius: 2,
+            height: 8,
+        };
+        let layers = [FlatLayer {
+            block_type: BlockType::Stone,
+            thickness: FLOOR as u32,
+        }];
+        let generator = FlatGenerator::new(&layers, BiomeType::Grassland);
+        let mut chunks = Chunks::new(dimensions, Box::new(generator));
+        for chunk_id in ChunkId::splat(0).range(1) {
+            chunks.generate_chunk(chunk_id);
+        }
+        chunks
+    }
+
+    #[test]
+    fn failed_searches_are_forgotten_when_any_chunk_loads() {
+        let chunks = test_world();
+        let mut cache = PathCache::default();
+        let start = IPos::new(0, 0, FLOOR);
+        // Floating in the air, out of reach:
+        let goal = IPos::new(2, 0, FLOOR + 3);
+        assert!(!cache
+            .find_path(&chunks, &start, &goal, &NavParams::default())
+            .is_found());
+        cache.find_path(
+            &chunks,
+            &start,
+            &start.delta(1, 0, 0),
+            &NavParams::default(),
+        );
+        let far_away = HashSet::from_iter([ChunkId::new(20, 20, 0)]);
+        cache.invalidate(&far_away, false);
+        assert_eq!(cache.paths.len(), 2);
+        cache.invalidate(&far_away, true);
+        assert_eq!(cache.paths.len(), 1);
+    }
+
+    #[test]
+    fn followers_search_again_when_the_goal_moves_or_time_passes() {
+        let chunks = test_world();
+        let mut cache = PathCache::default();
+        let mut follower = PathFollower::default();
+        let start = IPos::new(0, 0, FLOOR);
+        let goal = IPos::new(3, 0, FLOOR);
+        let params = NavParams::default();
+        let frame = Duration::from_millis(16);
+        let mut next_step = |cache: &mut PathCache, goal: &IPos, delta: Duration| {
+            follower.next_step(cache, &chunks, &start, goal, &params, delta)
+        };
+        assert_eq!(
+            next_step(&mut cache, &goal, frame),
+            Some(start.delta(1, 0, 0))
+        );
+        cache.clear();
+        next_step(&mut cache, &goal.delta(0, 1, 0), frame);
+        assert!(cache.paths.is_empty());
+        next_step(&mut cache, &goal.delta(0, -3, 0), frame);
+        assert_eq!(cache.paths.len(), 1);
+        cache.clear();
+        next_step(&mut cache, &goal.delta(0, -3, 0), Duration::from_secs(1));
+        assert_eq!(cache.paths.len(), 1);
+    }
+}
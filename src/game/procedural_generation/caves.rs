@@ -0,0 +1,145 @@
+use bevy::math::DVec3;
+use noise::{NoiseFn, OpenSimplex};
+use serde::{Deserialize, Serialize};
+use splines::{Interpolation, Key, Spline};
+
+use crate::game::hex_grid::axial::IPos;
+use crate::game::procedural_generation::seeding::chance;
+
+/// Keeps the cave noise fields from lining up with each other and with the noise graphs.
+const WORM_A_SEED_OFFSET: u32 = 1001;
+const WORM_B_SEED_OFFSET: u32 = 1002;
+const CAVERN_SEED_OFFSET: u32 = 1003;
+const OVERHANG_SEED_OFFSET: u32 = 1004;
+const BREACH_SALT: u32 = 1;
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct CaveConfig {
+    /// If false, no caves are carved. Overhangs are still applied.
+    pub enabled: bool,
+    /// The size (in meters) of the bends in worm caves.
+    pub worm_scale: f64,
+    /// How wide worm caves are. Worm caves run where two noise fields are both within this
+    /// distance of zero, so sensible values are small; around 0.05 to 0.15.
+    pub worm_width: f64,
+    /// The size (in meters) of cavern chambers.
+    pub cavern_scale: f64,
+    /// Caverns are carved where the cavern noise exceeds this value, so higher means fewer caverns.
+    pub cavern_threshold: f64,
+    /// Pairs of (depth below the surface in blocks, multiplier) that determine how common caves
+    /// are at each depth. A multiplier of 0 means no caves at all, 1 means the configured values
+    /// are used as-is. Depths in between the points are interpolated.
+    pub frequency_by_depth: Vec<(f64, f64)>,
+    /// Caves stay at least this many blocks below the surface, except where they breach it.
+    pub surface_cover: u32,
+    /// The chance that caves within an area are allowed to break through to the surface.
+    pub surface_breach_chance: f64,
+    /// The size (in meters) of the areas that surface breaches are decided for.
+    pub breach_area_size: f64,
+    /// The size (in meters) of overhangs and arches.
+    pub overhang_scale: f64,
+    /// How many blocks the 3D overhang noise can move the terrain surface up or down.
+    /// Zero turns the terrain into a plain heightfield.
+    pub overhang_strength: f64,
+}
+
+/// The 3D density pass of the terrain generator, ready to be sampled.
+pub struct CaveNoise {
+    config: CaveConfig,
+    seed: u32,
+    worm_a: OpenSimplex,
+    worm_b: OpenSimplex,
+    cavern: OpenSimplex,
+    overhang: OpenSimplex,
+    frequency_by_depth: Spline<f64, f64>,
+}
+
+impl Default for CaveNoise {
+    fn default() -> Self {
+        Self::new(&CaveConfig::default(), 0)
+    }
+}
+
+impl CaveNoise {
+    pub fn new(config: &CaveConfig, seed: u32) -> Self {
+        let mut points = config.frequency_by_depth.clone();
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self {
+            config: config.clone(),
+            seed,
+            worm_a: OpenSimplex::new(seed.wrapping_add(WORM_A_SEED_OFFSET)),
+            worm_b: OpenSimplex::new(seed.wrapping_add(WORM_B_SEED_OFFSET)),
+            cavern: OpenSimplex::new(seed.wrapping_add(CAVERN_SEED_OFFSET)),
+            overhang: OpenSimplex::new(seed.wrapping_add(OVERHANG_SEED_OFFSET)),
+            frequency_by_depth: Spline::from_vec(
+                points
+                    .iter()
+                    .map(|(depth, multiplier)| Key::new(*depth, *multiplier, Interpolation::Linear))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Whether the block at the given absolute position is solid.
+    /// The surface is the height of the terrain in this block's column, as given by the heightmap.
+    #[must_use]
+    pub fn is_solid(&self, pos: &IPos, surface: f64) -> bool {
+        let xyz = pos.as_xyz().as_dvec3();
+        let depth = surface - pos.z() as f64;
+        let density = depth + self.overhang_offset(xyz);
+        if density <= 0. {
+            return false;
+        }
+        if !self.config.enabled {
+            return true;
+        }
+        if depth < self.config.surface_cover as f64 && !self.breaches_surface(&xyz) {
+            return true;
+        }
+        !self.is_cave(&xyz, depth)
+    }
+
+    /// How many blocks the overhang noise moves the surface at this position.
+    fn overhang_offset(&self, xyz: DVec3) -> f64 {
+        if self.config.overhang_strength == 0. {
+            return 0.;
+        }
+        let sample = xyz / self.config.overhang_scale.max(0.0001);
+        self.overhang.get(sample.to_array()) * self.config.overhang_strength
+    }
+
+    fn is_cave(&self, xyz: &DVec3, depth: f64) -> bool {
+        let frequency = self.frequency_at(depth);
+        if frequency <= 0. {
+            return false;
+        }
+        let worm_sample = (*xyz / self.config.worm_scale.max(0.0001)).to_array();
+        let worm_width = self.config.worm_width * frequency;
+        if self.worm_a.get(worm_sample).abs() < worm_width
+            && self.worm_b.get(worm_sample).abs() < worm_width
+        {
+            return true;
+        }
+        let cavern_sample = (*xyz / self.config.cavern_scale.max(0.0001)).to_array();
+        let cavern_threshold = 1. - (1. - self.config.cavern_threshold) * frequency;
+        self.cavern.get(cavern_sample) > cavern_threshold
+    }
+
+    fn frequency_at(&self, depth: f64) -> f64 {
+        let keys = self.frequency_by_depth.keys();
+        match (keys.first(), keys.last()) {
+            (Some(first), _) if depth <= first.t => first.value,
+            (_, Some(last)) if depth >= last.t => last.value,
+            _ => self.frequency_by_depth.clamped_sample(depth).unwrap_or(1.),
+        }
+    }
+
+    /// Surface breaches are decided per area, so that a breaching cave opens up
+    /// into a proper entrance instead of a scattering of single holes.
+    fn breaches_surface(&self, xyz: &DVec3) -> bool {
+        let area = (xyz.truncate() / self.config.breach_area_size.max(1.)).floor();
+        chance(self.seed, BREACH_SALT, &[area.x as i32, area.y as i32])
+            < self.config.surface_breach_chance
+    }
+}
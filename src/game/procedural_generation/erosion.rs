@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::game::hex_grid::axial::{ChunkId, ColumnId};
+use crate::game::hex_grid::chunk::ChunkDimensions;
+use crate::game::procedural_generation::region_cache::RegionCache;
+use crate::game::procedural_generation::seeding::seeded_rng;
+
+const DROPLET_SALT: u32 = 400;
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ErosionConfig {
+    /// If false, the terrain is used exactly as the elevation graph describes it.
+    pub enabled: bool,
+    /// Erosion runs on hexagonal regions of this radius (in columns).
+    pub region_radius: u32,
+    /// Each region is eroded with this many extra columns around it. Where regions meet, their
+    /// results are blended, so a wider margin makes for less visible seams.
+    pub region_margin: u32,
+    pub hydraulic: HydraulicErosion,
+    pub thermal: ThermalErosion,
+}
+
+/// Simulates rain: droplets run downhill, picking up sediment where they speed up,
+/// and dropping it where they slow down or evaporate.
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct HydraulicErosion {
+    /// The number of droplets per column in the region. Zero disables hydraulic erosion.
+    pub droplets_per_column: f32,
+    /// The maximum number of steps a droplet takes before it stops.
+    pub max_lifetime: u32,
+    /// The chance that a droplet keeps going in the same direction, if that direction is downhill.
+    pub inertia: f64,
+    /// How much sediment a droplet can carry, relative to its speed, water and the slope.
+    pub capacity: f64,
+    /// The fraction of the remaining capacity that a droplet picks up in each step.
+    pub erosion_rate: f64,
+    /// The fraction of the excess sediment that a droplet drops in each step.
+    pub deposition_rate: f64,
+    /// The fraction of its water that a droplet loses in each step.
+    pub evaporation: f64,
+}
+
+/// Simulates loose material sliding down slopes that are too steep to hold it.
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ThermalErosion {
+    /// The number of passes over the region. Zero disables thermal erosion.
+    pub iterations: u32,
+    /// The largest height difference (in blocks) between neighbouring columns that stays put.
+    pub talus: f64,
+    /// The fraction of the excess height difference that slides down in each pass.
+    pub rate: f64,
+}
+
+/// The change in height of each column in a region, caused by erosion.
+type RegionErosion = HashMap<ColumnId, f64>;
+
+/// Erodes the terrain before chunks are filled in.
+///
+/// The heightmap is eroded per region, and the results are cached. Because droplets are seeded
+/// from the id of the region, every region always erodes the same way, no matter which chunk
+/// asked for it first.
+#[derive(Default)]
+pub struct Erosion {
+    config: ErosionConfig,
+    seed: u32,
+    regions: RegionCache<ChunkId, RegionErosion>,
+}
+
+impl Erosion {
+    /// The reach is how far (in columns) from one another the terrain may be sampled in one go,
+    /// for example while tracing rivers. The cache is made large enough to hold every region
+    /// within that reach, so they aren't eroded over and over again.
+    pub fn new(config: &ErosionConfig, seed: u32, reach: u32) -> Self {
+        Self {
+            config: config.clone(),
+            seed,
+            regions: RegionCache::new(Self::regions_within(config, reach)),
+        }
+    }
+    /// Estimates the number of regions that blend into the columns within reach of each other.
+    fn regions_within(config: &ErosionConfig, reach: u32) -> usize {
+        let hexagon = |radius: usize| 3 * radius * radius + 3 * radius + 1;
+        let region_radius = config.region_radius as usize;
+        // Columns blend the regions around them, so the regions along the edges count as well:
+        let covered = reach as usize + 2 * (region_radius + config.region_margin as usize);
+        // Regions don't tile a hexagon exactly, and lakes can spread beyond the reach of the rivers
+        // that fill them, so there is room for twice as many:
+        2 * hexagon(covered) / hexagon(region_radius) + 7
+    }
+    /// Returns the eroded height of the column. The height function should give the height
+    /// (in blocks) of the uneroded terrain for any column in the world.
+    #[must_use]
+    pub fn eroded_height(&self, column: &ColumnId, height: &impl Fn(&ColumnId) -> f64) -> f64 {
+        let raw_height = height(column);
+        if !self.config.enabled {
+            return raw_height;
+        }
+        // Blend the results of all regions that cover this column, weighted by how close the
+        // column is to their centers:
+        let dimensions = self.region_dimensions();
+        let reach = (self.config.region_radius + self.config.region_margin) as f64;
+        let nearest = ChunkId::from_block_pos(&column.as_ipos(0), &dimensions);
+        let mut total_weight = 0.;
+        let mut total_delta = 0.;
+        for region_id in (0..6).map(|i| nearest.neighbour(i)).chain([nearest]) {
+            let center = region_id.center_pos(&dimensions).as_column_id();
+            let distance = ColumnId::distance(column, &center) as f64;
+            let weight = (1. - distance / (reach + 1.)).max(0.).powi(2);
+            if weight == 0. {
+                continue;
+            }
+            if let Some(delta) = self.region(&region_id, height).get(column) {
+                total_weight += weight;
+                total_delta += delta * weight;
+            }
+        }
+        if total_weight == 0. {
+            raw_height
+        } else {
+            raw_height + total_delta / total_weight
+        }
+    }
+
+    fn region_dimensions(&self) -> ChunkDimensions {
+        ChunkDimensions {
+            radius: self.config.region_radius,
+            height: 1,
+        }
+    }
+
+    fn region(
+        &self,
+        region_id: &ChunkId,
+        height: &impl Fn(&ColumnId) -> f64,
+    ) -> Arc<RegionErosion> {
+        self.regions
+            .get_or_generate(region_id, || self.erode_region(region_id, height))
+    }
+
+    fn erode_region(
+        &self,
+        region_id: &ChunkId,
+        height: &impl Fn(&ColumnId) -> f64,
+    ) -> RegionErosion {
+        let center = region_id
+            .center_pos(&self.region_dimensions())
+            .as_column_id();
+        let columns = center.range(self.config.region_radius + self.config.region_margin);
+        let raw = columns
+            .iter()
+            .map(|column| (*column, height(column)))
+            .collect::<HashMap<ColumnId, f64>>();
+        let mut heights = raw.clone();
+        self.erode_hydraulic(&columns, &mut heights, region_id);
+        self.erode_thermal(&columns, &mut heights);
+        columns
+            .iter()
+            .map(|column| (*column, heights[column] - raw[column]))
+            .collect()
+    }
+
+    fn erode_hydraulic(
+        &self,
+        columns: &[ColumnId],
+        heights: &mut HashMap<ColumnId, f64>,
+        region_id: &ChunkId,
+    ) {
+        let settings = &self.config.hydraulic;
+        let droplets = (columns.len() as f32 * settings.droplets_per_column) as usize;
+        let mut rng = seeded_rng(self.seed, DROPLET_SALT, &[region_id.q(), region_id.r()]);
+        for _ in 0..droplets {
+            let mut current = columns[rng.gen_range(0..columns.len())];
+            let mut direction: Option<u32> = None;
+            let (mut speed, mut water, mut sediment) = (1., 1., 0.);
+            for _ in 0..settings.max_lifetime {
+                let here = heights[&current];
+                let steepest = (0..6)
+                    .filter_map(|i| {
+                        let neighbour = current.neighbour(i);
+                        heights.get(&neighbour).map(|height| (i, here - height))
+                    })
+                    .max_by(|a, b| a.1.total_cmp(&b.1));
+                // Keep going in the same direction if it's still downhill and inertia allows:
+                let keep_going = direction.and_then(|i| {
+                    let drop = here - heights.get(&current.neighbour(i))?;
+                    (drop > 0. && rng.gen_bool(settings.inertia.clamp(0., 1.))).then_some((i, drop))
+                });
+                let (next_direction, drop) = match keep_going.or(steepest) {
+                    Some((i, drop)) if drop > 0. => (i, drop),
+                    _ => {
+                        // The droplet is stuck in a pit; it drops what it carries and stops.
+                        *heights.get_mut(&current).unwrap() += sediment;
+                        break;
+                    }
+                };
+                let capacity = (drop * speed * water * settings.capacity).max(0.01);
+                let column_height = heights.get_mut(&current).unwrap();
+                if sediment > capacity {
+                    let deposit = (sediment - capacity) * settings.deposition_rate;
+                    sediment -= deposit;
+                    *column_height += deposit;
+                } else {
+                    // Never dig deeper than the next column, or the droplet would dig its own pit:
+                    let erode = ((capacity - sediment) * settings.erosion_rate).min(drop);
+                    sediment += erode;
+                    *column_height -= erode;
+                }
+                speed = (speed * speed + drop).sqrt();
+                water *= 1. - settings.evaporation;
+                direction = Some(next_direction);
+                current = current.neighbour(next_direction);
+            }
+        }
+    }
+
+    fn erode_thermal(&self, columns: &[ColumnId], heights: &mut HashMap<ColumnId, f64>) {
+        let settings = &self.config.thermal;
+        for _ in 0..settings.iterations {
+            let mut deltas = HashMap::<ColumnId, f64>::new();
+            for column in columns.iter() {
+                let here = heights[column];
+                for i in 0..6 {
+                    let neighbour = column.neighbour(i);
+                    if let Some(there) = heights.get(&neighbour) {
+                        let excess = here - there - settings.talus;
+                        if excess > 0. {
+                            // Split over the neighbours; at most 6 of them can take material:
+                            let amount = excess * settings.rate / 6.;
+                            *deltas.entry(*column).or_default() -= amount;
+                            *deltas.entry(neighbour).or_default() += amount;
+                        }
+                    }
+                }
+            }
+            for (column, delta) in deltas {
+                *heights.get_mut(&column).unwrap() += delta;
+            }
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::game::hex_grid::axial::{ChunkId, ColumnId, IPos};
+use crate::game::hex_grid::biomes::BiomeType;
+use crate::game::hex_grid::block::{Block, BlockType};
+use crate::game::hex_grid::chunk::Chunk;
+use crate::game::hex_grid::templates::{StructureLibrary, TemplateBlock, TemplatePlacement};
+use crate::game::procedural_generation::seeding::seeded_rng;
+
+const FEATURE_SALT: u32 = 200;
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct FeatureConfig {
+    /// For each biome, the features that may be placed there. Biomes that aren't listed stay bare.
+    pub biomes: HashMap<BiomeType, Vec<FeatureRule>>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct FeatureRule {
+    pub feature: Feature,
+    /// The average number of these features in a chunk fully covered by the biome.
+    pub density: f32,
+    /// The feature is only placed on top of these blocks.
+    pub surface_blocks: Vec<BlockType>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub enum Feature {
+    Tree {
+        /// The minimum and maximum height of the trunk, in blocks.
+        trunk_height: (u32, u32),
+        /// The radius of the widest layer of leaves.
+        crown_radius: u32,
+    },
+    Boulder {
+        /// The minimum and maximum radius of the boulder.
+        radius: (u32, u32),
+    },
+    Ruin {
+        /// The minimum and maximum radius of the ring of walls.
+        radius: (u32, u32),
+        /// The height of the walls where they are still standing.
+        wall_height: u32,
+        /// The chance that any given piece of wall has crumbled away.
+        decay: f32,
+    },
+    /// A structure template from the StructureLibrary, placed with a random rotation and mirroring.
+    Structure { id: String },
+}
+
+impl Feature {
+    /// Returns the blocks that make up this feature, relative to the block just above the surface.
+    fn blocks(&self, rng: &mut StdRng, structures: &StructureLibrary) -> Vec<TemplateBlock> {
+        match self {
+            Feature::Tree {
+                trunk_height,
+                crown_radius,
+            } => {
+                let height = rng.gen_range(trunk_height.0..=trunk_height.1.max(trunk_height.0));
+                let mut blocks = (0..height as i32)
+                    .map(|z| TemplateBlock::full(IPos::new(0, 0, z), BlockType::Wood))
+                    .collect::<Vec<TemplateBlock>>();
+                // The crown is widest just below the top of the trunk and narrows towards the top:
+                let crown_radius = *crown_radius as i32;
+                for layer in 0..=crown_radius {
+                    let z = height as i32 - 1 + layer - crown_radius / 2;
+                    let radius = (crown_radius - layer).max(1) as u32;
+                    for pos in IPos::new(0, 0, z).range(radius) {
+                        if pos.qr() != IPos::default().qr() || z >= height as i32 {
+                            blocks.push(TemplateBlock::full(pos, BlockType::Leaves));
+                        }
+                    }
+                }
+                blocks
+            }
+            Feature::Boulder { radius } => {
+                let radius = rng.gen_range(radius.0..=radius.1.max(radius.0)) as i32;
+                // A dome that is sunk one block into the ground:
+                (0..=radius)
+                    .flat_map(|layer| {
+                        IPos::new(0, 0, layer - 1).range((radius - layer).max(0) as u32)
+                    })
+                    .map(|pos| TemplateBlock::full(pos, BlockType::Stone))
+                    .collect()
+            }
+            Feature::Ruin {
+                radius,
+                wall_height,
+                decay,
+            } => {
+                let radius = rng.gen_range(radius.0..=radius.1.max(radius.0));
+                let mut blocks = vec![];
+                for column in ColumnId::ring(radius) {
+                    for z in 0..*wall_height as i32 {
+                        // Walls crumble from the top, so a crumbled block takes the rest with it:
+                        if rng.gen::<f32>() < *decay {
+                            break;
+                        }
+                        blocks.push(TemplateBlock::full(
+                            column.as_ipos(z),
+                            BlockType::Cobblestone,
+                        ));
+                    }
+                }
+                blocks
+            }
+            Feature::Structure { id } => {
+                let placement = TemplatePlacement {
+                    rotation: rng.gen_range(0..6),
+                    mirrored: rng.gen(),
+                };
+                structures
+                    .get(id)
+                    .map(|template| template.transformed(&IPos::default(), &placement))
+                    .unwrap_or_default()
+            }
+        }
+    }
+}
+
+/// Places trees, boulders and ruins on freshly generated chunks.
+/// Which features are placed where only depends on the seed and the chunk itself,
+/// so the same chunk always gets the same features.
+#[derive(Default)]
+pub struct FeaturePlacer {
+    config: FeatureConfig,
+    structures: StructureLibrary,
+    seed: u32,
+}
+
+impl FeaturePlacer {
+    pub fn new(config: &FeatureConfig, structures: &StructureLibrary, seed: u32) -> Self {
+        for rule in config.biomes.values().flatten() {
+            if let Feature::Structure { id } = &rule.feature {
+                if structures.get(id).is_none() {
+                    warn!(
+                        "The worldgen config uses structure '{}', but no such structure was loaded.",
+                        id
+                    );
+                }
+            }
+        }
+        Self {
+            config: config.clone(),
+            structures: structures.clone(),
+            seed,
+        }
+    }
+
+    /// Returns the blocks of all features that grow out of the given chunk, at absolute positions.
+    /// Features can extend beyond the chunk's borders, so not all blocks necessarily fall inside it.
+    /// Features are only placed in the layer of chunks that contains the terrain surface, which
+    /// is looked up through terrain_height. In the layers below, the highest solid block in
+    /// a column is buried under the next layer.
+    #[must_use]
+    pub fn place(
+        &self,
+        chunk: &Chunk,
+        chunk_id: &ChunkId,
+        terrain_height: &impl Fn(&ColumnId) -> f64,
+    ) -> Vec<(IPos, Block)> {
+        let dimensions = chunk.dimensions();
+        let center = chunk_id.center_pos(dimensions);
+        let columns = chunk.columns();
+        let mut rng = seeded_rng(
+            self.seed,
+            FEATURE_SALT,
+            &[chunk_id.q(), chunk_id.r(), chunk_id.z()],
+        );
+        let mut blocks = vec![];
+        for column in columns.iter() {
+            // Always roll for every column, so the rolls don't shift around when a column is skipped:
+            let roll = rng.gen::<f32>() * columns.len() as f32;
+            let feature_seed = rng.gen::<u32>();
+            let surface_z = match chunk.surface_height(column) {
+                Ok(Some(z)) => z,
+                _ => continue,
+            };
+            let terrain_top = terrain_height(&(column + center).as_column_id()).ceil() as i32 - 1;
+            if !dimensions.contains(&column.as_ipos(terrain_top - center.z())) {
+                continue;
+            }
+            // Features don't grow under water:
+            let above = column.as_ipos(surface_z + 1);
+            if dimensions.contains(&above) && chunk.block(&above).block_type != BlockType::Air {
+                continue;
+            }
+            let surface = chunk.block(&column.as_ipos(surface_z));
+            let rule = self
+                .config
+                .biomes
+                .get(&surface.biome_type)
+                .and_then(|rules| pick_rule(rules, roll));
+            if let Some(rule) = rule {
+                if !rule.surface_blocks.contains(&surface.block_type) {
+                    continue;
+                }
+                let base = column.as_ipos(surface_z + 1) + center;
+                let mut feature_rng =
+                    seeded_rng(self.seed, feature_seed, &[base.q(), base.r(), base.z()]);
+                for block in rule.feature.blocks(&mut feature_rng, &self.structures) {
+                    let pos = block.pos + base;
+                    let block = Block {
+                        block_type: block.block_type,
+                        biome_type: surface.biome_type,
+                        shape: block.shape,
+                    };
+                    blocks.push((pos, block));
+                }
+            }
+        }
+        blocks
+    }
+}
+
+/// The densities of the rules are stacked on top of each other; the roll picks one of them,
+/// or none if it lands beyond the total.
+fn pick_rule(rules: &[FeatureRule], roll: f32) -> Option<&FeatureRule> {
+    let mut total = 0.;
+    for rule in rules.iter() {
+        total += rule.density;
+        if roll < total {
+            return Some(rule);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::hex_grid::chunk::ChunkDimensions;
+    use crate::game::procedural_generation::presets::{FlatGenerator, FlatLayer};
+    use crate::game::procedural_generation::world_generator::WorldGenerator;
+
+    #[test]
+    fn features_only_grow_in_the_layer_of_the_terrain_surface() {
+        let dimensions = ChunkDimensions {
+            radius: 3,
+            height: 8,
+        };
+        let rule = FeatureRule {
+            feature: Feature::Boulder { radius: (1, 1) },
+            density: 1000.,
+            surface_blocks: vec![BlockType::Stone],
+        };
+        let config = FeatureConfig {
+            biomes: [(BiomeType::Grassland, vec![rule])].into_iter().collect(),
+        };
+        let placer = FeaturePlacer::new(&config, &StructureLibrary::default(), 7);
+        // The terrain surface is in the second layer, three blocks above the first:
+        let thickness = dimensions.height + 3;
+        let layers = [FlatLayer {
+            block_type: BlockType::Stone,
+            thickness,
+        }];
+        let generator = FlatGenerator::new(&layers, BiomeType::Grassland);
+        let terrain_height = |_: &ColumnId| thickness as f64;
+        for (chunk_id, expect_features) in [
+            (ChunkId::new(0, 0, 0), false),
+            (ChunkId::new(0, 0, 1), true),
+            (ChunkId::new(0, 0, 2), false),
+        ] {
+            let chunk = generator.generate_chunk(&chunk_id, dimensions);
+            let features = placer.place(&chunk, &chunk_id, &terrain_height);
+            assert_eq!(!features.is_empty(), expect_features, "{:?}", chunk_id);
+        }
+    }
+}
@@ -0,0 +1,432 @@
+use std::fmt::{Display, Formatter};
+
+use bevy::math::{DVec2, Vec3Swizzles};
+use serde::{Deserialize, Serialize};
+
+use crate::game::hex_grid::axial::ChunkId;
+use crate::game::hex_grid::biomes::BiomeType;
+use crate::game::hex_grid::block::{Block, BlockShape, BlockType};
+use crate::game::hex_grid::chunk::{Chunk, ChunkDimensions};
+use crate::game::procedural_generation::world_generator::WorldGenerator;
+use crate::io::asset_loading::ModDirectories;
+
+/// The file to read the heights of the world from.
+/// Paths are relative to the mod directories, and the last mod that has the file is used.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub enum HeightmapFile {
+    /// A grayscale PGM image, either plain (P2) or binary (P5), with 8 or 16 bits per pixel.
+    Pgm(String),
+    /// Headerless 16-bit grayscale pixels, row by row.
+    Raw16 {
+        path: String,
+        width: u32,
+        height: u32,
+        little_endian: bool,
+    },
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct HeightmapSettings {
+    pub heightmap: HeightmapFile,
+    /// An optional PPM image (P3 or P6) with the same dimensions as the heightmap,
+    /// in which each pixel's color determines the biome.
+    pub biome_map: Option<String>,
+    /// Each color in the biome map is matched to the closest of these colors.
+    #[serde(default)]
+    pub biome_colors: Vec<((u8, u8, u8), BiomeType)>,
+    /// The biome used everywhere if there is no biome map.
+    pub default_biome: BiomeType,
+    /// The size of a single pixel in the world, in meters.
+    pub meters_per_pixel: f32,
+    /// The height (in blocks) of black pixels. Everything beyond the edges of the image is
+    /// this height as well.
+    pub min_height: f64,
+    /// The height (in blocks) of white pixels.
+    pub max_height: f64,
+    /// Columns lower than this are filled up with water to this height.
+    pub sea_level: Option<f64>,
+}
+
+/// The ways in which reading a heightmap or biome map can fail.
+#[derive(Debug)]
+pub enum HeightmapError {
+    /// None of the loaded mods has a file at this path.
+    NotFound(String),
+    Io(String, std::io::Error),
+    /// The file isn't a netpbm image of the expected kind.
+    UnsupportedFormat(String),
+    /// The file ended before all pixels were read, or a value couldn't be parsed.
+    Malformed(String),
+}
+
+impl Display for HeightmapError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HeightmapError::NotFound(path) => {
+                write!(f, "None of the loaded mods contains {}.", path)
+            }
+            HeightmapError::Io(path, err) => write!(f, "Could not read {}: {}", path, err),
+            HeightmapError::UnsupportedFormat(path) => write!(
+                f,
+                "{} is not a supported image; expected a PGM (P2/P5) or PPM (P3/P6) file.",
+                path
+            ),
+            HeightmapError::Malformed(path) => {
+                write!(f, "{} is malformed or shorter than its header says.", path)
+            }
+        }
+    }
+}
+
+impl std::error::Error for HeightmapError {}
+
+/// A decoded image, with every channel of every pixel normalised to 0..1.
+struct Image {
+    width: usize,
+    height: usize,
+    channels: usize,
+    values: Vec<f64>,
+}
+
+impl Image {
+    /// Parses a netpbm image. The path is only used in errors.
+    fn parse_netpbm(
+        bytes: &[u8],
+        path: &str,
+        expected_channels: usize,
+    ) -> Result<Self, HeightmapError> {
+        let malformed = || HeightmapError::Malformed(path.to_string());
+        let (magic, channels, binary) = match bytes.get(0..2) {
+            Some(b"P2") => ("P2", 1, false),
+            Some(b"P5") => ("P5", 1, true),
+            Some(b"P3") => ("P3", 3, false),
+            Some(b"P6") => ("P6", 3, true),
+            _ => return Err(HeightmapError::UnsupportedFormat(path.to_string())),
+        };
+        if channels != expected_channels {
+            return Err(HeightmapError::UnsupportedFormat(format!(
+                "{} ({})",
+                path, magic
+            )));
+        }
+        // The header is the magic number followed by width, height and maximum value,
+        // separated by whitespace and possibly interrupted by comments:
+        let mut cursor = 2;
+        let mut header = [0usize; 3];
+        for value in header.iter_mut() {
+            let token = next_token(bytes, &mut cursor).ok_or_else(malformed)?;
+            *value = token.parse().map_err(|_| malformed())?;
+        }
+        let [width, height, max_value] = header;
+        let count = width * height * channels;
+        let values = if binary {
+            // Exactly one whitespace character separates the header from the pixels:
+            let data = bytes.get(cursor + 1..).ok_or_else(malformed)?;
+            if max_value > 255 {
+                data.chunks_exact(2)
+                    .take(count)
+                    .map(|pair| u16::from_be_bytes([pair[0], pair[1]]) as f64)
+                    .collect::<Vec<f64>>()
+            } else {
+                data.iter().take(count).map(|byte| *byte as f64).collect()
+            }
+        } else {
+            let mut values = Vec::with_capacity(count);
+            while values.len() < count {
+                let token = next_token(bytes, &mut cursor).ok_or_else(malformed)?;
+                values.push(token.parse::<f64>().map_err(|_| malformed())?);
+            }
+            values
+        };
+        if values.len() < count || max_value == 0 {
+            return Err(malformed());
+        }
+        Ok(Self {
+            width,
+            height,
+            channels,
+            values: values.iter().map(|v| v / max_value as f64).collect(),
+        })
+    }
+
+    /// Parses headerless 16-bit pixels. The path is only used in errors.
+    fn parse_raw16(
+        bytes: &[u8],
+        path: &str,
+        width: usize,
+        height: usize,
+        little_endian: bool,
+    ) -> Result<Self, HeightmapError> {
+        if bytes.len() < width * height * 2 {
+            return Err(HeightmapError::Malformed(path.to_string()));
+        }
+        let values = bytes
+            .chunks_exact(2)
+            .take(width * height)
+            .map(|pair| {
+                let value = if little_endian {
+                    u16::from_le_bytes([pair[0], pair[1]])
+                } else {
+                    u16::from_be_bytes([pair[0], pair[1]])
+                };
+                value as f64 / u16::MAX as f64
+            })
+            .collect();
+        Ok(Self {
+            width,
+            height,
+            channels: 1,
+            values,
+        })
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> &[f64] {
+        let index = (y * self.width + x) * self.channels;
+        &self.values[index..index + self.channels]
+    }
+
+    /// Interpolates between the four pixels around the point.
+    /// Returns None if the point lies outside of the image.
+    fn sample_bilinear(&self, pixel: DVec2) -> Option<f64> {
+        let max = DVec2::new(self.width as f64 - 1., self.height as f64 - 1.);
+        if pixel.x < 0. || pixel.y < 0. || pixel.x > max.x || pixel.y > max.y {
+            return None;
+        }
+        let (x0, y0) = (pixel.x.floor() as usize, pixel.y.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let t = pixel - pixel.floor();
+        let top = self.pixel(x0, y0)[0] * (1. - t.x) + self.pixel(x1, y0)[0] * t.x;
+        let bottom = self.pixel(x0, y1)[0] * (1. - t.x) + self.pixel(x1, y1)[0] * t.x;
+        Some(top * (1. - t.y) + bottom * t.y)
+    }
+
+    /// Returns the pixel closest to the point, or None if the point lies outside of the image.
+    fn sample_nearest(&self, pixel: DVec2) -> Option<&[f64]> {
+        let rounded = pixel.round();
+        if rounded.x < 0.
+            || rounded.y < 0.
+            || rounded.x >= self.width as f64
+            || rounded.y >= self.height as f64
+        {
+            return None;
+        }
+        Some(self.pixel(rounded.x as usize, rounded.y as usize))
+    }
+}
+
+fn read(mods: &ModDirectories, path: &str) -> Result<Vec<u8>, HeightmapError> {
+    let file = mods
+        .find(path)
+        .ok_or_else(|| HeightmapError::NotFound(path.to_string()))?;
+    std::fs::read(file).map_err(|err| HeightmapError::Io(path.to_string(), err))
+}
+
+/// Returns the next whitespace-separated token, skipping comments that run from '#' to the end of
+/// the line.
+fn next_token<'a>(bytes: &'a [u8], cursor: &mut usize) -> Option<&'a str> {
+    loop {
+        match bytes.get(*cursor)? {
+            b'#' => {
+                while *bytes.get(*cursor)? != b'\n' {
+                    *cursor += 1;
+                }
+            }
+            byte if byte.is_ascii_whitespace() => *cursor += 1,
+            _ => break,
+        }
+    }
+    let start = *cursor;
+    while bytes
+        .get(*cursor)
+        .map_or(false, |byte| !byte.is_ascii_whitespace())
+    {
+        *cursor += 1;
+    }
+    std::str::from_utf8(&bytes[start..*cursor]).ok()
+}
+
+/// Generates a world from a heightmap image, and optionally a biome map.
+///
+/// The center of the image lies at the origin of the world. Each column is mapped to its position
+/// in the image through its xy-coordinates, and its height is interpolated from the pixels around
+/// it. The image is read once, up front; chunks are still generated on demand.
+pub struct HeightmapGenerator {
+    settings: HeightmapSettings,
+    heightmap: Image,
+    biome_map: Option<Image>,
+}
+
+impl HeightmapGenerator {
+    /// Reads the images from the first mod that has them, going backwards through the loading order.
+    pub fn new(
+        settings: &HeightmapSettings,
+        mods: &ModDirectories,
+    ) -> Result<Self, HeightmapError> {
+        let heightmap = match &settings.heightmap {
+            HeightmapFile::Pgm(path) => Image::parse_netpbm(&read(mods, path)?, path, 1)?,
+            HeightmapFile::Raw16 {
+                path,
+                width,
+                height,
+                little_endian,
+            } => Image::parse_raw16(
+                &read(mods, path)?,
+                path,
+                *width as usize,
+                *height as usize,
+                *little_endian,
+            )?,
+        };
+        let biome_map = match &settings.biome_map {
+            Some(path) => Some(Image::parse_netpbm(&read(mods, path)?, path, 3)?),
+            None => None,
+        };
+        Ok(Self {
+            settings: settings.clone(),
+            heightmap,
+            biome_map,
+        })
+    }
+
+    /// Converts a position in the world (in xy-space) to a position in the image, in pixels.
+    /// The image's y-axis points down, while the world's points up.
+    fn to_pixel(&self, xy: DVec2, image: &Image) -> DVec2 {
+        let center = DVec2::new(image.width as f64 - 1., image.height as f64 - 1.) / 2.;
+        let pixel = xy / self.settings.meters_per_pixel.max(0.0001) as f64;
+        DVec2::new(center.x + pixel.x, center.y - pixel.y)
+    }
+
+    fn height_at(&self, xy: DVec2) -> f64 {
+        let value = self
+            .heightmap
+            .sample_bilinear(self.to_pixel(xy, &self.heightmap))
+            .unwrap_or(0.);
+        self.settings.min_height + value * (self.settings.max_height - self.settings.min_height)
+    }
+
+    fn biome_at(&self, xy: DVec2) -> BiomeType {
+        let color = self
+            .biome_map
+            .as_ref()
+            .and_then(|map| map.sample_nearest(self.to_pixel(xy, map)));
+        if let Some(color) = color {
+            let color = [color[0] * 255., color[1] * 255., color[2] * 255.];
+            let closest = self.settings.biome_colors.iter().min_by(|a, b| {
+                color_distance(&color, &a.0).total_cmp(&color_distance(&color, &b.0))
+            });
+            if let Some((_, biome)) = closest {
+                return *biome;
+            }
+        }
+        self.settings.default_biome
+    }
+}
+
+fn color_distance(color: &[f64; 3], other: &(u8, u8, u8)) -> f64 {
+    let (r, g, b) = (
+        color[0] - other.0 as f64,
+        color[1] - other.1 as f64,
+        color[2] - other.2 as f64,
+    );
+    r * r + g * g + b * b
+}
+
+impl WorldGenerator for HeightmapGenerator {
+    fn generate_chunk(&self, chunk_id: &ChunkId, dimensions: ChunkDimensions) -> Chunk {
+        let mut chunk = Chunk::new(dimensions);
+        let center = chunk_id.center_pos(&dimensions);
+        for column in dimensions.columns() {
+            let xy = (column + center).as_xyz().xy().as_dvec2();
+            let height = self.height_at(xy);
+            let sea_level = self.settings.sea_level.unwrap_or(f64::MIN);
+            let biome_type = self.biome_at(xy);
+            for z in 0..dimensions.height as i32 {
+                let z_absolute = (center.z() + z) as f64;
+                let block_type = if z_absolute < height {
+                    BlockType::Stone
+                } else if z_absolute < sea_level {
+                    BlockType::Water
+                } else {
+                    BlockType::Air
+                };
+                chunk.set(
+                    &column.as_ipos(z),
+                    Block {
+                        block_type,
+                        biome_type,
+                        shape: BlockShape::Full,
+                    },
+                );
+            }
+        }
+        chunk
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(bytes: &[u8]) -> Result<Image, HeightmapError> {
+        Image::parse_netpbm(bytes, "test.pgm", 1)
+    }
+
+    #[test]
+    fn plain_and_binary_pgm_have_the_same_values() {
+        let plain = parse(b"P2\n2 2\n255\n0 51\n204 255\n").unwrap();
+        let binary = parse(b"P5\n2 2\n255\n\x00\x33\xcc\xff").unwrap();
+        assert_eq!((plain.width, plain.height), (2, 2));
+        assert_eq!((binary.width, binary.height), (2, 2));
+        assert_eq!(plain.values, vec![0., 0.2, 0.8, 1.]);
+        assert_eq!(binary.values, plain.values);
+    }
+
+    #[test]
+    fn sixteen_bit_pixels_are_big_endian_pairs() {
+        let binary = parse(b"P5 3 1 65535 \x00\x00\x80\x00\xff\xff").unwrap();
+        assert_eq!(binary.values, vec![0., 32768. / 65535., 1.]);
+        let plain = parse(b"P2 3 1 1000 0 250 1000").unwrap();
+        assert_eq!(plain.values, vec![0., 0.25, 1.]);
+    }
+
+    #[test]
+    fn comments_in_the_header_are_skipped() {
+        let image = parse(b"P2\n# Drawn by hand\n2 # width\n1\n# max:\n255\n0 255\n").unwrap();
+        assert_eq!((image.width, image.height), (2, 1));
+        assert_eq!(image.values, vec![0., 1.]);
+    }
+
+    #[test]
+    fn truncated_files_are_malformed() {
+        for bytes in [
+            &b"P2\n2 2\n255\n0 51 204\n"[..],
+            b"P5\n2 2\n255\n\x00\x33\xcc",
+            b"P5\n2 1\n65535\n\x00\x00\xff",
+            b"P2\n2 2\n",
+            b"P5\n2 2\n255",
+        ] {
+            assert!(
+                matches!(parse(bytes), Err(HeightmapError::Malformed(_))),
+                "{:?}",
+                String::from_utf8_lossy(bytes)
+            );
+        }
+        let raw = Image::parse_raw16(&[0, 0, 0], "test.raw", 2, 1, true);
+        assert!(matches!(raw, Err(HeightmapError::Malformed(_))));
+    }
+
+    #[test]
+    fn color_images_are_not_heightmaps() {
+        let result = parse(b"P3\n1 1\n255\n0 0 0\n");
+        assert!(matches!(result, Err(HeightmapError::UnsupportedFormat(_))));
+        let result = parse(b"GIF89a");
+        assert!(matches!(result, Err(HeightmapError::UnsupportedFormat(_))));
+    }
+
+    #[test]
+    fn files_outside_of_the_mods_are_not_found() {
+        let result = read(&ModDirectories::default(), "heightmaps/continent.pgm");
+        assert!(matches!(result, Err(HeightmapError::NotFound(_))));
+    }
+}
@@ -0,0 +1,449 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, BinaryHeap, HashMap, HashSet};
+use std::sync::Arc;
+
+use bevy::math::{DVec2, Vec3Swizzles};
+use serde::{Deserialize, Serialize};
+
+use crate::game::hex_grid::axial::{ColumnId, Pos};
+use crate::game::procedural_generation::region_cache::RegionCache;
+use crate::game::procedural_generation::seeding::chance;
+
+const SOURCE_SALT: u32 = 300;
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct HydrologyConfig {
+    /// If false, no water is generated at all.
+    pub enabled: bool,
+    /// Water flows across a coarse grid of nodes; this is the number of columns between them.
+    pub node_spacing: u32,
+    /// Rivers are traced per region of this many nodes across, so that every chunk sees the same
+    /// rivers no matter in which order chunks are generated.
+    pub region_size: u32,
+    /// Everything below this height (in blocks) is sea. Rivers end when they reach it.
+    pub sea_level: f64,
+    /// Rivers only spring from nodes at least this high (in blocks).
+    pub source_min_height: f64,
+    /// The chance that a node that is high enough is the source of a river.
+    pub source_chance: f64,
+    /// Rivers that haven't reached the sea after this many nodes dry up.
+    /// The nodes that a river skips while crossing a lake count towards this as well.
+    pub max_river_length: u32,
+    /// Half the width of a river, in meters.
+    pub river_half_width: f64,
+    /// How deep a river cuts below its water surface at its center, in blocks.
+    pub river_depth: f64,
+    /// Basins that would need more nodes than this to fill up don't become lakes;
+    /// any river that ends up in one dries up instead.
+    pub max_lake_nodes: u32,
+}
+
+impl HydrologyConfig {
+    /// How far (in columns) from the center of a region the terrain is sampled while its rivers
+    /// are traced. Rivers run up to max_river_length nodes away from the region's edge, and the
+    /// lakes they fill reach up to max_lake_nodes further.
+    #[must_use]
+    pub fn trace_reach(&self) -> u32 {
+        if !self.enabled {
+            return 0;
+        }
+        (self.region_size.max(1) / 2 + self.reach() + 1) * self.node_spacing.max(1)
+    }
+    /// How far (in nodes) water can end up from the source of its river.
+    fn reach(&self) -> u32 {
+        self.max_river_length + self.max_lake_nodes
+    }
+}
+
+/// The water in a single column.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ColumnWater {
+    /// The height of the ground under the water. This may be lower than the terrain was,
+    /// where a river carved out its channel.
+    pub bed: f64,
+    /// The height of the water surface. Blocks from the bed up to this height are water.
+    pub level: f64,
+}
+
+/// A stretch of river between two nodes, in xy-space.
+#[derive(Debug, Copy, Clone)]
+struct Segment {
+    from: DVec2,
+    to: DVec2,
+    level_from: f64,
+    level_to: f64,
+}
+
+/// All rivers that spring from a single region, and the lakes they fill.
+#[derive(Debug, Default)]
+struct RegionHydrology {
+    segments: Vec<Segment>,
+    /// Maps nodes that are under water to the level of the lake they're in.
+    lakes: HashMap<ColumnId, f64>,
+}
+
+/// Traces rivers downhill across a coarse grid of nodes, filling closed basins with lakes
+/// along the way.
+///
+/// The rivers only depend on the seed and the elevation, and are traced per region.
+/// To find the water for a chunk, all regions close enough for their rivers to reach it
+/// are traced (or taken from the cache).
+#[derive(Default)]
+pub struct Hydrology {
+    config: HydrologyConfig,
+    seed: u32,
+    regions: RegionCache<ColumnId, RegionHydrology>,
+}
+
+impl Hydrology {
+    pub fn new(config: &HydrologyConfig, seed: u32) -> Self {
+        Self {
+            config: config.clone(),
+            seed,
+            regions: RegionCache::default(),
+        }
+    }
+
+    /// Returns the water in each of the given columns, in the same order.
+    /// The columns should be absolute, and the height function should give the height of the
+    /// terrain (in blocks) for any column in the world.
+    #[must_use]
+    pub fn water(
+        &self,
+        columns: &[ColumnId],
+        height: &impl Fn(&ColumnId) -> f64,
+    ) -> Vec<Option<ColumnWater>> {
+        if !self.config.enabled || columns.is_empty() {
+            return vec![None; columns.len()];
+        }
+        let regions = self.regions_near(columns, height);
+        columns
+            .iter()
+            .map(|column| self.column_water(column, height(column), &regions))
+            .collect()
+    }
+
+    fn column_water(
+        &self,
+        column: &ColumnId,
+        terrain_height: f64,
+        regions: &[Arc<RegionHydrology>],
+    ) -> Option<ColumnWater> {
+        let xy = column_xy(column);
+        let mut water: Option<ColumnWater> = None;
+        let mut add_water = |candidate: ColumnWater| {
+            if water.map_or(true, |water| candidate.bed < water.bed) {
+                water = Some(candidate);
+            }
+        };
+        if terrain_height < self.config.sea_level {
+            add_water(ColumnWater {
+                bed: terrain_height,
+                level: self.config.sea_level,
+            });
+        }
+        let node = self.nearest_node(column);
+        for region in regions.iter() {
+            if let Some(level) = region.lakes.get(&node) {
+                if terrain_height < *level {
+                    add_water(ColumnWater {
+                        bed: terrain_height,
+                        level: *level,
+                    });
+                }
+            }
+            for segment in region.segments.iter() {
+                let (distance, t) = distance_to_segment(xy, segment.from, segment.to);
+                if distance >= self.config.river_half_width {
+                    continue;
+                }
+                let level = segment.level_from + (segment.level_to - segment.level_from) * t;
+                let level = level.min(terrain_height);
+                // The channel is deepest in the middle of the river:
+                let falloff = distance / self.config.river_half_width;
+                let bed = level - 1. - self.config.river_depth * (1. - falloff * falloff);
+                add_water(ColumnWater {
+                    bed: bed.min(terrain_height),
+                    level,
+                });
+            }
+        }
+        water
+    }
+
+    /// Returns the traced regions whose rivers could reach any of the given columns.
+    fn regions_near(
+        &self,
+        columns: &[ColumnId],
+        height: &impl Fn(&ColumnId) -> f64,
+    ) -> Vec<Arc<RegionHydrology>> {
+        let region_size = self.config.region_size.max(1) as i32;
+        let reach = self.config.reach() as i32 / region_size + 1;
+        let centers = columns
+            .iter()
+            .map(|column| self.region_of(&self.nearest_node(column)))
+            .collect::<HashSet<ColumnId>>();
+        // Sorted, so the regions are always visited in the same order:
+        let mut region_ids = BTreeSet::new();
+        for center in centers {
+            for q in -reach..=reach {
+                for r in -reach..=reach {
+                    region_ids.insert(ColumnId::new(center.q() + q, center.r() + r));
+                }
+            }
+        }
+        region_ids
+            .iter()
+            .map(|region_id| self.region(region_id, height))
+            .collect()
+    }
+
+    fn region(
+        &self,
+        region_id: &ColumnId,
+        height: &impl Fn(&ColumnId) -> f64,
+    ) -> Arc<RegionHydrology> {
+        self.regions
+            .get_or_generate(region_id, || self.trace_region(region_id, height))
+    }
+
+    fn trace_region(
+        &self,
+        region_id: &ColumnId,
+        height: &impl Fn(&ColumnId) -> f64,
+    ) -> RegionHydrology {
+        let size = self.config.region_size.max(1) as i32;
+        let mut heights = HashMap::new();
+        let mut node_height = |node: &ColumnId| -> f64 {
+            *heights
+                .entry(*node)
+                .or_insert_with(|| height(&self.node_column(node)))
+        };
+        let mut region = RegionHydrology::default();
+        for q in 0..size {
+            for r in 0..size {
+                let source = ColumnId::new(region_id.q() * size + q, region_id.r() * size + r);
+                if node_height(&source) >= self.config.source_min_height
+                    && chance(self.seed, SOURCE_SALT, &[source.q(), source.r()])
+                        < self.config.source_chance
+                {
+                    self.trace_river(&source, &mut node_height, &mut region);
+                }
+            }
+        }
+        region
+    }
+
+    /// Follows the steepest way down from the source, until the river reaches the sea or dries up.
+    fn trace_river(
+        &self,
+        source: &ColumnId,
+        node_height: &mut impl FnMut(&ColumnId) -> f64,
+        region: &mut RegionHydrology,
+    ) {
+        let mut current = *source;
+        let mut level = node_height(&current);
+        let mut length = 0;
+        while length < self.config.max_river_length {
+            if level <= self.config.sea_level {
+                return;
+            }
+            let lowest = (0..6)
+                .map(|i| current.neighbour(i))
+                .map(|node| (node, node_height(&node)))
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .expect("There are always 6 neighbours.");
+            let next = if lowest.1 < level {
+                lowest
+            } else if let Some((lake, outlet, lake_level)) = self.flood(&current, node_height) {
+                // The river ended up in a basin; it fills up into a lake and flows on from
+                // the lowest point on its shore:
+                for node in lake {
+                    region.lakes.insert(node, lake_level);
+                }
+                level = lake_level;
+                outlet
+            } else {
+                return;
+            };
+            length += ColumnId::distance(&current, &next.0) as u32;
+            if length > self.config.max_river_length {
+                return;
+            }
+            region.segments.push(Segment {
+                from: column_xy(&self.node_column(&current)),
+                to: column_xy(&self.node_column(&next.0)),
+                level_from: level,
+                level_to: next.1.min(level),
+            });
+            current = next.0;
+            level = next.1.min(level);
+        }
+    }
+
+    /// Fills the basin around the pit, lowest nodes first, until the water spills over.
+    /// Returns the flooded nodes, the node just beyond the lowest point on the shore and the level
+    /// of the lake. Returns None if the basin is too big.
+    #[allow(clippy::type_complexity)]
+    fn flood(
+        &self,
+        pit: &ColumnId,
+        node_height: &mut impl FnMut(&ColumnId) -> f64,
+    ) -> Option<(Vec<ColumnId>, (ColumnId, f64), f64)> {
+        // Heights are compared as whole millimeters, so they can be ordered in the heap:
+        let key = |height: f64| Reverse((height * 1000.) as i64);
+        let mut visited = HashSet::from([*pit]);
+        let mut queue = BinaryHeap::from([(key(node_height(pit)), *pit)]);
+        let mut flooded = vec![];
+        let mut level = f64::MIN;
+        while let Some((_, node)) = queue.pop() {
+            let height = node_height(&node);
+            if height < level {
+                // This node is lower than the water, so the water spills over into it:
+                flooded.retain(|flooded_node| node_height(flooded_node) < level);
+                return Some((flooded, (node, height), level));
+            }
+            level = level.max(height);
+            flooded.push(node);
+            if flooded.len() > self.config.max_lake_nodes as usize {
+                return None;
+            }
+            for i in 0..6 {
+                let neighbour = node.neighbour(i);
+                if visited.insert(neighbour) {
+                    queue.push((key(node_height(&neighbour)), neighbour));
+                }
+            }
+        }
+        None
+    }
+
+    fn node_column(&self, node: &ColumnId) -> ColumnId {
+        let spacing = self.config.node_spacing.max(1) as i32;
+        ColumnId::new(node.q() * spacing, node.r() * spacing)
+    }
+    fn nearest_node(&self, column: &ColumnId) -> ColumnId {
+        let spacing = self.config.node_spacing.max(1) as f32;
+        Pos::new(column.q() as f32 / spacing, column.r() as f32 / spacing, 0.)
+            .as_ipos_round()
+            .as_column_id()
+    }
+    fn region_of(&self, node: &ColumnId) -> ColumnId {
+        let size = self.config.region_size.max(1) as i32;
+        ColumnId::new(node.q().div_euclid(size), node.r().div_euclid(size))
+    }
+}
+
+fn column_xy(column: &ColumnId) -> DVec2 {
+    column.as_ipos(0).as_xyz().xy().as_dvec2()
+}
+
+/// Returns the distance from the point to the segment, and how far along the segment
+/// (from 0 to 1) the closest point lies.
+fn distance_to_segment(point: DVec2, from: DVec2, to: DVec2) -> (f64, f64) {
+    let along = to - from;
+    let length_squared = along.length_squared();
+    let t = if length_squared == 0. {
+        0.
+    } else {
+        ((point - from).dot(along) / length_squared).clamp(0., 1.)
+    };
+    ((from + along * t).distance(point), t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::hex_grid::axial::ChunkId;
+    use crate::game::hex_grid::chunk::ChunkDimensions;
+
+    const DIMENSIONS: ChunkDimensions = ChunkDimensions {
+        radius: 6,
+        height: 16,
+    };
+
+    /// Regions of a single node, so that the water in a chunk comes from many different regions.
+    fn hydrology() -> Hydrology {
+        let config = HydrologyConfig {
+            enabled: true,
+            node_spacing: 1,
+            region_size: 1,
+            sea_level: -2.,
+            source_min_height: 0.,
+            source_chance: 0.1,
+            max_river_length: 6,
+            river_half_width: 1.,
+            river_depth: 1.,
+            max_lake_nodes: 8,
+        };
+        Hydrology::new(&config, 42)
+    }
+
+    /// A gentle slope for rivers to run down, with small dents for lakes to form in.
+    fn height(column: &ColumnId) -> f64 {
+        let dent = (column.q().div_euclid(2) * 7 + column.r().div_euclid(2) * 13).rem_euclid(5);
+        let depth = if dent == 0 { 3. } else { 0. };
+        8. + 0.2 * column.q() as f64 + (column.r() as f64 * 0.5).sin() - depth
+    }
+
+    fn chunk_columns(chunk_id: &ChunkId) -> Vec<ColumnId> {
+        let center = chunk_id.center_pos(&DIMENSIONS);
+        DIMENSIONS
+            .columns()
+            .iter()
+            .map(|column| (column.as_ipos(0) + center).as_column_id())
+            .collect()
+    }
+
+    /// Looks for water chunk by chunk, like the terrain generator does.
+    fn water_per_chunk(
+        hydrology: &Hydrology,
+        ids: impl Iterator<Item = ChunkId>,
+    ) -> HashMap<ColumnId, Option<ColumnWater>> {
+        let mut water = HashMap::new();
+        for id in ids {
+            let columns = chunk_columns(&id);
+            let chunk_water = hydrology.water(&columns, &height);
+            water.extend(columns.into_iter().zip(chunk_water));
+        }
+        water
+    }
+
+    #[test]
+    fn water_does_not_depend_on_the_generation_order() {
+        let ids = ChunkId::splat(0).range(1);
+        let forwards = water_per_chunk(&hydrology(), ids.iter().copied());
+        let backwards = water_per_chunk(&hydrology(), ids.iter().rev().copied());
+        assert!(forwards.values().any(|water| water.is_some()));
+        assert_eq!(forwards, backwards);
+    }
+
+    #[test]
+    fn water_is_continuous_across_chunk_borders() {
+        let hydrology = hydrology();
+        let per_chunk = water_per_chunk(&hydrology, ChunkId::splat(0).range(1).into_iter());
+        let mut rivers = 0;
+        let mut lakes = 0;
+        // Each column must get the same water, whichever chunk it is generated with:
+        for (column, water) in per_chunk.iter() {
+            assert_eq!(
+                hydrology.water(&[*column], &height)[0],
+                *water,
+                "Column {:?}",
+                column
+            );
+            match water {
+                Some(water) if water.bed < height(column) => rivers += 1,
+                Some(water) if water.level > hydrology.config.sea_level => lakes += 1,
+                _ => {}
+            }
+        }
+        assert!(
+            rivers > 0 && lakes > 0,
+            "{} rivers, {} lakes",
+            rivers,
+            lakes
+        );
+    }
+}
@@ -1 +1,12 @@
-pub mod noise_generation;
+pub mod caves;
+pub mod erosion;
+pub mod features;
+pub mod heightmap;
+pub mod hydrology;
+pub mod noise_graph;
+pub mod ores;
+pub mod presets;
+pub mod region_cache;
+pub mod seeding;
+pub mod terrain;
+pub mod world_generator;
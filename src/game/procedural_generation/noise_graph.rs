@@ -0,0 +1,271 @@
+use bevy::math::DVec2;
+use noise::{NoiseFn, OpenSimplex};
+use serde::{Deserialize, Serialize};
+use splines::{Interpolation, Key, Spline};
+
+/// The name of the graph that determines the terrain height.
+/// Its output is mapped from -1..1 to the bottom and top of a chunk.
+pub const ELEVATION_GRAPH: &str = "elevation";
+/// The name of the graph that determines the humidity, used to pick biomes. Expected to be in 0..1.
+pub const HUMIDITY_GRAPH: &str = "humidity";
+/// The name of the graph that determines the temperature, used to pick biomes. Expected to be in 0..1.
+pub const TEMPERATURE_GRAPH: &str = "temperature";
+
+/// A node in a noise graph, as defined in the worldgen config.
+/// Every node produces a single value for each position in the world (in xy-space).
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub enum NoiseNode {
+    /// The same value everywhere.
+    Constant(f64),
+    /// Fractal OpenSimplex noise, roughly in -1..1.
+    Simplex(FractalSettings),
+    /// Ridged multifractal noise: sharp ridges, good for mountain ranges. Roughly in -1..1.
+    RidgedMulti(FractalSettings),
+    /// Billowy noise: rounded lumps, good for hills and clouds. Roughly in -1..1.
+    Billow(FractalSettings),
+    /// Offsets the position at which the source is sampled by the outputs of two other nodes.
+    DomainWarp {
+        source: Box<NoiseNode>,
+        warp_x: Box<NoiseNode>,
+        warp_y: Box<NoiseNode>,
+        /// The distance (in meters) that an output of 1 moves the sample position.
+        strength: f64,
+    },
+    /// Maps the source from one range to another, bending it with an exponent on the way.
+    /// Values outside of the `from` range are clamped.
+    Curve {
+        source: Box<NoiseNode>,
+        from: (f64, f64),
+        to: (f64, f64),
+        /// 1 is linear, greater values push the output towards the start of the `to` range.
+        exponent: f64,
+    },
+    /// Maps the source through a smooth curve going through the given (input, output) points.
+    /// Inputs beyond the first or last point are clamped to it.
+    Spline {
+        source: Box<NoiseNode>,
+        points: Vec<(f64, f64)>,
+    },
+    /// The sum of all inputs.
+    Add(Vec<NoiseNode>),
+    /// The product of all inputs.
+    Multiply(Vec<NoiseNode>),
+    /// Picks `low` where the control is below the threshold and `high` where it is above.
+    /// Within `falloff` of the threshold, the two are blended smoothly.
+    Select {
+        control: Box<NoiseNode>,
+        low: Box<NoiseNode>,
+        high: Box<NoiseNode>,
+        threshold: f64,
+        #[serde(default)]
+        falloff: f64,
+    },
+    Clamp {
+        source: Box<NoiseNode>,
+        min: f64,
+        max: f64,
+    },
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct FractalSettings {
+    /// Added to the world seed, so that different nodes don't produce the same pattern.
+    #[serde(default)]
+    pub seed_offset: u32,
+    /// The size (in meters) of the largest features.
+    pub scale: f64,
+    pub octaves: u32,
+    /// How much the amplitude shrinks with each octave.
+    pub persistence: f64,
+    /// How much the frequency grows with each octave.
+    pub lacunarity: f64,
+}
+
+#[derive(Copy, Clone)]
+enum FractalShape {
+    Simplex,
+    RidgedMulti,
+    Billow,
+}
+
+/// A noise graph that is ready to be sampled.
+pub struct NoiseGraph {
+    sample: Box<dyn Fn(DVec2) -> f64 + Send + Sync>,
+}
+
+impl Default for NoiseGraph {
+    fn default() -> Self {
+        Self::new(&NoiseNode::Constant(0.), 0)
+    }
+}
+
+impl NoiseGraph {
+    pub fn new(node: &NoiseNode, seed: u32) -> Self {
+        Self {
+            sample: compile(node, seed),
+        }
+    }
+    /// Samples the graph at an absolute position in the world (in xy-space).
+    #[must_use]
+    pub fn get(&self, position: DVec2) -> f64 {
+        (self.sample)(position)
+    }
+    /// Samples the graph at each of the given positions.
+    #[must_use]
+    pub fn get_all(&self, positions: &[DVec2]) -> Vec<f64> {
+        positions
+            .iter()
+            .map(|position| self.get(*position))
+            .collect()
+    }
+}
+
+fn compile(node: &NoiseNode, seed: u32) -> Box<dyn Fn(DVec2) -> f64 + Send + Sync> {
+    match node {
+        NoiseNode::Constant(value) => {
+            let value = *value;
+            Box::new(move |_| value)
+        }
+        NoiseNode::Simplex(settings) => fractal(settings, seed, FractalShape::Simplex),
+        NoiseNode::RidgedMulti(settings) => fractal(settings, seed, FractalShape::RidgedMulti),
+        NoiseNode::Billow(settings) => fractal(settings, seed, FractalShape::Billow),
+        NoiseNode::DomainWarp {
+            source,
+            warp_x,
+            warp_y,
+            strength,
+        } => {
+            let (source, warp_x, warp_y) = (
+                compile(source, seed),
+                compile(warp_x, seed),
+                compile(warp_y, seed),
+            );
+            let strength = *strength;
+            Box::new(move |pos| {
+                let offset = DVec2::new(warp_x(pos), warp_y(pos)) * strength;
+                source(pos + offset)
+            })
+        }
+        NoiseNode::Curve {
+            source,
+            from,
+            to,
+            exponent,
+        } => {
+            let source = compile(source, seed);
+            let (from, to, exponent) = (*from, *to, *exponent);
+            Box::new(move |pos| {
+                let t = ((source(pos) - from.0) / (from.1 - from.0)).clamp(0., 1.);
+                to.0 + t.powf(exponent) * (to.1 - to.0)
+            })
+        }
+        NoiseNode::Spline { source, points } => {
+            let source = compile(source, seed);
+            let mut points = points.clone();
+            points.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let (first, last) = (points.first().copied(), points.last().copied());
+            let spline = Spline::from_vec(
+                points
+                    .iter()
+                    .map(|(input, output)| Key::new(*input, *output, Interpolation::Cosine))
+                    .collect(),
+            );
+            Box::new(move |pos| {
+                let input = source(pos);
+                match (first, last) {
+                    (Some(first), _) if input <= first.0 => first.1,
+                    (_, Some(last)) if input >= last.0 => last.1,
+                    _ => spline.clamped_sample(input).unwrap_or(input),
+                }
+            })
+        }
+        NoiseNode::Add(inputs) => {
+            let inputs = inputs
+                .iter()
+                .map(|input| compile(input, seed))
+                .collect::<Vec<_>>();
+            Box::new(move |pos| inputs.iter().map(|input| input(pos)).sum())
+        }
+        NoiseNode::Multiply(inputs) => {
+            let inputs = inputs
+                .iter()
+                .map(|input| compile(input, seed))
+                .collect::<Vec<_>>();
+            Box::new(move |pos| inputs.iter().map(|input| input(pos)).product())
+        }
+        NoiseNode::Select {
+            control,
+            low,
+            high,
+            threshold,
+            falloff,
+        } => {
+            let (control, low, high) = (
+                compile(control, seed),
+                compile(low, seed),
+                compile(high, seed),
+            );
+            let (threshold, falloff) = (*threshold, falloff.max(0.));
+            Box::new(move |pos| {
+                let control = control(pos);
+                if control <= threshold - falloff {
+                    low(pos)
+                } else if control >= threshold + falloff {
+                    high(pos)
+                } else {
+                    let t = (control - (threshold - falloff)) / (2. * falloff);
+                    let t = t * t * (3. - 2. * t);
+                    low(pos) * (1. - t) + high(pos) * t
+                }
+            })
+        }
+        NoiseNode::Clamp { source, min, max } => {
+            let source = compile(source, seed);
+            let (min, max) = (*min, *max);
+            Box::new(move |pos| source(pos).clamp(min, max))
+        }
+    }
+}
+
+/// Each octave gets its own OpenSimplex instance, so the octaves don't line up with each other.
+/// The result is divided by the total amplitude, to keep it roughly in -1..1.
+fn fractal(
+    settings: &FractalSettings,
+    seed: u32,
+    shape: FractalShape,
+) -> Box<dyn Fn(DVec2) -> f64 + Send + Sync> {
+    let seed = seed.wrapping_add(settings.seed_offset);
+    let octaves = (0..settings.octaves.max(1))
+        .map(|octave| OpenSimplex::new(seed.wrapping_add(octave)))
+        .collect::<Vec<OpenSimplex>>();
+    let scale = settings.scale.max(0.0001);
+    let (persistence, lacunarity) = (settings.persistence, settings.lacunarity);
+    Box::new(move |pos| {
+        let mut amplitude = 1.;
+        let mut frequency = 1.;
+        let mut total_amplitude = 0.;
+        let mut value = 0.;
+        // Ridges in later octaves are weighted by the ridges of the previous ones,
+        // so that detail accumulates along the ridges instead of everywhere:
+        let mut weight = 1.;
+        for simplex in octaves.iter() {
+            let sample = pos / scale * frequency;
+            let noise = simplex.get([sample.x, sample.y]);
+            value += amplitude
+                * match shape {
+                    FractalShape::Simplex => noise,
+                    FractalShape::Billow => noise.abs() * 2. - 1.,
+                    FractalShape::RidgedMulti => {
+                        let ridge = (1. - noise.abs()).powi(2) * weight;
+                        weight = ridge.clamp(0., 1.);
+                        ridge * 2. - 1.
+                    }
+                };
+            total_amplitude += amplitude;
+            amplitude *= persistence;
+            frequency *= lacunarity;
+        }
+        value / total_amplitude
+    })
+}
@@ -0,0 +1,222 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::game::hex_grid::axial::{ChunkId, IPos};
+use crate::game::hex_grid::block::{Block, BlockType};
+use crate::game::hex_grid::chunk::{Chunk, ChunkDimensions};
+use crate::game::procedural_generation::seeding::seeded_rng;
+
+/// Added to the index of the ore, so that each ore gets its own random sequence.
+const ORE_SALT: u32 = 100;
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct OreConfig {
+    pub ore: BlockType,
+    /// Veins only start at absolute heights (in blocks) within this range, inclusive.
+    pub height_range: (i32, i32),
+    /// The minimum and maximum number of blocks in a single vein.
+    /// Veins are never bigger than the chunk radius or height, so that they reach at most one
+    /// chunk further, sideways or up and down.
+    pub vein_size: (u32, u32),
+    /// The average number of veins that start in each chunk.
+    pub veins_per_chunk: f32,
+    /// The ore only replaces these blocks. Anything else stays as it is.
+    pub host_blocks: Vec<BlockType>,
+}
+
+/// Places ore veins in freshly generated chunks.
+///
+/// Veins are seeded per chunk, but may reach into neighbouring chunks. When a chunk is generated,
+/// the veins of its neighbours are generated again and the parts that fall inside this chunk are
+/// kept. Because veins only depend on the seed and the id of the chunk they start in,
+/// the result is the same no matter in which order the chunks are generated.
+#[derive(Default)]
+pub struct OreGenerator {
+    ores: Vec<OreConfig>,
+    seed: u32,
+}
+
+impl OreGenerator {
+    pub fn new(ores: &[OreConfig], seed: u32) -> Self {
+        Self {
+            ores: ores.to_vec(),
+            seed,
+        }
+    }
+
+    /// Replaces host blocks in the chunk with ore, for every vein that reaches into it.
+    /// Ores are placed in the order they are configured in, so later ores can replace earlier
+    /// ones if they list them as host blocks.
+    pub fn populate(&self, chunk: &mut Chunk, chunk_id: &ChunkId) {
+        let dimensions = *chunk.dimensions();
+        let center = chunk_id.center_pos(&dimensions);
+        let sources = chunk_id
+            .range(1)
+            .iter()
+            .flat_map(|id| (-1..=1).map(move |delta_z| id.delta(0, 0, delta_z)))
+            .collect::<Vec<ChunkId>>();
+        for (index, ore) in self.ores.iter().enumerate() {
+            for source in sources.iter() {
+                for vein in self.veins(index, source, &dimensions) {
+                    for pos in vein {
+                        let relative = pos - center;
+                        if let Ok(block) = chunk.try_block(&relative) {
+                            if ore.host_blocks.contains(&block.block_type) {
+                                let block = Block {
+                                    block_type: ore.ore,
+                                    // Ore takes the place of the host block, shape and all:
+                                    ..*block
+                                };
+                                chunk.set(&relative, block);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the veins of the ore at the given index that start in the given chunk.
+    /// Each vein is a list of absolute block positions.
+    #[must_use]
+    pub fn veins(
+        &self,
+        ore_index: usize,
+        chunk_id: &ChunkId,
+        dimensions: &ChunkDimensions,
+    ) -> Vec<Vec<IPos>> {
+        let ore = &self.ores[ore_index];
+        let mut rng = seeded_rng(
+            self.seed,
+            ORE_SALT + ore_index as u32,
+            &[chunk_id.q(), chunk_id.r(), chunk_id.z()],
+        );
+        let center = chunk_id.center_pos(dimensions);
+        let columns = dimensions.columns();
+        // Chunks only look one chunk sideways, up and down for veins that reach into them:
+        let max_size = ore
+            .vein_size
+            .1
+            .min(dimensions.radius.min(dimensions.height))
+            .max(1);
+        let min_size = ore.vein_size.0.clamp(1, max_size);
+        // The fractional part of veins_per_chunk is the chance of one extra vein:
+        let mut count = ore.veins_per_chunk.max(0.).floor() as u32;
+        if rng.gen::<f32>() < ore.veins_per_chunk.fract() {
+            count += 1;
+        }
+        let mut veins = vec![];
+        for _ in 0..count {
+            // Always draw the same numbers, even if the vein is skipped,
+            // so that the other veins in this chunk don't depend on it:
+            let column = columns[rng.gen_range(0..columns.len())];
+            let start = column.as_ipos(rng.gen_range(0..dimensions.height as i32)) + center;
+            let size = rng.gen_range(min_size..=max_size);
+            let vein_seed = rng.gen::<u64>();
+            if start.z() < ore.height_range.0 || start.z() > ore.height_range.1 {
+                continue;
+            }
+            veins.push(random_walk(start, size, vein_seed));
+        }
+        veins
+    }
+}
+
+/// A blob of connected blocks, grown by wandering from the start in random directions.
+fn random_walk(start: IPos, size: u32, vein_seed: u64) -> Vec<IPos> {
+    let mut rng = StdRng::seed_from_u64(vein_seed);
+    let mut vein = vec![start];
+    let mut current = start;
+    while vein.len() < size as usize {
+        let neighbours = current.neighbours_3d();
+        current = neighbours[rng.gen_range(0..neighbours.len())];
+        if !vein.contains(&current) {
+            vein.push(current);
+        }
+    }
+    vein
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    /// Flat chunks, so veins easily reach more than one chunk up or down.
+    const DIMENSIONS: ChunkDimensions = ChunkDimensions {
+        radius: 6,
+        height: 1,
+    };
+
+    fn ore_generator() -> OreGenerator {
+        let gold = OreConfig {
+            ore: BlockType::Gold,
+            height_range: (-100, 100),
+            vein_size: (6, 6),
+            veins_per_chunk: 20.,
+            host_blocks: vec![BlockType::Stone],
+        };
+        OreGenerator::new(&[gold], 7)
+    }
+
+    /// The chunks around the origin, going up and down as far as veins could possibly reach.
+    fn chunk_ids() -> Vec<ChunkId> {
+        ChunkId::splat(0)
+            .range(1)
+            .into_iter()
+            .flat_map(|id| (-5..=5).map(move |delta_z| id.delta(0, 0, delta_z)))
+            .collect()
+    }
+
+    /// Populates stone chunks with ore, in the given order. Returns the ore blocks.
+    fn populate(ids: impl Iterator<Item = ChunkId>) -> HashMap<IPos, BlockType> {
+        let generator = ore_generator();
+        let stone = Block {
+            block_type: BlockType::Stone,
+            ..Default::default()
+        };
+        let mut ores = HashMap::new();
+        for id in ids {
+            let mut chunk = Chunk::new(DIMENSIONS);
+            for column in DIMENSIONS.columns() {
+                for z in 0..DIMENSIONS.height as i32 {
+                    chunk.set(&column.as_ipos(z), stone);
+                }
+            }
+            generator.populate(&mut chunk, &id);
+            let center = id.center_pos(&DIMENSIONS);
+            for column in DIMENSIONS.columns() {
+                for z in 0..DIMENSIONS.height as i32 {
+                    let block = chunk.block(&column.as_ipos(z));
+                    if block.block_type != BlockType::Stone {
+                        ores.insert(column.as_ipos(z) + center, block.block_type);
+                    }
+                }
+            }
+        }
+        ores
+    }
+
+    #[test]
+    fn veins_do_not_depend_on_the_generation_order() {
+        let ids = chunk_ids();
+        let forwards = populate(ids.iter().copied());
+        let backwards = populate(ids.iter().rev().copied());
+        assert!(!forwards.is_empty());
+        assert_eq!(forwards, backwards);
+    }
+
+    #[test]
+    fn veins_are_never_cut_off_at_chunk_borders() {
+        let ores = populate(chunk_ids().into_iter());
+        let generator = ore_generator();
+        for vein in generator.veins(0, &ChunkId::splat(0), &DIMENSIONS) {
+            for pos in vein {
+                assert_eq!(ores.get(&pos), Some(&BlockType::Gold), "Block {:?}", pos);
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+
+use crate::game::hex_grid::axial::{ChunkId, ColumnId, IPos};
+use crate::game::hex_grid::biomes::BiomeType;
+use crate::game::hex_grid::block::{Block, BlockShape, BlockType};
+use crate::game::hex_grid::chunk::{Chunk, ChunkDimensions};
+use crate::game::procedural_generation::world_generator::{fill_chunk, WorldGenerator};
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct FlatLayer {
+    pub block_type: BlockType,
+    /// The number of blocks in this layer.
+    pub thickness: u32,
+}
+
+/// Generates the same stack of layers in every column.
+pub struct FlatGenerator {
+    /// The block type at each height, starting at z=0.
+    column: Vec<BlockType>,
+    biome: BiomeType,
+}
+
+impl FlatGenerator {
+    pub fn new(layers: &[FlatLayer], biome: BiomeType) -> Self {
+        Self {
+            column: layers
+                .iter()
+                .flat_map(|layer| vec![layer.block_type; layer.thickness as usize])
+                .collect(),
+            biome,
+        }
+    }
+}
+
+impl WorldGenerator for FlatGenerator {
+    fn generate_chunk(&self, chunk_id: &ChunkId, dimensions: ChunkDimensions) -> Chunk {
+        fill_chunk(chunk_id, dimensions, |pos| Block {
+            block_type: usize::try_from(pos.z())
+                .ok()
+                .and_then(|z| self.column.get(z))
+                .copied()
+                .unwrap_or(BlockType::Air),
+            biome_type: self.biome,
+            shape: BlockShape::Full,
+        })
+    }
+}
+
+/// Generates nothing but a small platform around the origin.
+pub struct VoidGenerator {
+    platform_radius: u32,
+    platform_z: i32,
+    platform_block: BlockType,
+}
+
+impl VoidGenerator {
+    pub fn new(platform_radius: u32, platform_z: i32, platform_block: BlockType) -> Self {
+        Self {
+            platform_radius,
+            platform_z,
+            platform_block,
+        }
+    }
+}
+
+impl WorldGenerator for VoidGenerator {
+    fn generate_chunk(&self, chunk_id: &ChunkId, dimensions: ChunkDimensions) -> Chunk {
+        let platform_center = IPos::new(0, 0, self.platform_z);
+        fill_chunk(chunk_id, dimensions, |pos| {
+            let on_platform = pos.z() == self.platform_z
+                && IPos::column_distance(pos, &platform_center) <= self.platform_radius as i32;
+            Block {
+                block_type: if on_platform {
+                    self.platform_block
+                } else {
+                    BlockType::Air
+                },
+                ..Default::default()
+            }
+        })
+    }
+}
+
+/// Generates a stone floor at z=0, with one of every block type on display on top of it.
+pub struct DebugGridGenerator {
+    /// Maps the columns of the grid to the block type that is displayed there.
+    grid: Vec<(ColumnId, BlockType)>,
+}
+
+impl DebugGridGenerator {
+    pub fn new(spacing: u32) -> Self {
+        let spacing = spacing.max(1) as i32;
+        let block_types = BlockType::all()
+            .into_iter()
+            .filter(|block_type| *block_type != BlockType::Air)
+            .collect::<Vec<BlockType>>();
+        let row_length = (block_types.len() as f32).sqrt().ceil() as i32;
+        let grid = block_types
+            .into_iter()
+            .enumerate()
+            .map(|(index, block_type)| {
+                let (q, r) = (index as i32 % row_length, index as i32 / row_length);
+                (ColumnId::new(q * spacing, r * spacing), block_type)
+            })
+            .collect();
+        Self { grid }
+    }
+}
+
+impl WorldGenerator for DebugGridGenerator {
+    fn generate_chunk(&self, chunk_id: &ChunkId, dimensions: ChunkDimensions) -> Chunk {
+        fill_chunk(chunk_id, dimensions, |pos| {
+            let block_type = match pos.z() {
+                0 => BlockType::Stone,
+                1 => self
+                    .grid
+                    .iter()
+                    .find(|(column, _)| *column == pos.as_column_id())
+                    .map(|(_, block_type)| *block_type)
+                    .unwrap_or(BlockType::Air),
+                _ => BlockType::Air,
+            };
+            Block {
+                block_type,
+                ..Default::default()
+            }
+        })
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+
+/// The number of regions a cache holds if no capacity is given.
+const DEFAULT_CAPACITY: usize = 256;
+
+/// Caches the results of generation stages that work on whole regions at a time.
+/// When the cache is full, the region that was used the longest time ago is evicted,
+/// so the regions around the chunks that are being generated stay cached.
+pub struct RegionCache<K, V> {
+    capacity: usize,
+    entries: Mutex<Entries<K, V>>,
+}
+
+struct Entries<K, V> {
+    /// The cached regions, along with the time they were last used.
+    regions: HashMap<K, (Arc<V>, u64)>,
+    /// Counts up every time a region is used.
+    clock: u64,
+}
+
+impl<K, V> Default for RegionCache<K, V> {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl<K, V> RegionCache<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            entries: Mutex::new(Entries {
+                regions: HashMap::new(),
+                clock: 0,
+            }),
+        }
+    }
+}
+
+impl<K: Copy + Eq + Hash, V> RegionCache<K, V> {
+    /// Returns the cached region, or generates it and adds it to the cache.
+    /// Generation happens without holding the lock. If two threads generate the same region at
+    /// once, they come up with the same result, so it doesn't matter which one ends up cached.
+    pub fn get_or_generate(&self, key: &K, generate: impl FnOnce() -> V) -> Arc<V> {
+        if let Some(region) = self.entries.lock().unwrap().touch(key) {
+            return region;
+        }
+        let region = Arc::new(generate());
+        let mut entries = self.entries.lock().unwrap();
+        if entries.regions.len() >= self.capacity {
+            entries.evict_least_recently_used();
+        }
+        entries.clock += 1;
+        let clock = entries.clock;
+        entries.regions.insert(*key, (region.clone(), clock));
+        region
+    }
+}
+
+impl<K: Copy + Eq + Hash, V> Entries<K, V> {
+    fn touch(&mut self, key: &K) -> Option<Arc<V>> {
+        self.clock += 1;
+        let clock = self.clock;
+        self.regions.get_mut(key).map(|(region, last_used)| {
+            *last_used = clock;
+            region.clone()
+        })
+    }
+    fn evict_least_recently_used(&mut self) {
+        let oldest = self
+            .regions
+            .iter()
+            .min_by_key(|(_, (_, last_used))| *last_used)
+            .map(|(key, _)| *key);
+        if let Some(key) = oldest {
+            self.regions.remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_the_least_recently_used_region() {
+        let cache = RegionCache::<i32, i32>::new(2);
+        cache.get_or_generate(&1, || 10);
+        cache.get_or_generate(&2, || 20);
+        // Using region 1 again makes region 2 the oldest:
+        cache.get_or_generate(&1, || panic!("Region 1 should be cached"));
+        cache.get_or_generate(&3, || 30);
+        assert_eq!(*cache.get_or_generate(&1, || 0), 10);
+        assert_eq!(*cache.get_or_generate(&2, || 0), 0);
+    }
+}
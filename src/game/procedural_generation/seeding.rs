@@ -0,0 +1,36 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// Mixes the world seed, a salt and some coordinates into a single well-distributed number.
+/// The salt keeps different generation stages from making the same decisions in the same places.
+#[must_use]
+pub fn hash(seed: u32, salt: u32, coordinates: &[i32]) -> u64 {
+    let mut hash = mix((seed as u64) << 32 | salt as u64);
+    for coordinate in coordinates {
+        hash = mix(hash ^ *coordinate as u32 as u64);
+    }
+    hash
+}
+
+/// A random number in 0..1 that is always the same for the same inputs.
+#[must_use]
+pub fn chance(seed: u32, salt: u32, coordinates: &[i32]) -> f64 {
+    (hash(seed, salt, coordinates) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// A random number generator that always produces the same sequence for the same inputs.
+#[must_use]
+pub fn seeded_rng(seed: u32, salt: u32, coordinates: &[i32]) -> StdRng {
+    StdRng::seed_from_u64(hash(seed, salt, coordinates))
+}
+
+/// The SplitMix64 finalizer.
+fn mix(value: u64) -> u64 {
+    let mut value = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    value ^ (value >> 31)
+}
+
+// References
+// 1. https://prng.di.unimi.it/splitmix64.c
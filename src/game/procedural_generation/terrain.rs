@@ -0,0 +1,82 @@
+use bevy::math::Vec3Swizzles;
+
+use crate::game::hex_grid::axial::{ChunkId, ColumnId, IPos};
+use crate::game::hex_grid::block::Block;
+use crate::game::hex_grid::chunk::{Chunk, ChunkDimensions};
+use crate::game::hex_grid::chunks::map_value;
+use crate::game::hex_grid::templates::StructureLibrary;
+use crate::game::procedural_generation::caves::CaveNoise;
+use crate::game::procedural_generation::erosion::Erosion;
+use crate::game::procedural_generation::features::FeaturePlacer;
+use crate::game::procedural_generation::hydrology::Hydrology;
+use crate::game::procedural_generation::noise_graph::{
+    NoiseGraph, ELEVATION_GRAPH, HUMIDITY_GRAPH, TEMPERATURE_GRAPH,
+};
+use crate::game::procedural_generation::ores::OreGenerator;
+use crate::game::procedural_generation::world_generator::WorldGenerator;
+use crate::io::config::WorldGenConfig;
+
+/// Everything that the terrain generator needs, ready to be sampled.
+/// It is built once from the worldgen config, and then used to generate every chunk in the world.
+pub struct TerrainGenerator {
+    pub elevation: NoiseGraph,
+    pub humidity: NoiseGraph,
+    pub temperature: NoiseGraph,
+    pub caves: CaveNoise,
+    pub ores: OreGenerator,
+    pub features: FeaturePlacer,
+    pub hydrology: Hydrology,
+    pub erosion: Erosion,
+    /// Height differences in the terrain are multiplied by this. 1 leaves the terrain as it is.
+    pub amplification: f64,
+}
+
+impl TerrainGenerator {
+    pub fn from_config(config: &WorldGenConfig, structures: &StructureLibrary) -> Self {
+        Self {
+            elevation: config.graph(ELEVATION_GRAPH),
+            humidity: config.graph(HUMIDITY_GRAPH),
+            temperature: config.graph(TEMPERATURE_GRAPH),
+            caves: CaveNoise::new(&config.caves, config.seed),
+            ores: OreGenerator::new(&config.ores, config.seed),
+            features: FeaturePlacer::new(&config.features, structures, config.seed),
+            hydrology: Hydrology::new(&config.hydrology, config.seed),
+            erosion: Erosion::new(&config.erosion, config.seed, config.hydrology.trace_reach()),
+            amplification: 1.,
+        }
+    }
+
+    /// The height (in blocks) of the terrain surface in the given column, after erosion.
+    #[must_use]
+    pub fn terrain_height(&self, column: &ColumnId, dimensions: &ChunkDimensions) -> f64 {
+        self.erosion
+            .eroded_height(column, &|column| self.raw_height(column, dimensions))
+    }
+    /// The height (in blocks) of the terrain surface in the given column, according to the
+    /// elevation graph alone. The graph's output is amplified, then mapped from -1..1 to the
+    /// bottom and top of the first layer of chunks.
+    #[must_use]
+    pub fn raw_height(&self, column: &ColumnId, dimensions: &ChunkDimensions) -> f64 {
+        let elevation = self
+            .elevation
+            .get(column.as_ipos(0).as_xyz().xy().as_dvec2());
+        map_value(
+            elevation * self.amplification,
+            -1.0,
+            1.0,
+            0.0,
+            dimensions.height as f64,
+        )
+    }
+}
+
+impl WorldGenerator for TerrainGenerator {
+    fn generate_chunk(&self, chunk_id: &ChunkId, dimensions: ChunkDimensions) -> Chunk {
+        Chunk::from_noise(chunk_id, dimensions, self)
+    }
+    fn place_features(&self, chunk: &Chunk, chunk_id: &ChunkId) -> Vec<(IPos, Block)> {
+        self.features.place(chunk, chunk_id, &|column| {
+            self.terrain_height(column, chunk.dimensions())
+        })
+    }
+}
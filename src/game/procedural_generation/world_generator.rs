@@ -0,0 +1,114 @@
+use bevy::prelude::error;
+use serde::{Deserialize, Serialize};
+
+use crate::game::hex_grid::axial::{ChunkId, IPos};
+use crate::game::hex_grid::biomes::BiomeType;
+use crate::game::hex_grid::block::{Block, BlockType};
+use crate::game::hex_grid::chunk::{Chunk, ChunkDimensions};
+use crate::game::hex_grid::templates::StructureLibrary;
+use crate::game::procedural_generation::heightmap::{HeightmapGenerator, HeightmapSettings};
+use crate::game::procedural_generation::presets::{
+    DebugGridGenerator, FlatGenerator, FlatLayer, VoidGenerator,
+};
+use crate::game::procedural_generation::terrain::TerrainGenerator;
+use crate::io::asset_loading::ModDirectories;
+use crate::io::config::WorldGenConfig;
+
+/// Generates the chunks of a world. Chunks calls this whenever a chunk needs to be generated.
+pub trait WorldGenerator: Send + Sync {
+    /// Generates the blocks of a single chunk.
+    fn generate_chunk(&self, chunk_id: &ChunkId, dimensions: ChunkDimensions) -> Chunk;
+    /// Returns the blocks of all features that grow out of the freshly generated chunk,
+    /// at absolute positions. They may extend beyond the chunk's borders.
+    fn place_features(&self, _chunk: &Chunk, _chunk_id: &ChunkId) -> Vec<(IPos, Block)> {
+        vec![]
+    }
+}
+
+/// The kind of world to generate. This is chosen when a world is created.
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+pub enum WorldPreset {
+    /// Regular terrain, generated from the noise graphs and the other worldgen settings.
+    #[default]
+    Terrain,
+    /// Regular terrain, but with the height differences exaggerated.
+    /// Use this with taller chunks, or mountains will be cut off at the top.
+    Amplified {
+        /// How much the height differences are multiplied by.
+        amplification: f64,
+    },
+    /// Flat layers of blocks stacked on top of each other, the same everywhere.
+    Flat {
+        /// The layers from the bottom up.
+        layers: Vec<FlatLayer>,
+        biome: BiomeType,
+    },
+    /// Nothing but air, apart from a small platform to spawn on.
+    Void {
+        /// The radius of the platform around the world's origin.
+        platform_radius: u32,
+        /// The height at which the platform floats.
+        platform_z: i32,
+        platform_block: BlockType,
+    },
+    /// A flat floor with one of every block type on display, for testing.
+    DebugGrid {
+        /// The number of columns between the displayed blocks.
+        spacing: u32,
+    },
+    /// Terrain read from a heightmap image, and optionally a biome map, drawn by hand.
+    Heightmap(HeightmapSettings),
+}
+
+impl WorldPreset {
+    pub fn create_generator(
+        &self,
+        config: &WorldGenConfig,
+        structures: &StructureLibrary,
+        mods: &ModDirectories,
+    ) -> Box<dyn WorldGenerator> {
+        match self {
+            WorldPreset::Terrain => Box::new(TerrainGenerator::from_config(config, structures)),
+            WorldPreset::Amplified { amplification } => {
+                let mut generator = TerrainGenerator::from_config(config, structures);
+                generator.amplification = *amplification;
+                Box::new(generator)
+            }
+            WorldPreset::Flat { layers, biome } => Box::new(FlatGenerator::new(layers, *biome)),
+            WorldPreset::Void {
+                platform_radius,
+                platform_z,
+                platform_block,
+            } => Box::new(VoidGenerator::new(
+                *platform_radius,
+                *platform_z,
+                *platform_block,
+            )),
+            WorldPreset::DebugGrid { spacing } => Box::new(DebugGridGenerator::new(*spacing)),
+            WorldPreset::Heightmap(settings) => match HeightmapGenerator::new(settings, mods) {
+                Ok(generator) => Box::new(generator),
+                Err(err) => {
+                    error!("{} Falling back to regular terrain.", err);
+                    Box::new(TerrainGenerator::from_config(config, structures))
+                }
+            },
+        }
+    }
+}
+
+/// Creates a chunk by asking for the block at every absolute position in it.
+pub fn fill_chunk(
+    chunk_id: &ChunkId,
+    dimensions: ChunkDimensions,
+    block_at: impl Fn(&IPos) -> Block,
+) -> Chunk {
+    let mut chunk = Chunk::new(dimensions);
+    let center = chunk_id.center_pos(&dimensions);
+    for column in dimensions.columns() {
+        for z in 0..dimensions.height as i32 {
+            let pos_relative = column.as_ipos(z);
+            chunk.set(&pos_relative, block_at(&(pos_relative + center)));
+        }
+    }
+    chunk
+}
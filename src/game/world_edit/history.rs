@@ -0,0 +1,228 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::Resource;
+
+use crate::game::hex_grid::axial::IPos;
+use crate::game::hex_grid::block::Block;
+use crate::game::hex_grid::chunks::Chunks;
+use crate::game::hex_grid::world_error::WorldError;
+
+/// The oldest edits are forgotten once there are more than this many to undo.
+const MAX_HISTORY: usize = 64;
+
+/// A single block that was changed by an edit.
+#[derive(Debug, Copy, Clone)]
+pub struct BlockChange {
+    pub pos: IPos,
+    pub before: Block,
+    pub after: Block,
+}
+
+/// All blocks changed by a single world-edit operation.
+#[derive(Debug, Clone)]
+pub struct Edit {
+    /// A short description of the operation, for logging.
+    pub name: String,
+    pub changes: Vec<BlockChange>,
+}
+
+/// Keeps track of world edits, so they can be undone and redone.
+///
+/// Edits are stored as the blocks they changed, both before and after. Undoing an edit writes
+/// the old blocks back; this fails without changing anything if any of them are in a chunk that
+/// is no longer loaded.
+#[derive(Resource, Default)]
+pub struct EditHistory {
+    undo: VecDeque<Edit>,
+    redo: Vec<Edit>,
+}
+
+impl EditHistory {
+    /// Sets the blocks, and remembers what they were before so the edit can be undone.
+    /// Blocks that wouldn't change are left out of the history.
+    /// Returns the number of blocks that changed.
+    pub fn apply(
+        &mut self,
+        chunks: &mut Chunks,
+        name: &str,
+        blocks: Vec<(IPos, Block)>,
+    ) -> Result<usize, WorldError> {
+        let mut changes = vec![];
+        for (pos, after) in blocks {
+            let before = *chunks.try_block(&pos)?;
+            if before.block_type != after.block_type
+                || before.biome_type != after.biome_type
+                || before.shape != after.shape
+            {
+                changes.push(BlockChange { pos, before, after });
+            }
+        }
+        chunks.set_blocks(changes.iter().map(|change| (change.pos, change.after)))?;
+        let count = changes.len();
+        if count > 0 {
+            self.undo.push_back(Edit {
+                name: name.to_string(),
+                changes,
+            });
+            if self.undo.len() > MAX_HISTORY {
+                self.undo.pop_front();
+            }
+            self.redo.clear();
+        }
+        Ok(count)
+    }
+    /// Reverts the most recent edit. Returns its name, or None if there was nothing to undo.
+    pub fn undo(&mut self, chunks: &mut Chunks) -> Result<Option<String>, WorldError> {
+        if let Some(edit) = self.undo.pop_back() {
+            let result = chunks.set_blocks(
+                edit.changes
+                    .iter()
+                    .map(|change| (change.pos, change.before)),
+            );
+            let name = edit.name.clone();
+            if let Err(err) = result {
+                self.undo.push_back(edit);
+                return Err(err);
+            }
+            self.redo.push(edit);
+            Ok(Some(name))
+        } else {
+            Ok(None)
+        }
+    }
+    /// Applies the most recently undone edit again. Returns its name, or None if there was nothing
+    /// to redo.
+    pub fn redo(&mut self, chunks: &mut Chunks) -> Result<Option<String>, WorldError> {
+        if let Some(edit) = self.redo.pop() {
+            let result =
+                chunks.set_blocks(edit.changes.iter().map(|change| (change.pos, change.after)));
+            let name = edit.name.clone();
+            if let Err(err) = result {
+                self.redo.push(edit);
+                return Err(err);
+            }
+            self.undo.push_back(edit);
+            Ok(Some(name))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::utils::HashSet;
+
+    use super::*;
+    use crate::game::hex_grid::axial::ChunkId;
+    use crate::game::hex_grid::biomes::BiomeType;
+    use crate::game::hex_grid::block::{BlockShape, BlockType};
+    use crate::game::hex_grid::chunk::ChunkDimensions;
+    use crate::game::procedural_generation::presets::{FlatGenerator, FlatLayer};
+    use crate::game::world_edit::operations::fill;
+    use crate::game::world_edit::selection::Selection;
+
+    const FLOOR: i32 = 2;
+
+    fn test_world() -> Chunks {
+        let dimensions = ChunkDimensions {
+            radius: 3,
+            height: 8,
+        };
+        let layers = [FlatLayer {
+            block_type: BlockType::Stone,
+            thickness: FLOOR as u32,
+        }];
+        let generator = FlatGenerator::new(&layers, BiomeType::Grassland);
+        let mut chunks = Chunks::new(dimensions, Box::new(generator));
+        for chunk_id in ChunkId::splat(0).range(1) {
+            chunks.generate_chunk(chunk_id);
+        }
+        chunks
+    }
+
+    /// A box above the floor that spans several chunks.
+    fn selection() -> Selection {
+        Selection::from_corners(&IPos::new(-2, -1, FLOOR), &IPos::new(3, 2, FLOOR + 2))
+    }
+
+    fn fill_with(
+        history: &mut EditHistory,
+        chunks: &mut Chunks,
+        block_type: BlockType,
+    ) -> Result<usize, WorldError> {
+        let blocks = fill(chunks, &selection(), block_type, BlockShape::Full)?;
+        history.apply(chunks, "Fill", blocks)
+    }
+
+    fn assert_filled_with(chunks: &Chunks, block_type: BlockType) {
+        for pos in selection().positions() {
+            assert_eq!(
+                chunks.block(&pos).unwrap().block_type,
+                block_type,
+                "Block {:?}",
+                pos
+            );
+        }
+    }
+
+    #[test]
+    fn undo_restores_the_blocks_and_redo_applies_them_again() {
+        let mut chunks = test_world();
+        let mut history = EditHistory::default();
+        let count = fill_with(&mut history, &mut chunks, BlockType::Stone).unwrap();
+        assert_eq!(count, selection().positions().len());
+        assert_filled_with(&chunks, BlockType::Stone);
+        assert_eq!(history.undo(&mut chunks).unwrap(), Some("Fill".to_string()));
+        assert_filled_with(&chunks, BlockType::Air);
+        assert_eq!(history.redo(&mut chunks).unwrap(), Some("Fill".to_string()));
+        assert_filled_with(&chunks, BlockType::Stone);
+        assert_eq!(history.redo(&mut chunks).unwrap(), None);
+        // Filling with the same blocks again changes nothing, so there's nothing to remember:
+        assert_eq!(
+            fill_with(&mut history, &mut chunks, BlockType::Stone).unwrap(),
+            0
+        );
+        history.undo(&mut chunks).unwrap();
+        assert_eq!(history.undo(&mut chunks).unwrap(), None);
+    }
+
+    #[test]
+    fn a_new_edit_clears_the_redo_stack() {
+        let mut chunks = test_world();
+        let mut history = EditHistory::default();
+        fill_with(&mut history, &mut chunks, BlockType::Stone).unwrap();
+        history.undo(&mut chunks).unwrap();
+        fill_with(&mut history, &mut chunks, BlockType::Dirt).unwrap();
+        assert_eq!(history.redo(&mut chunks).unwrap(), None);
+        assert_filled_with(&chunks, BlockType::Dirt);
+        history.undo(&mut chunks).unwrap();
+        assert_filled_with(&chunks, BlockType::Air);
+    }
+
+    #[test]
+    fn undo_into_an_unloaded_chunk_changes_nothing() {
+        let mut chunks = test_world();
+        let mut history = EditHistory::default();
+        fill_with(&mut history, &mut chunks, BlockType::Stone).unwrap();
+        let positions = selection().positions();
+        let unloaded = chunks.chunk_id(positions.last().unwrap());
+        assert_ne!(unloaded, chunks.chunk_id(&positions[0]));
+        let allowed = ChunkId::splat(0)
+            .range(1)
+            .into_iter()
+            .filter(|id| *id != unloaded)
+            .collect::<HashSet<ChunkId>>();
+        chunks.cull_chunks(&allowed);
+        assert!(history.undo(&mut chunks).is_err());
+        for pos in positions.iter() {
+            if let Some(block) = chunks.block(pos) {
+                assert_eq!(block.block_type, BlockType::Stone, "Block {:?}", pos);
+            }
+        }
+        // The edit is kept, so it can be undone once the chunk is back:
+        chunks.generate_chunk(unloaded);
+        assert_eq!(history.undo(&mut chunks).unwrap(), Some("Fill".to_string()));
+        assert_filled_with(&chunks, BlockType::Air);
+    }
+}
@@ -0,0 +1,4 @@
+pub mod history;
+pub mod operations;
+pub mod selection;
+pub mod tool;
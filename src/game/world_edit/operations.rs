@@ -0,0 +1,213 @@
+use std::collections::HashSet;
+
+use crate::game::hex_grid::axial::IPos;
+use crate::game::hex_grid::block::{Block, BlockShape, BlockType};
+use crate::game::hex_grid::chunks::Chunks;
+use crate::game::hex_grid::templates::{StructureTemplate, TemplateBlock, TemplatePlacement};
+use crate::game::hex_grid::world_error::WorldError;
+use crate::game::world_edit::selection::Selection;
+
+// Each operation returns the blocks it would set, without changing the world. Pass them to
+// EditHistory::apply to actually make the change, so it can be undone.
+// Blocks always keep the biome of the block they replace.
+
+/// Sets every block in the selection to the given type and shape.
+pub fn fill(
+    chunks: &Chunks,
+    selection: &Selection,
+    block_type: BlockType,
+    shape: BlockShape,
+) -> Result<Vec<(IPos, Block)>, WorldError> {
+    set_where(chunks, selection.positions(), block_type, shape, |_, _| {
+        true
+    })
+}
+
+/// Sets every block in the selection whose type is in the filter to the given type and shape.
+pub fn replace(
+    chunks: &Chunks,
+    selection: &Selection,
+    filter: &HashSet<BlockType>,
+    block_type: BlockType,
+    shape: BlockShape,
+) -> Result<Vec<(IPos, Block)>, WorldError> {
+    set_where(
+        chunks,
+        selection.positions(),
+        block_type,
+        shape,
+        |_, block| filter.contains(&block.block_type),
+    )
+}
+
+/// Clears out everything inside the selection, leaving only its outer shell.
+pub fn hollow(chunks: &Chunks, selection: &Selection) -> Result<Vec<(IPos, Block)>, WorldError> {
+    set_where(
+        chunks,
+        selection.positions(),
+        BlockType::Air,
+        BlockShape::Full,
+        |pos, _| !selection.is_shell(pos),
+    )
+}
+
+/// Copies all blocks in the selection, including air, relative to the selection's origin.
+pub fn copy(chunks: &Chunks, selection: &Selection) -> Result<StructureTemplate, WorldError> {
+    let origin = selection.origin();
+    let mut blocks = vec![];
+    for pos in selection.positions() {
+        let block = chunks.try_block(&pos)?;
+        blocks.push(TemplateBlock {
+            pos: pos - origin,
+            block_type: block.block_type,
+            shape: block.shape,
+        });
+    }
+    Ok(StructureTemplate {
+        name: "Clipboard".to_string(),
+        description: String::new(),
+        author: String::new(),
+        tags: vec![],
+        blocks,
+    })
+}
+
+/// Places the copied blocks with their origin at the given position.
+pub fn paste(
+    chunks: &Chunks,
+    clipboard: &StructureTemplate,
+    origin: &IPos,
+    placement: &TemplatePlacement,
+) -> Result<Vec<(IPos, Block)>, WorldError> {
+    clipboard
+        .transformed(origin, placement)
+        .into_iter()
+        .map(|block| {
+            let biome_type = chunks.try_block(&block.pos)?.biome_type;
+            Ok((
+                block.pos,
+                Block {
+                    block_type: block.block_type,
+                    biome_type,
+                    shape: block.shape,
+                },
+            ))
+        })
+        .collect()
+}
+
+fn set_where(
+    chunks: &Chunks,
+    positions: Vec<IPos>,
+    block_type: BlockType,
+    shape: BlockShape,
+    predicate: impl Fn(&IPos, &Block) -> bool,
+) -> Result<Vec<(IPos, Block)>, WorldError> {
+    let mut blocks = vec![];
+    for pos in positions {
+        let block = chunks.try_block(&pos)?;
+        if predicate(&pos, block) {
+            blocks.push((
+                pos,
+                Block {
+                    block_type,
+                    biome_type: block.biome_type,
+                    shape,
+                },
+            ));
+        }
+    }
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::hex_grid::axial::ChunkId;
+    use crate::game::hex_grid::biomes::BiomeType;
+    use crate::game::hex_grid::chunk::ChunkDimensions;
+    use crate::game::procedural_generation::presets::{FlatGenerator, FlatLayer};
+    use crate::game::world_edit::history::EditHistory;
+
+    const FLOOR: i32 = 2;
+
+    fn test_world() -> Chunks {
+        let dimensions = ChunkDimensions {
+            radius: 3,
+            height: 8,
+        };
+        let layers = [FlatLayer {
+            block_type: BlockType::Stone,
+            thickness: FLOOR as u32,
+        }];
+        let generator = FlatGenerator::new(&layers, BiomeType::Grassland);
+        let mut chunks = Chunks::new(dimensions, Box::new(generator));
+        for chunk_id in ChunkId::splat(0).range(1) {
+            chunks.generate_chunk(chunk_id);
+        }
+        chunks
+    }
+
+    #[test]
+    fn hollow_leaves_only_the_shell() {
+        for selection in [
+            Selection::from_corners(&IPos::new(-2, -1, FLOOR), &IPos::new(3, 2, FLOOR + 3)),
+            Selection::Radius {
+                center: IPos::new(1, 0, FLOOR + 2),
+                radius: 2,
+            },
+        ] {
+            let mut chunks = test_world();
+            let mut history = EditHistory::default();
+            let blocks = fill(&chunks, &selection, BlockType::Stone, BlockShape::Full).unwrap();
+            history.apply(&mut chunks, "Fill", blocks).unwrap();
+            let blocks = hollow(&chunks, &selection).unwrap();
+            history.apply(&mut chunks, "Hollow", blocks).unwrap();
+            let mut inside = 0;
+            for pos in selection.positions() {
+                let expected = if selection.is_shell(&pos) {
+                    BlockType::Stone
+                } else {
+                    inside += 1;
+                    BlockType::Air
+                };
+                assert_eq!(
+                    chunks.block(&pos).unwrap().block_type,
+                    expected,
+                    "Block {:?}",
+                    pos
+                );
+            }
+            assert!(inside > 0);
+        }
+    }
+
+    #[test]
+    fn paste_puts_a_copy_at_the_target() {
+        let mut chunks = test_world();
+        let mut history = EditHistory::default();
+        let selection =
+            Selection::from_corners(&IPos::new(-2, 0, FLOOR), &IPos::new(0, 1, FLOOR + 1));
+        let mut blocks = fill(&chunks, &selection, BlockType::Wood, BlockShape::Full).unwrap();
+        blocks.truncate(blocks.len() / 2);
+        history.apply(&mut chunks, "Fill", blocks).unwrap();
+        let clipboard = copy(&chunks, &selection).unwrap();
+        let target = IPos::new(3, -3, FLOOR);
+        let placement = TemplatePlacement {
+            rotation: 1,
+            mirrored: false,
+        };
+        let blocks = paste(&chunks, &clipboard, &target, &placement).unwrap();
+        history.apply(&mut chunks, "Paste", blocks).unwrap();
+        for pos in selection.positions() {
+            let relative = pos - selection.origin();
+            let pasted = placement.apply(&relative) + target;
+            assert_eq!(
+                chunks.block(&pasted).unwrap().block_type,
+                chunks.block(&pos).unwrap().block_type,
+                "Block {:?}",
+                relative
+            );
+        }
+    }
+}
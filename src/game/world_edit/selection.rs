@@ -0,0 +1,57 @@
+use crate::game::hex_grid::axial::IPos;
+use crate::game::hex_grid::region::Region;
+
+/// The blocks that world-edit operations act on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Selection {
+    /// Everything between two corners.
+    Region(Region),
+    /// A hexagonal prism around a point: every block within the radius of the center, both
+    /// horizontally and vertically.
+    Radius { center: IPos, radius: u32 },
+}
+
+impl Selection {
+    #[must_use]
+    pub fn from_corners(a: &IPos, b: &IPos) -> Self {
+        Self::Region(Region::from_corners(a, b))
+    }
+    #[must_use]
+    pub fn contains(&self, pos: &IPos) -> bool {
+        match self {
+            Selection::Region(region) => region.contains(pos),
+            Selection::Radius { center, radius } => {
+                IPos::distance_3d(pos, center) <= *radius as i32
+            }
+        }
+    }
+    /// All positions in the selection.
+    #[must_use]
+    pub fn positions(&self) -> Vec<IPos> {
+        match self {
+            Selection::Region(region) => region.positions().collect(),
+            Selection::Radius { center, radius } => center.range_3d(*radius),
+        }
+    }
+    /// The bottom center of the selection. Copied blocks are stored relative to this point,
+    /// so that pasting puts the bottom of the copy on the targeted block.
+    #[must_use]
+    pub fn origin(&self) -> IPos {
+        match self {
+            Selection::Region(region) => {
+                let (min, max) = (region.min(), region.max());
+                IPos::new((min.q() + max.q()) / 2, (min.r() + max.r()) / 2, min.z())
+            }
+            Selection::Radius { center, radius } => center.delta(0, 0, -(*radius as i32)),
+        }
+    }
+    /// Returns true if the position is in the selection, but at least one of its neighbours isn't.
+    #[must_use]
+    pub fn is_shell(&self, pos: &IPos) -> bool {
+        self.contains(pos)
+            && pos
+                .neighbours_3d()
+                .iter()
+                .any(|neighbour| !self.contains(neighbour))
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+
+use crate::game::camera::first_person::PlayerCamera;
+use crate::game::hex_grid::axial::IPos;
+use crate::game::hex_grid::block::{Block, BlockShape, BlockType};
+use crate::game::hex_grid::chunks::Chunks;
+use crate::game::hex_grid::templates::{StructureTemplate, TemplatePlacement};
+use crate::game::hex_grid::world_error::WorldError;
+use crate::game::world_edit::history::EditHistory;
+use crate::game::world_edit::operations;
+use crate::game::world_edit::selection::Selection;
+use crate::io::input::{InputHandler, WorldEditInput};
+
+/// Blocks further away from the camera than this (in meters) can't be targeted.
+const MAX_REACH: f32 = 64.;
+/// The distance (in meters) between the points at which the view ray is checked for blocks.
+const REACH_STEP: f32 = 0.05;
+
+/// The state of the world-edit tools: what is selected, and what to edit it with.
+#[derive(Resource)]
+pub struct WorldEditor {
+    corner_a: Option<IPos>,
+    corner_b: Option<IPos>,
+    /// The radius used when selecting around a point.
+    radius: u32,
+    selection: Option<Selection>,
+    /// The block type used to fill and replace.
+    brush: BlockType,
+    /// The shape of the blocks placed by fill and replace.
+    brush_shape: BlockShape,
+    /// The block types that are replaced by Replace.
+    filter: HashSet<BlockType>,
+    clipboard: Option<StructureTemplate>,
+    placement: TemplatePlacement,
+}
+
+impl Default for WorldEditor {
+    fn default() -> Self {
+        Self {
+            corner_a: None,
+            corner_b: None,
+            radius: 3,
+            selection: None,
+            brush: BlockType::Stone,
+            brush_shape: BlockShape::Full,
+            filter: HashSet::new(),
+            clipboard: None,
+            placement: TemplatePlacement::default(),
+        }
+    }
+}
+
+impl WorldEditor {
+    fn select_corner(&mut self, pos: IPos, first: bool) {
+        if first {
+            self.corner_a = Some(pos);
+        } else {
+            self.corner_b = Some(pos);
+        }
+        if let (Some(a), Some(b)) = (self.corner_a, self.corner_b) {
+            self.selection = Some(Selection::from_corners(&a, &b));
+        }
+    }
+    fn cycle_brush(&mut self, step: i32) {
+        let all = BlockType::all();
+        let index = all
+            .iter()
+            .position(|block| *block == self.brush)
+            .unwrap_or(0) as i32;
+        self.brush = all[(index + step).rem_euclid(all.len() as i32) as usize];
+    }
+    fn cycle_brush_shape(&mut self, step: i32) {
+        let all = [
+            BlockShape::Full,
+            BlockShape::HalfSlab,
+            BlockShape::Layer,
+            BlockShape::Pillar,
+        ]
+        .into_iter()
+        .chain((0..6).map(BlockShape::Wedge))
+        .collect::<Vec<BlockShape>>();
+        let index = all
+            .iter()
+            .position(|shape| *shape == self.brush_shape)
+            .unwrap_or(0) as i32;
+        self.brush_shape = all[(index + step).rem_euclid(all.len() as i32) as usize];
+    }
+}
+
+/// Returns the first solid block that the player is looking at, if it's within reach.
+pub fn targeted_block(chunks: &Chunks, camera: &GlobalTransform) -> Option<IPos> {
+    let origin = camera.translation();
+    let direction = camera.forward();
+    (0..(MAX_REACH / REACH_STEP) as u32)
+        .map(|step| IPos::block_containing(&(origin + direction * step as f32 * REACH_STEP)))
+        .find(|pos| chunks.is_solid(pos))
+}
+
+/// Handles everything that changes what is selected or what it will be edited with.
+pub fn process_world_edit_selection(
+    input: InputHandler,
+    mut editor: ResMut<WorldEditor>,
+    chunks: Res<Chunks>,
+    camera: Query<&GlobalTransform, With<PlayerCamera>>,
+) {
+    let target = camera
+        .get_single()
+        .ok()
+        .and_then(|camera| targeted_block(&chunks, camera));
+    for (action, first) in [
+        (WorldEditInput::SelectCornerA, true),
+        (WorldEditInput::SelectCornerB, false),
+    ] {
+        if input.is_active(action) {
+            if let Some(pos) = target {
+                editor.select_corner(pos, first);
+                info!(
+                    "Selected corner {:?}; selection is now {:?}",
+                    pos, editor.selection
+                );
+            }
+        }
+    }
+    if input.is_active(WorldEditInput::SelectRadius) {
+        if let Some(center) = target {
+            let radius = editor.radius;
+            editor.selection = Some(Selection::Radius { center, radius });
+            info!("Selected radius {} around {:?}", radius, center);
+        }
+    }
+    let radius = input.direction(WorldEditInput::ShrinkRadius, WorldEditInput::GrowRadius);
+    if !radius.is_neutral() {
+        editor.radius = (editor.radius as i32 + radius.signum_i()).max(0) as u32;
+        if let Some(Selection::Radius { center, .. }) = editor.selection {
+            editor.selection = Some(Selection::Radius {
+                center,
+                radius: editor.radius,
+            });
+        }
+        info!("Selection radius is now {}", editor.radius);
+    }
+    if input.is_active(WorldEditInput::ClearSelection) {
+        editor.corner_a = None;
+        editor.corner_b = None;
+        editor.selection = None;
+        info!("Cleared the selection.");
+    }
+    let brush = input.direction(WorldEditInput::PreviousBlock, WorldEditInput::NextBlock);
+    if !brush.is_neutral() {
+        editor.cycle_brush(brush.signum_i());
+        info!("Editing with {:?}", editor.brush);
+    }
+    let shape = input.direction(WorldEditInput::PreviousShape, WorldEditInput::NextShape);
+    if !shape.is_neutral() {
+        editor.cycle_brush_shape(shape.signum_i());
+        info!("Editing with shape {:?}", editor.brush_shape);
+    }
+    if input.is_active(WorldEditInput::ToggleFilter) {
+        if let Some(block) = target.and_then(|pos| chunks.block(&pos)) {
+            let block_type = block.block_type;
+            if !editor.filter.remove(&block_type) {
+                editor.filter.insert(block_type);
+            }
+            info!("Replace filter is now {:?}", editor.filter);
+        }
+    }
+    if input.is_active(WorldEditInput::ClearFilter) {
+        editor.filter.clear();
+        info!("Cleared the replace filter.");
+    }
+    if input.is_active(WorldEditInput::RotateClipboard) {
+        editor.placement.rotation = (editor.placement.rotation + 1).rem_euclid(6);
+        info!("Clipboard rotation is now {}", editor.placement.rotation);
+    }
+    if input.is_active(WorldEditInput::MirrorClipboard) {
+        editor.placement.mirrored ^= true;
+        info!("Clipboard mirrored: {}", editor.placement.mirrored);
+    }
+}
+
+/// Handles everything that changes the world: editing the selection, pasting, undo and redo.
+pub fn process_world_edit_operations(
+    input: InputHandler,
+    mut editor: ResMut<WorldEditor>,
+    mut history: ResMut<EditHistory>,
+    mut chunks: ResMut<Chunks>,
+    camera: Query<&GlobalTransform, With<PlayerCamera>>,
+) {
+    if input.is_active(WorldEditInput::Undo) {
+        match history.undo(&mut chunks) {
+            Ok(Some(name)) => info!("Undid {}", name),
+            Ok(None) => info!("Nothing to undo."),
+            Err(err) => warn!("Could not undo: {}", err),
+        }
+    }
+    if input.is_active(WorldEditInput::Redo) {
+        match history.redo(&mut chunks) {
+            Ok(Some(name)) => info!("Redid {}", name),
+            Ok(None) => info!("Nothing to redo."),
+            Err(err) => warn!("Could not redo: {}", err),
+        }
+    }
+    if input.is_active(WorldEditInput::Paste) {
+        let target = camera
+            .get_single()
+            .ok()
+            .and_then(|camera| targeted_block(&chunks, camera));
+        if let (Some(clipboard), Some(target)) = (&editor.clipboard, target) {
+            let blocks = operations::paste(
+                &chunks,
+                clipboard,
+                &target.delta(0, 0, 1),
+                &editor.placement,
+            );
+            apply(&mut history, &mut chunks, "paste", blocks);
+        }
+    }
+    let selection = if let Some(selection) = editor.selection {
+        selection
+    } else {
+        return;
+    };
+    if input.is_active(WorldEditInput::Fill) {
+        let blocks = operations::fill(&chunks, &selection, editor.brush, editor.brush_shape);
+        apply(&mut history, &mut chunks, "fill", blocks);
+    }
+    if input.is_active(WorldEditInput::Replace) {
+        let blocks = operations::replace(
+            &chunks,
+            &selection,
+            &editor.filter,
+            editor.brush,
+            editor.brush_shape,
+        );
+        apply(&mut history, &mut chunks, "replace", blocks);
+    }
+    if input.is_active(WorldEditInput::Hollow) {
+        let blocks = operations::hollow(&chunks, &selection);
+        apply(&mut history, &mut chunks, "hollow", blocks);
+    }
+    if input.is_active(WorldEditInput::Copy) {
+        match operations::copy(&chunks, &selection) {
+            Ok(clipboard) => {
+                info!("Copied {} blocks.", clipboard.blocks.len());
+                editor.clipboard = Some(clipboard);
+                editor.placement = TemplatePlacement::default();
+            }
+            Err(err) => warn!("Could not copy: {}", err),
+        }
+    }
+}
+
+fn apply(
+    history: &mut EditHistory,
+    chunks: &mut Chunks,
+    name: &str,
+    blocks: Result<Vec<(IPos, Block)>, WorldError>,
+) {
+    match blocks.and_then(|blocks| history.apply(chunks, name, blocks)) {
+        Ok(count) => info!("Applied {}: {} blocks changed.", name, count),
+        Err(err) => warn!("Could not {}: {}", name, err),
+    }
+}
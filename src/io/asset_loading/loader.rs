@@ -1,8 +1,11 @@
-use crate::io::asset_loading::meta::{LoaderHandles, MetaAsset};
+use crate::game::hex_grid::templates::{StructureLibrary, StructureTemplate};
+use crate::io::asset_loading::meta::{LoaderHandles, MetaAsset, ModDirectories};
 use crate::io::asset_loading::MergingAsset;
+use crate::io::audio::AudioLibrary;
 use crate::io::config::Config;
-use bevy::asset::{AssetServer, LoadState};
+use bevy::asset::{AssetServer, FileAssetIo, LoadState};
 use bevy::prelude::*;
+use bevy_kira_audio::prelude::AudioSource;
 use iyes_loopless::prelude::NextState;
 
 /// These are sub-states that the loading state works through.
@@ -137,10 +140,51 @@ pub fn load_files(
                 handles.put_config(filename, server.load(&path));
             }
         }
+        // Music is left out for now; it's in mp3 format, which we don't have a decoder enabled for.
+        for sounds in [&file_structure.sfx, &file_structure.ambience] {
+            for (group_id, items) in sounds.iter() {
+                for (item_id, filename) in items.iter() {
+                    let path = format!("{}/{}", mod_name, filename);
+                    if let Some(audio) = load_audio(&server, &path) {
+                        handles.put_audio(group_id, item_id, audio);
+                    }
+                }
+            }
+        }
+        for (id, filename) in file_structure.structures.iter() {
+            let path = format!("{}/{}", mod_name, filename);
+            if server.asset_io().get_metadata((path).as_ref()).is_ok() {
+                handles
+                    .structures
+                    .insert(id.to_string(), server.load(&path));
+            }
+        }
     }
     commands.insert_resource(NextState(LoadProcess::WaitForFiles));
 }
 
+/// Loads either a single audio file, or all audio files in the given directory.
+/// Returns None if the path doesn't exist in this mod.
+fn load_audio(server: &AssetServer, path: &str) -> Option<Vec<Handle<AudioSource>>> {
+    let metadata = server.asset_io().get_metadata(path.as_ref()).ok()?;
+    if metadata.is_dir() {
+        match server.load_folder(path) {
+            Ok(handles) => Some(
+                handles
+                    .into_iter()
+                    .map(|handle| handle.typed::<AudioSource>())
+                    .collect(),
+            ),
+            Err(err) => {
+                error!("Failed to load audio directory {}: {:?}", path, err);
+                None
+            }
+        }
+    } else {
+        Some(vec![server.load(path)])
+    }
+}
+
 pub fn check_files_are_present(
     mut commands: Commands,
     handles: Res<LoaderHandles>,
@@ -162,6 +206,7 @@ pub fn resolve_mods(
     handles: Res<LoaderHandles>,
     meta_assets: Res<Assets<MetaAsset>>,
     configs: Res<Assets<Config>>,
+    structures: Res<Assets<StructureTemplate>>,
 ) {
     let file_structure = meta_assets.get(&handles.file_structure);
     let file_structure = file_structure
@@ -186,5 +231,29 @@ pub fn resolve_mods(
             panic!("Failed to load resource {}.", config_type);
         }
     }
+    commands.insert_resource(AudioLibrary::new(handles.audio.clone()));
+    commands.insert_resource(StructureLibrary::new(
+        handles
+            .structures
+            .iter()
+            .filter_map(|(id, handle)| {
+                structures
+                    .get(handle)
+                    .map(|template| (id.to_string(), template.clone()))
+            })
+            .collect(),
+    ));
+    let mod_order = meta_assets
+        .get(&handles.mod_order)
+        .expect("mod_order.meta.ron wasn't loaded (yet)!")
+        .as_mod_order();
+    let assets_dir = FileAssetIo::get_base_path().join("assets");
+    commands.insert_resource(ModDirectories(
+        mod_order
+            .mods
+            .iter()
+            .map(|mod_name| assets_dir.join(mod_name))
+            .collect(),
+    ));
     commands.insert_resource(NextState(LoadProcess::DoneLoading));
 }
@@ -1,9 +1,12 @@
 use bevy::asset::{AssetLoader, BoxedFuture, Handle, HandleId, LoadContext, LoadedAsset};
 use bevy::prelude::*;
 use bevy::reflect::TypeUuid;
+use bevy_kira_audio::prelude::AudioSource;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::PathBuf;
 
+use crate::game::hex_grid::templates::StructureTemplate;
 use crate::io::config::Config;
 
 /// Implement for any assets that can merge together different versions from different mods.
@@ -26,6 +29,12 @@ pub struct LoaderHandles {
     pub manifests: HashMap<String, Handle<MetaAsset>>,
     /// Maps config name to a list of configs from different mods. It always picks the last one.
     pub configs: HashMap<String, Vec<Handle<Config>>>,
+    /// Maps group id and item id to the audio files of that sound.
+    /// If multiple mods provide the same sound, the one loaded last replaces the others.
+    pub audio: HashMap<String, HashMap<String, Vec<Handle<AudioSource>>>>,
+    /// Maps the id of a structure template to its file.
+    /// If multiple mods provide the same template, the one loaded last replaces the others.
+    pub structures: HashMap<String, Handle<StructureTemplate>>,
 }
 
 impl LoaderHandles {
@@ -35,6 +44,12 @@ impl LoaderHandles {
         }
         self.configs.get_mut(filename).unwrap().push(handle);
     }
+    pub fn put_audio(&mut self, group_id: &str, item_id: &str, handles: Vec<Handle<AudioSource>>) {
+        self.audio
+            .entry(group_id.to_string())
+            .or_default()
+            .insert(item_id.to_string(), handles);
+    }
     /// Temporary function, used to wait until all assets are loaded.
     /// TODO: Replace with something more sophisticated that can actually report what files are not
     ///     loaded yet and what files errorred while loading.
@@ -48,6 +63,16 @@ impl LoaderHandles {
                 .map(|handle| handle.id())
                 .for_each(|id| vec.push(id));
         }
+        self.audio
+            .values()
+            .flat_map(|group| group.values())
+            .flatten()
+            .map(|handle| handle.id())
+            .for_each(|id| vec.push(id));
+        self.structures
+            .values()
+            .map(|handle| handle.id())
+            .for_each(|id| vec.push(id));
         vec
     }
 }
@@ -99,6 +124,24 @@ pub struct ModOrder {
     pub mods: Vec<String>,
 }
 
+/// The directories of the loaded mods, in the order they were loaded.
+/// Files that aren't loaded through the AssetServer are looked up in these.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct ModDirectories(pub Vec<PathBuf>);
+
+impl ModDirectories {
+    /// Returns the path of the file in the last mod that has it, or None if no mod has it.
+    /// Like other files, a file in a mod later in the loading order replaces those before it.
+    #[must_use]
+    pub fn find(&self, path: &str) -> Option<PathBuf> {
+        self.0
+            .iter()
+            .rev()
+            .map(|dir| dir.join(path))
+            .find(|path| path.is_file())
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct FileStructure {
@@ -108,6 +151,11 @@ pub struct FileStructure {
     pub sfx: HashMap<String, HashMap<String, String>>,
     /// Maps SfxId.group_id() and SfxId.item_id() to an asset directory of music files.
     pub music: HashMap<String, HashMap<String, String>>,
+    /// Maps AmbienceId.group_id() and AmbienceId.item_id() to a looping ambient audio file.
+    pub ambience: HashMap<String, HashMap<String, String>>,
+    /// Maps the id of a structure template to its file.
+    #[serde(default)]
+    pub structures: HashMap<String, String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
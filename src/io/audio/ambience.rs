@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_kira_audio::prelude::{Audio, AudioControl, AudioInstance, AudioTween};
+
+use crate::game::actors::structs::Player;
+use crate::game::hex_grid::axial::{ColumnId, IPos};
+use crate::game::hex_grid::biomes::BiomeType;
+use crate::game::hex_grid::chunks::Chunks;
+use crate::game::meshes::sun::DayNight;
+use crate::io::audio::{AmbientSound, AudioLibrary};
+use crate::io::config::AudioConfig;
+
+/// How long it takes for an ambient track to fade to its new volume.
+/// This should roughly equal the interval at which update_ambience runs.
+const AMBIENCE_FADE_MILLIS: u64 = 1000;
+
+/// Keeps track of the looping ambient tracks that are currently playing.
+#[derive(Resource, Default)]
+pub struct AmbiencePlayer {
+    instances: HashMap<AmbientSound, Handle<AudioInstance>>,
+}
+
+/// Starts all ambient tracks that are used by any biome, silently.
+/// Their volumes are adjusted afterwards by update_ambience.
+pub fn start_ambience(
+    mut commands: Commands,
+    audio: Res<Audio>,
+    library: Res<AudioLibrary>,
+    config: Res<AudioConfig>,
+) {
+    let mut sounds = config
+        .biome_ambience
+        .values()
+        .flat_map(|ambience| ambience.day.iter().chain(ambience.night.iter()))
+        .copied()
+        .collect::<Vec<AmbientSound>>();
+    sounds.sort();
+    sounds.dedup();
+    let instances = sounds
+        .into_iter()
+        .filter_map(|sound| {
+            library.ambience(&sound).map(|source| {
+                let instance = audio.play(source).looped().with_volume(0.).handle();
+                (sound, instance)
+            })
+        })
+        .collect();
+    commands.insert_resource(AmbiencePlayer { instances });
+}
+
+/// A system meant to run periodically (not every tick).
+/// Samples the biomes around the player and blends the ambient tracks accordingly.
+pub fn update_ambience(
+    config: Res<AudioConfig>,
+    chunks: Res<Chunks>,
+    day_night: Res<DayNight>,
+    player: Res<AmbiencePlayer>,
+    mut instances: ResMut<Assets<AudioInstance>>,
+    query: Query<&Transform, With<Player>>,
+) {
+    if let Ok(transform) = query.get_single() {
+        let center = IPos::block_containing(&transform.translation);
+        let biome_mix = sample_biomes(&chunks, &center, config.ambience_radius);
+        let daylight = day_night.daylight() as f64;
+
+        let mut volumes = player
+            .instances
+            .keys()
+            .map(|sound| (*sound, 0.))
+            .collect::<HashMap<AmbientSound, f64>>();
+        for (biome, weight) in biome_mix.iter() {
+            if let Some(ambience) = config.biome_ambience.get(biome) {
+                for sound in ambience.day.iter() {
+                    *volumes.entry(*sound).or_default() += weight * daylight;
+                }
+                for sound in ambience.night.iter() {
+                    *volumes.entry(*sound).or_default() += weight * (1. - daylight);
+                }
+            }
+        }
+        for (sound, handle) in player.instances.iter() {
+            if let Some(instance) = instances.get_mut(handle) {
+                let volume = volumes.get(sound).copied().unwrap_or_default().min(1.);
+                instance.set_volume(
+                    volume * config.ambience_volume,
+                    AudioTween::linear(Duration::from_millis(AMBIENCE_FADE_MILLIS)),
+                );
+            }
+        }
+    }
+}
+
+/// Returns the fraction of loaded columns within the radius that belong to each biome.
+/// The fractions add up to one, unless none of the columns are loaded.
+fn sample_biomes(chunks: &Chunks, center: &IPos, radius: u32) -> HashMap<BiomeType, f64> {
+    let mut counts: HashMap<BiomeType, u32> = HashMap::new();
+    let mut total = 0;
+    // Every block in a column has the same biome, so we only need to look at one of them.
+    // Sample at the center's own height, which lies in a chunk layer that is loaded around it:
+    for column in ColumnId::spiral(radius).iter() {
+        if let Some(block) = chunks.block(&(column + *center)) {
+            *counts.entry(block.biome_type).or_default() += 1;
+            total += 1;
+        }
+    }
+    counts
+        .into_iter()
+        .map(|(biome, count)| (biome, count as f64 / total as f64))
+        .collect()
+}
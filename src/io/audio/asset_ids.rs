@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 
+use hex_derive::AmbienceId;
 use hex_derive::MusicId;
 use hex_derive::SfxId;
 
@@ -17,6 +18,13 @@ pub trait MusicId {
     fn item_id(&self) -> &'static str;
 }
 
+pub trait AmbienceId {
+    /// The name of the enum.
+    fn group_id(&self) -> &'static str;
+    /// The enum value.
+    fn item_id(&self) -> &'static str;
+}
+
 #[derive(SfxId, Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
 #[serde(deny_unknown_fields)]
 pub enum SfxMonster {
@@ -32,3 +40,19 @@ pub enum BackgroundMusic {
     ExistentialHexMan,
     HexagonalCubes,
 }
+
+/// Looping background tracks that are blended together depending on the biomes around the player.
+#[derive(
+    AmbienceId, Deserialize, Serialize, Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord,
+)]
+#[serde(deny_unknown_fields)]
+pub enum AmbientSound {
+    Wind,
+    NightWind,
+    Birds,
+    Owls,
+    Insects,
+    NightInsects,
+    Frogs,
+    Crickets,
+}
@@ -0,0 +1,47 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_kira_audio::prelude::AudioSource;
+use rand::seq::SliceRandom;
+
+use crate::io::audio::{AmbienceId, SfxId};
+
+/// Holds handles to all loaded audio files, grouped the same way as in file_structure.meta.ron.
+///
+/// Sound effects can have multiple variations (every file in the configured directory), one of
+/// which is picked at random each time the effect is played.
+#[derive(Resource, Default)]
+pub struct AudioLibrary {
+    sounds: HashMap<String, HashMap<String, Vec<Handle<AudioSource>>>>,
+}
+
+impl AudioLibrary {
+    pub fn new(sounds: HashMap<String, HashMap<String, Vec<Handle<AudioSource>>>>) -> Self {
+        Self { sounds }
+    }
+    /// Returns one of the variations of the given sound effect, picked at random.
+    pub fn sfx(&self, id: &impl SfxId) -> Option<Handle<AudioSource>> {
+        self.variations(id.group_id(), id.item_id())
+            .choose(&mut rand::thread_rng())
+            .cloned()
+    }
+    /// Returns the looping track for the given ambient sound.
+    pub fn ambience(&self, id: &impl AmbienceId) -> Option<Handle<AudioSource>> {
+        self.variations(id.group_id(), id.item_id())
+            .first()
+            .cloned()
+    }
+    fn variations(&self, group_id: &str, item_id: &str) -> &[Handle<AudioSource>] {
+        self.sounds
+            .get(group_id)
+            .and_then(|group| group.get(item_id))
+            .map(|handles| handles.as_slice())
+            .unwrap_or_else(|| {
+                warn!(
+                    "Audio lookup failed: no files were loaded for {:?}::{:?}.",
+                    group_id, item_id
+                );
+                &[]
+            })
+    }
+}
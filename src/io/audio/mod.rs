@@ -1 +1,7 @@
+pub use self::ambience::*;
+pub use self::asset_ids::*;
+pub use self::library::*;
+
+mod ambience;
 mod asset_ids;
+mod library;
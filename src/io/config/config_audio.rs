@@ -1,5 +1,27 @@
+use std::collections::HashMap;
+
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
+
+use crate::game::hex_grid::biomes::BiomeType;
+use crate::io::audio::AmbientSound;
+
 #[derive(Resource, Debug, Default, Deserialize, Serialize, Clone)]
 #[serde(deny_unknown_fields)]
-pub struct AudioConfig {}
+pub struct AudioConfig {
+    /// Volume of the ambient sounds when a single biome fully surrounds the player. Between 0 and 1.
+    pub ambience_volume: f64,
+    /// Radius (in columns) of the area around the player that is sampled to find the biome mix.
+    pub ambience_radius: u32,
+    /// The ambient sounds that play in each biome. Biomes that aren't listed are silent.
+    pub biome_ambience: HashMap<BiomeType, BiomeAmbience>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct BiomeAmbience {
+    /// Sounds that play during the day. They fade out towards midnight.
+    pub day: Vec<AmbientSound>,
+    /// Sounds that play during the night. They fade out towards noon.
+    pub night: Vec<AmbientSound>,
+}
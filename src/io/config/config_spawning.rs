@@ -0,0 +1,34 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::game::actors::spawning::ActorType;
+use crate::game::hex_grid::biomes::BiomeType;
+use crate::game::hex_grid::block::BlockType;
+
+#[derive(Resource, Debug, Default, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SpawningConfig {
+    /// Actors never spawn closer than this distance (in meters) to a chunk loader, such as the player.
+    pub min_distance_to_loader: f32,
+    /// The maximum number of actors that can spawn in a single chunk when it is loaded.
+    pub max_per_chunk: u32,
+    /// For each biome, the actors that may spawn there. Biomes that aren't listed have no spawns.
+    pub biomes: HashMap<BiomeType, Vec<SpawnRule>>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SpawnRule {
+    pub actor: ActorType,
+    /// The average number of actors of this type that spawn in a chunk fully covered by the biome.
+    pub density: f32,
+    /// Actors only spawn while the daylight is at least this value.
+    /// Daylight is 0 at midnight and 1 at noon.
+    pub min_daylight: f32,
+    /// Actors only spawn while the daylight is at most this value.
+    pub max_daylight: f32,
+    /// Actors only spawn on top of these blocks.
+    pub surface_blocks: Vec<BlockType>,
+}
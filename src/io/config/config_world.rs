@@ -2,6 +2,10 @@ use bevy::prelude::Color;
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
 use splines::{Interpolation, Key, Spline};
+
+use crate::game::actors::spawn_point::SpawnSearchConfig;
+use crate::game::hex_grid::chunk::ChunkDimensions;
+
 #[derive(Resource, Debug, Default, Deserialize, Serialize, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct WorldConfig {
@@ -20,6 +24,11 @@ pub struct WorldConfig {
     /// Note that one additional ring of chunks will be loaded into memory, but not rendered.
     /// This is primarily to make sure sides of chunks don't end up in the mesh unnecessarily.
     pub render_distance_max: u32,
+    /// The size of the chunks that new worlds are divided into.
+    /// Existing worlds keep the chunk dimensions they were created with.
+    pub chunk_dimensions: ChunkDimensions,
+    /// Determines where new players spawn.
+    pub spawn_search: SpawnSearchConfig,
 }
 
 impl WorldConfig {
@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::game::procedural_generation::caves::CaveConfig;
+use crate::game::procedural_generation::erosion::ErosionConfig;
+use crate::game::procedural_generation::features::FeatureConfig;
+use crate::game::procedural_generation::hydrology::HydrologyConfig;
+use crate::game::procedural_generation::noise_graph::{NoiseGraph, NoiseNode};
+use crate::game::procedural_generation::ores::OreConfig;
+use crate::game::procedural_generation::world_generator::WorldPreset;
+
+#[derive(Resource, Debug, Default, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct WorldGenConfig {
+    /// Every random aspect of world generation is derived from this seed.
+    /// Only new worlds use it; existing worlds keep the seed they were created with.
+    pub seed: u32,
+    /// The kind of world to generate. Only the Terrain and Amplified presets use the other settings.
+    /// Only new worlds use it; existing worlds keep the preset they were created with.
+    pub preset: WorldPreset,
+    /// Named noise graphs. The terrain generator looks for the graphs named
+    /// "elevation", "humidity" and "temperature".
+    pub graphs: HashMap<String, NoiseNode>,
+    /// Carves caves into the terrain and adds overhangs to it.
+    pub caves: CaveConfig,
+    /// Ore veins, placed after the base terrain in the order they are listed.
+    pub ores: Vec<OreConfig>,
+    /// Trees, boulders and ruins, placed on top of the finished terrain.
+    pub features: FeatureConfig,
+    /// Rivers, lakes and the sea.
+    pub hydrology: HydrologyConfig,
+    /// Erodes the heightmap before chunks are filled in. Rivers follow the eroded terrain.
+    pub erosion: ErosionConfig,
+}
+
+impl WorldGenConfig {
+    /// Compiles the graph with the given name. If there is none, a flat graph is returned instead.
+    #[must_use]
+    pub fn graph(&self, name: &str) -> NoiseGraph {
+        if let Some(node) = self.graphs.get(name) {
+            NoiseGraph::new(node, self.seed)
+        } else {
+            warn!(
+                "The worldgen config has no noise graph named '{}', it will be flat instead.",
+                name
+            );
+            NoiseGraph::default()
+        }
+    }
+}
@@ -4,7 +4,9 @@ use bevy::prelude::Commands;
 use bevy::reflect::TypeUuid;
 use serde::{Deserialize, Serialize};
 
-use crate::io::config::{AudioConfig, DebugConfig, InputConfig, WorldConfig};
+use crate::io::config::{
+    AudioConfig, DebugConfig, InputConfig, SpawningConfig, WorldConfig, WorldGenConfig,
+};
 
 /// This wrapper around the different config types is needed to create a single AssetLoader for
 /// all configs. Otherwise we'd need to implement a separate AssetLoader per config type.
@@ -15,7 +17,10 @@ pub enum Config {
     Audio(AudioConfig),
     Debug(DebugConfig),
     Input(InputConfig),
+    Spawning(SpawningConfig),
     World(WorldConfig),
+    /// Boxed, because it is much larger than the other configs.
+    WorldGen(Box<WorldGenConfig>),
 }
 
 impl Config {
@@ -30,9 +35,15 @@ impl Config {
             Config::Input(value) => {
                 commands.insert_resource(value);
             }
+            Config::Spawning(value) => {
+                commands.insert_resource(value);
+            }
             Config::World(value) => {
                 commands.insert_resource(value);
             }
+            Config::WorldGen(value) => {
+                commands.insert_resource(*value);
+            }
         }
     }
 }
@@ -42,7 +53,9 @@ impl MergingAsset for Config {
         match self {
             Config::Audio(_) => self.clone(),
             Config::Debug(_) => self.clone(),
+            Config::Spawning(_) => self.clone(),
             Config::World(_) => self.clone(),
+            Config::WorldGen(_) => self.clone(),
             Config::Input(value) => {
                 if let Some(Config::Input(accumulator)) = accumulator {
                     Config::Input(value.merge(Some(accumulator)))
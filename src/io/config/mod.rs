@@ -1,11 +1,15 @@
 pub use self::config_audio::AudioConfig;
 pub use self::config_debug::DebugConfig;
 pub use self::config_input::InputConfig;
+pub use self::config_spawning::SpawningConfig;
 pub use self::config_world::WorldConfig;
+pub use self::config_worldgen::WorldGenConfig;
 pub use self::configs::*;
 
 mod config_audio;
 mod config_debug;
 mod config_input;
+mod config_spawning;
 mod config_world;
+mod config_worldgen;
 mod configs;
@@ -32,6 +32,8 @@ pub enum MovementInput {
     Down,
     /// Enable faster movement while this is active.
     Sprint,
+    /// Switch between flying and walking.
+    ToggleFlying,
 }
 
 #[derive(InputAction, Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
@@ -48,3 +50,42 @@ pub enum DayNightInput {
     /// Sets time of day forward in increments of three hours.
     SetTimeForward,
 }
+
+#[derive(InputAction, Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(deny_unknown_fields)]
+pub enum WorldEditInput {
+    /// Set the first or second corner of the selection to the targeted block.
+    SelectCornerA,
+    SelectCornerB,
+    /// Select everything within the current radius of the targeted block.
+    SelectRadius,
+    /// Change the radius used by SelectRadius.
+    GrowRadius,
+    ShrinkRadius,
+    ClearSelection,
+    /// Cycle through the block types that are used to fill and replace.
+    NextBlock,
+    PreviousBlock,
+    /// Cycle through the block shapes that are used to fill and replace.
+    NextShape,
+    PreviousShape,
+    /// Add or remove the targeted block's type to the filter of blocks that Replace replaces.
+    ToggleFilter,
+    ClearFilter,
+    /// Set every block in the selection to the current block type.
+    Fill,
+    /// Set every block in the selection that passes the filter to the current block type.
+    Replace,
+    /// Clear out the inside of the selection, leaving only its outer shell.
+    Hollow,
+    /// Copy the selection to the clipboard.
+    Copy,
+    /// Paste the clipboard on top of the targeted block.
+    Paste,
+    /// Rotate the clipboard by 60 degrees for the next paste.
+    RotateClipboard,
+    /// Mirror the clipboard for the next paste.
+    MirrorClipboard,
+    Undo,
+    Redo,
+}
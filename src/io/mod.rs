@@ -3,3 +3,4 @@ pub mod audio;
 pub mod config;
 pub mod input;
 pub mod window;
+pub mod world_meta;
@@ -7,7 +7,7 @@ use bevy_egui::{EguiContext, EguiPlugin};
 use bevy_inspector_egui::{WorldInspectorParams, WorldInspectorPlugin};
 use iyes_loopless::prelude::ConditionSet;
 
-use crate::AppState;
+use crate::states::AppState;
 
 static SECOND_WINDOW_ID: Lazy<WindowId> = Lazy::new(WindowId::new);
 const SECONDARY_EGUI_PASS: &str = "secondary_egui_pass";
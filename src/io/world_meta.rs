@@ -0,0 +1,123 @@
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+use bevy::asset::FileAssetIo;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::game::hex_grid::chunk::ChunkDimensions;
+use crate::game::procedural_generation::world_generator::WorldPreset;
+use crate::io::config::{WorldConfig, WorldGenConfig};
+
+/// Worlds are saved in this directory, next to the assets.
+const SAVES_DIR: &str = "saves";
+/// The name of the world that is played. There is only one for now.
+const WORLD_NAME: &str = "world";
+const META_FILE: &str = "world.meta.ron";
+
+/// The settings that a world was created with. They are saved with the world when it is created,
+/// so that changing the configs afterwards only affects new worlds.
+#[derive(Resource, Debug, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct WorldMeta {
+    pub seed: u32,
+    pub preset: WorldPreset,
+    pub chunk_dimensions: ChunkDimensions,
+}
+
+/// The ways in which reading or writing the world's metadata can fail.
+#[derive(Debug)]
+pub enum WorldMetaError {
+    Io(PathBuf, std::io::Error),
+    /// The file exists, but isn't valid metadata.
+    Malformed(PathBuf, String),
+}
+
+impl Display for WorldMetaError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WorldMetaError::Io(path, err) => {
+                write!(f, "Could not access {}: {}", path.display(), err)
+            }
+            WorldMetaError::Malformed(path, err) => {
+                write!(f, "{} is malformed: {}", path.display(), err)
+            }
+        }
+    }
+}
+
+impl std::error::Error for WorldMetaError {}
+
+impl WorldMeta {
+    /// The settings for a new world, taken from the configs.
+    #[must_use]
+    pub fn from_configs(world: &WorldConfig, worldgen: &WorldGenConfig) -> Self {
+        Self {
+            seed: worldgen.seed,
+            preset: worldgen.preset.clone(),
+            chunk_dimensions: world.chunk_dimensions,
+        }
+    }
+
+    /// The directory that the world that is played is saved in.
+    #[must_use]
+    pub fn world_dir() -> PathBuf {
+        FileAssetIo::get_base_path()
+            .join(SAVES_DIR)
+            .join(WORLD_NAME)
+    }
+
+    /// Reads the metadata of the world saved in the directory. If there is no world there yet,
+    /// the new world's metadata is written there first.
+    pub fn load_or_create(
+        dir: &Path,
+        new_world: impl FnOnce() -> Self,
+    ) -> Result<Self, WorldMetaError> {
+        let path = dir.join(META_FILE);
+        match std::fs::read_to_string(&path) {
+            Ok(text) => {
+                ron::from_str(&text).map_err(|err| WorldMetaError::Malformed(path, err.to_string()))
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                let meta = new_world();
+                let text = ron::ser::to_string_pretty(&meta, ron::ser::PrettyConfig::default())
+                    .map_err(|err| WorldMetaError::Malformed(path.clone(), err.to_string()))?;
+                std::fs::create_dir_all(dir)
+                    .and_then(|_| std::fs::write(&path, text))
+                    .map_err(|err| WorldMetaError::Io(path, err))?;
+                info!("Created a new world in {}.", dir.display());
+                Ok(meta)
+            }
+            Err(err) => Err(WorldMetaError::Io(path, err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_world_keeps_the_settings_it_was_created_with() {
+        let dir = std::env::temp_dir().join(format!("hex_world_meta_{}", std::process::id()));
+        let created = WorldMeta {
+            seed: 7,
+            preset: WorldPreset::DebugGrid { spacing: 3 },
+            chunk_dimensions: ChunkDimensions {
+                radius: 4,
+                height: 16,
+            },
+        };
+        WorldMeta::load_or_create(&dir, || created.clone()).unwrap();
+        // Later on, the configs may say something else, but the saved world wins:
+        let reloaded = WorldMeta::load_or_create(&dir, || panic!("The world already exists"));
+        std::fs::remove_dir_all(&dir).unwrap();
+        let reloaded = reloaded.unwrap();
+        assert_eq!(reloaded.seed, created.seed);
+        assert_eq!(reloaded.chunk_dimensions, created.chunk_dimensions);
+        assert!(matches!(
+            reloaded.preset,
+            WorldPreset::DebugGrid { spacing: 3 }
+        ));
+    }
+}
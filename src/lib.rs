@@ -0,0 +1,12 @@
+//! The game itself. The hex binary runs it, and tools such as worldgen_preview reuse its parts.
+#![forbid(unsafe_code)]
+#![allow(
+    dead_code,
+    unused_variables,
+    clippy::type_complexity,
+    clippy::too_many_arguments
+)]
+
+pub mod game;
+pub mod io;
+pub mod states;
@@ -1,33 +1,20 @@
 #![forbid(unsafe_code)]
-#![allow(
-    dead_code,
-    unused_variables,
-    clippy::type_complexity,
-    clippy::too_many_arguments
-)]
 
 extern crate core;
 
-use crate::game::hex_grid::axial::{ChunkId, Pos};
-use crate::game::meshes::debug_lines::LineMaterial;
-use crate::io::window::handle_window;
-use crate::states::*;
 use bevy::log::Level;
 use bevy::prelude::*;
 use bevy::window::close_on_esc;
+use bevy_kira_audio::AudioPlugin;
+use hex::game::meshes::debug_lines::LineMaterial;
+use hex::io::window::handle_window;
+use hex::states::*;
 use iyes_loopless::prelude::{AppLooplessStateExt, CurrentState};
 
 #[cfg(feature = "debugwindow")]
-use crate::debug_window::DebugWindowPlugin;
-mod game;
-mod io;
-mod states;
+use hex::io::window::DebugWindowPlugin;
 
 fn main() {
-    // This is just setting up some static constants:
-    ChunkId::setup();
-    Pos::setup();
-
     let mut app = App::new();
     // this code is compiled only if debug assertions are enabled (debug mode)
     #[cfg(debug_assertions)]
@@ -46,6 +33,7 @@ fn main() {
         level: Level::ERROR,
     }));
     app.add_plugin(MaterialPlugin::<LineMaterial>::default())
+        .add_plugin(AudioPlugin)
         .add_system(close_on_esc)
         .add_loopless_state(AppState::Loading)
         .add_plugin(LoadingState)
@@ -6,19 +6,35 @@ use bevy::prelude::*;
 use iyes_loopless::condition::ConditionSet;
 use iyes_loopless::prelude::{AppLooplessFixedTimestepExt, AppLooplessStateExt};
 
+use crate::game::actors::health::{
+    apply_damage, fall_damage_system, handle_deaths, tick_invulnerability, DamageEvent, DeathEvent,
+};
+use crate::game::actors::monster::{monster_behaviour_system, monster_movement_system};
 use crate::game::actors::player::setup_player;
+use crate::game::actors::spawn_point::{find_spawn_point, SpawnPoint};
+use crate::game::actors::spawning::{
+    despawn_actors_in_unloaded_chunks, spawn_actors_in_loaded_chunks,
+};
 use crate::game::actors::structs::Player;
 use crate::game::camera::first_person::{
     cursor_grab, position_player_camera, rotate_player_camera,
 };
-use crate::game::hex_grid::axial::{ChunkId, Pos};
+use crate::game::hex_grid::axial::Pos;
 use crate::game::hex_grid::chunk_loading::{
-    check_chunk_loader, load_unload_chunks, LoadUnloadEvent,
+    check_chunk_loader, load_unload_chunks, remesh_dirty_chunks, setup_chunks, ChunkLoadedEvent,
+    ChunkUnloadedEvent, ChunksChangedEvent, LoadUnloadEvent,
 };
 use crate::game::hex_grid::chunks::Chunks;
 use crate::game::meshes::debug_lines::spawn_debug_lines;
 use crate::game::meshes::sun::{animate_sun, process_day_night_input, spawn_sun};
 use crate::game::movement::char_control::player_movement_system;
+use crate::game::movement::structs::LandedEvent;
+use crate::game::pathfinding::path_cache::{invalidate_path_cache, PathCache};
+use crate::game::world_edit::history::EditHistory;
+use crate::game::world_edit::tool::{
+    process_world_edit_operations, process_world_edit_selection, WorldEditor,
+};
+use crate::io::audio::{start_ambience, update_ambience};
 use crate::states::appstate::AppState;
 
 pub struct GameState;
@@ -26,15 +42,46 @@ pub struct GameState;
 impl Plugin for GameState {
     fn build(&self, app: &mut App) {
         app.insert_resource(Chunks::default());
+        app.insert_resource(SpawnPoint::default());
+        app.insert_resource(PathCache::default());
+        app.insert_resource(WorldEditor::default());
+        app.insert_resource(EditHistory::default());
         app.add_event::<LoadUnloadEvent>();
+        app.add_event::<ChunkLoadedEvent>();
+        app.add_event::<ChunkUnloadedEvent>();
+        app.add_event::<ChunksChangedEvent>();
+        app.add_event::<LandedEvent>();
+        app.add_event::<DamageEvent>();
+        app.add_event::<DeathEvent>();
+        // The world must be set up before a spawn point can be found in it,
+        // and the spawn point must be known before the player can be spawned:
         app.add_enter_system_set(
             AppState::Game,
             ConditionSet::new()
                 .run_in_state(AppState::Game)
+                .label("setup_chunks")
+                .with_system(setup_chunks)
+                .into(),
+        );
+        app.add_enter_system_set(
+            AppState::Game,
+            ConditionSet::new()
+                .run_in_state(AppState::Game)
+                .label("find_spawn_point")
+                .after("setup_chunks")
+                .with_system(find_spawn_point)
+                .into(),
+        );
+        app.add_enter_system_set(
+            AppState::Game,
+            ConditionSet::new()
+                .run_in_state(AppState::Game)
+                .after("find_spawn_point")
                 .with_system(cursor_grab)
                 .with_system(spawn_sun)
                 .with_system(setup_player)
                 .with_system(spawn_debug_lines)
+                .with_system(start_ambience)
                 // .with_system(spawn_test_grid)
                 .into(),
         )
@@ -42,6 +89,16 @@ impl Plugin for GameState {
         .add_fixed_timestep(Duration::from_millis(1000), "check_chunk_loading")
         .add_fixed_timestep_child_stage("check_chunk_loading")
         .add_fixed_timestep_system("check_chunk_loading", 0, check_chunk_loader)
+        // Blending ambient sounds doesn't need to happen every tick either:
+        .add_fixed_timestep(Duration::from_millis(1000), "update_ambience")
+        .add_fixed_timestep_system_set(
+            "update_ambience",
+            0,
+            ConditionSet::new()
+                .run_in_state(AppState::Game)
+                .with_system(update_ambience)
+                .into(),
+        )
         // The actual chunk loading is only done when a LoadUnloadEvent is sent:
         .add_stage_after(Update, "perform_chunk_loading", SystemStage::parallel())
         .add_system_set_to_stage(
@@ -52,12 +109,30 @@ impl Plugin for GameState {
                 .with_system(load_unload_chunks)
                 .into(),
         )
+        .add_system_set_to_stage(
+            "perform_chunk_loading",
+            ConditionSet::new()
+                .run_in_state(AppState::Game)
+                .with_system(remesh_dirty_chunks)
+                .into(),
+        )
         .add_system_set(
             ConditionSet::new()
                 .run_in_state(AppState::Game)
                 .with_system(process_day_night_input)
                 .with_system(animate_sun)
                 .with_system(player_movement_system)
+                .with_system(monster_behaviour_system)
+                .with_system(monster_movement_system)
+                .with_system(spawn_actors_in_loaded_chunks)
+                .with_system(despawn_actors_in_unloaded_chunks)
+                .with_system(fall_damage_system)
+                .with_system(apply_damage)
+                .with_system(handle_deaths)
+                .with_system(tick_invulnerability)
+                .with_system(invalidate_path_cache)
+                .with_system(process_world_edit_selection)
+                .with_system(process_world_edit_operations)
                 .with_system(rotate_player_camera)
                 .with_system(position_player_camera)
                 // .with_system(debug_print_coordinates)
@@ -67,10 +142,10 @@ impl Plugin for GameState {
 }
 
 /// For testing.
-fn debug_print_coordinates(query: Query<(&Transform, &Player)>) {
+fn debug_print_coordinates(query: Query<(&Transform, &Player)>, chunks: Res<Chunks>) {
     if let Ok((transform, _)) = query.get_single() {
         let pos = Pos::from_xyz(&transform.translation).as_ipos_round();
-        let chunk_pos = ChunkId::from_block_pos(&pos);
+        let chunk_pos = chunks.chunk_id(&pos);
         info!("Pos={:?}\tChunk={:?}", pos, chunk_pos);
         info!("Player is at {:?}", transform.translation);
     }
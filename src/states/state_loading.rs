@@ -4,6 +4,7 @@ use bevy::prelude::Commands;
 use iyes_loopless::condition::ConditionSet;
 use iyes_loopless::prelude::*;
 
+use crate::game::hex_grid::templates::{StructureLoader, StructureTemplate};
 use crate::io::asset_loading::*;
 use crate::io::config::{Config, ConfigLoader};
 use crate::io::input::process_input_bindings;
@@ -17,6 +18,8 @@ impl Plugin for LoadingState {
             .init_asset_loader::<MetaLoader>()
             .add_asset::<Config>()
             .init_asset_loader::<ConfigLoader>()
+            .add_asset::<StructureTemplate>()
+            .init_asset_loader::<StructureLoader>()
             .add_loopless_state(LoadProcess::StartLoading)
             .add_system_set(
                 ConditionSet::new()